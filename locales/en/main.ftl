@@ -0,0 +1,150 @@
+# English messages of arch-updates-rs. Every other catalog falls back to
+# these, so a translation may leave messages out.
+#
+# `{ $name }` stands for a variable the program fills in. Messages with a
+# count pick their variant with `{ $count -> ... }`, so translations can
+# have as many plural forms as their language needs.
+
+## Pending updates
+
+pending-updates = { $count ->
+    [one] { $count } pending update
+   *[other] { $count } pending updates
+}
+pending-updates-total = { $total ->
+    [one] { $total } pending update ({ $count } on this machine)
+   *[other] { $total } pending updates ({ $count } on this machine)
+}
+local-pending-updates = { $count ->
+    [one] { $prefix }{ $count } pending update
+   *[other] { $prefix }{ $count } pending updates
+}
+local-pending-updates-downloaded = { $count ->
+    [one] { $prefix }{ $count } pending update, downloaded
+   *[other] { $prefix }{ $count } pending updates, downloaded
+}
+section-pending-updates = { $count ->
+    [one] { $name }: { $count } pending update
+   *[other] { $name }: { $count } pending updates
+}
+section-check-failed = { $name }: check failed
+section-not-checked = { $name }: not checked yet
+aur-out-of-date = ⚠ flagged out-of-date since { $date }
+aur-orphaned = orphaned
+downloaded-ready = Downloaded, ready to install
+config-error = Config error, using the previous config
+state-paused = Checks are paused
+state-offline = Offline, showing the last result
+state-error = The last check failed: { $error }
+
+## How long ago something happened
+
+elapsed-just-now = just now
+elapsed-minutes = { $minutes } min ago
+elapsed-hours = { $hours } h ago
+elapsed-days = { $days ->
+    [one] { $days } day ago
+   *[other] { $days } days ago
+}
+last-checked = Last checked: { $elapsed }
+last-checked-never = Last checked: never
+last-upgraded = Last upgraded: { $elapsed }
+last-upgraded-unknown = Last upgraded: unknown
+oldest-pending = { $days ->
+    [one] Oldest pending: { $days } day
+   *[other] Oldest pending: { $days } days
+}
+oldest-pending-today = Oldest pending: less than a day
+oldest-pending-none = Oldest pending: none
+
+## Menu
+
+show-updates = Show updates…
+update-now = Update now
+update-now-keyring-first = Update now ({ $package } first)
+install-updates = Install updates
+mark-news-read = Mark news read
+mark-news-read-unread = Mark news read ({ $count } unread)
+acknowledge-all = Acknowledge all
+download-in-background = Download updates in the background
+check-now = Check now
+pause-checks = Pause checks
+resume-checks = Resume checks
+needs-rebuild = Needs rebuild: { $count }
+ignored-updates = Ignored by pacman.conf: { $count }
+needs-rebuild-not-checked = Needs rebuild: not checked yet
+maintenance = Maintenance
+clean-cache = Clean package cache
+remove-orphans = Remove orphaned packages
+debug = Debug
+save-menu-snapshot = Save menu snapshot
+copy-command = Copy command: { $label }
+
+## Updates window
+
+updates-window-title = { $count ->
+    [one] { $count } pending update
+   *[other] { $count } pending updates
+}
+updates-window-search = Search by name, repository or version
+
+## Statistics
+
+statistics = Statistics
+statistics-none = No statistics yet
+statistics-average = Average: { $average } updates per week
+statistics-biggest-batch = { $count ->
+    [one] Biggest batch (30 days): { $count } package
+   *[other] Biggest batch (30 days): { $count } packages
+}
+statistics-most-updated = Most updated packages:
+
+## Upgrades
+
+updating = Updating
+progress-installing = Installing { $done }/{ $total }: { $package }
+progress-removing = Removing { $done }/{ $total }: { $package }
+read-news-first = Read the Arch news before upgrading:
+partial-upgrade = Partial upgrade: { $packages } installed without a full upgrade, run pacman -Syu
+reboot-required = Reboot required: running kernel { $running }, installed { $installed }
+thread-restarted = The { $thread } thread was restarted because { $reason }
+keyring-first = Update { $package } first, an outdated keyring is the most common reason upgrades fail.
+
+## Advisories
+
+mirrorlist-old = { $months ->
+    [one] Mirrorlist is { $months } month old, consider refreshing it
+   *[other] Mirrorlist is { $months } months old, consider refreshing it
+}
+mirrorlist-old-stale-mirror = Mirrorlist is { $months } months old and the mirror's databases are { $days } days old, consider refreshing it
+low-space-root = Only { $free } MiB free on /, the updates need { $needed } MiB
+low-space-boot = Only { $free } MiB free on /boot, kernel updates need at least { $needed } MiB
+
+## Maintenance
+
+nothing-to-clean = Nothing to clean
+no-orphans = No orphaned packages
+preview-failed = Failed to determine what will change: { $error }
+confirm-will-run = This will run:
+confirm-cancel = Cancel
+confirm-run = Run
+confirm-dont-ask-again = Don't ask again
+
+## Notifications
+
+new-updates = { $count ->
+    [one] { $count } new update
+   *[other] { $count } new updates
+}
+more-updates = and { $count } more
+snooze = Snooze
+digest-summary = Your week in updates
+digest-body = This week: { $updated } packages updated, { $pending } currently pending, last full upgrade { $last_upgrade }
+digest-never = never
+already-running = arch-updates-rs is already running
+already-running-body = Another instance is already checking for updates.
+config-error-summary = arch-updates-rs config error
+config-error-body = Keeping the previous config.
+install-failed = Installing updates failed
+command-copied = Command copied
+command-copied-body = Your account can't run this as root. Run it from an account that can:
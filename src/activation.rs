@@ -0,0 +1,72 @@
+use std::{
+    env,
+    os::fd::{FromRawFd, OwnedFd, RawFd},
+    sync::{Mutex, OnceLock},
+};
+
+use tracing::info;
+
+/// The first file descriptor systemd passes, after stdin, stdout and stderr.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// `FileDescriptorName=` of the socket unit for the control socket.
+pub const CONTROL: &str = "control";
+/// `FileDescriptorName=` of the socket unit for the HTTP endpoint.
+#[cfg(feature = "http")]
+pub const HTTP: &str = "http";
+
+/// The sockets systemd passed by socket activation, by name.
+fn sockets() -> &'static Mutex<Vec<(String, OwnedFd)>> {
+    static SOCKETS: OnceLock<Mutex<Vec<(String, OwnedFd)>>> = OnceLock::new();
+
+    SOCKETS.get_or_init(|| Mutex::new(receive()))
+}
+
+/// Reads `LISTEN_FDS` and friends, as described in sd_listen_fds(3).
+fn receive() -> Vec<(String, OwnedFd)> {
+    // The variables may have been inherited from a parent that was started
+    // by socket activation.
+    let for_this_process = env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        == Some(std::process::id());
+    let count = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|count| count.parse::<RawFd>().ok())
+        .filter(|_| for_this_process)
+        .unwrap_or_default();
+    let names = env::var("LISTEN_FDNAMES").unwrap_or_default();
+
+    // Keeps the processes the program starts from taking them for theirs.
+    // SAFETY: called once, before there are other threads.
+    unsafe {
+        env::remove_var("LISTEN_PID");
+        env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_FDNAMES");
+    }
+
+    let mut names = names.split(':');
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(|fd| {
+            let name = names.next().unwrap_or_default().to_string();
+            info!("Received socket {:?} from systemd", name);
+            // SAFETY: systemd passes these open and to this process only.
+            (name, unsafe { OwnedFd::from_raw_fd(fd) })
+        })
+        .collect()
+}
+
+/// Receives the sockets systemd passed. Must be called before any threads
+/// are started, since it changes the environment.
+pub fn receive_sockets() {
+    sockets();
+}
+
+/// Takes the socket named `name` that systemd passed, if it passed one.
+pub fn take(name: &str) -> Option<OwnedFd> {
+    let mut sockets = sockets().lock().unwrap();
+    let index = sockets
+        .iter()
+        .position(|(socket_name, _)| socket_name == name)?;
+    Some(sockets.remove(index).1)
+}
@@ -0,0 +1,244 @@
+use std::{
+    env, fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{i18n::t, status::Severity};
+
+const MIRRORLIST_PATH: &str = "/etc/pacman.d/mirrorlist";
+
+const SECS_PER_DAY: u64 = 60 * 60 * 24;
+
+/// Months are counted as 30 days.
+const SECS_PER_MONTH: u64 = SECS_PER_DAY * 30;
+
+const MIB: u64 = 1024 * 1024;
+
+/// The units pacman prints sizes in.
+const SIZE_UNITS: &[(&str, f64)] = &[
+    ("B", 1.0),
+    ("KiB", 1024.0),
+    ("MiB", 1024.0 * 1024.0),
+    ("GiB", 1024.0 * 1024.0 * 1024.0),
+];
+
+/// Fields of `pacman -Si` and `pacman -Qi`.
+const INSTALLED_SIZE: &str = "Installed Size";
+const DOWNLOAD_SIZE: &str = "Download Size";
+
+/// The mirrors publish new databases several times a day, so databases this
+/// old mean a mirror stopped syncing.
+const STALE_DATABASE_DAYS: u64 = 3;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdvisoriesConfig {
+    /// Suggests refreshing the mirrorlist once it is this many months old.
+    /// `0` turns the advisory off.
+    pub mirrorlist_warning_months: u32,
+    /// Free space `/boot` needs for a new kernel and its initramfs. `0`
+    /// turns the check off.
+    pub boot_min_free_mib: u64,
+}
+
+impl Default for AdvisoriesConfig {
+    fn default() -> Self {
+        Self {
+            mirrorlist_warning_months: 6,
+            boot_min_free_mib: 100,
+        }
+    }
+}
+
+/// Something about the system the user should look into, shown in the menu
+/// below the pending updates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Advisory {
+    pub message: String,
+    /// The severity pending updates get at least while the advisory holds.
+    pub severity: Severity,
+}
+
+/// Looks for problems that aren't pending updates themselves, or that
+/// installing `updates` would run into.
+pub fn collect(config: &AdvisoriesConfig, updates: &[String]) -> Vec<Advisory> {
+    let mut advisories = Vec::new();
+    advisories.extend(mirrorlist(config));
+    advisories.extend(disk_space(config, updates));
+    advisories
+}
+
+/// Suggests refreshing a mirrorlist older than `mirrorlist_warning_months`.
+/// Mirrors that went out of date since are mentioned too.
+fn mirrorlist(config: &AdvisoriesConfig) -> Option<Advisory> {
+    if config.mirrorlist_warning_months == 0 {
+        return None;
+    }
+
+    let months = age_in_secs(Path::new(MIRRORLIST_PATH))? / SECS_PER_MONTH;
+    if months < config.mirrorlist_warning_months as u64 {
+        return None;
+    }
+
+    let message = match newest_database_age_in_secs() {
+        Some(database_age) if database_age >= STALE_DATABASE_DAYS * SECS_PER_DAY => t!(
+            "mirrorlist-old-stale-mirror",
+            months = months,
+            days = database_age / SECS_PER_DAY
+        ),
+        _ => t!("mirrorlist-old", months = months),
+    };
+
+    Some(Advisory {
+        message,
+        severity: Severity::Normal,
+    })
+}
+
+/// Checks that `/` has room for what the updates add to the installed size,
+/// and that `/boot` keeps `boot_min_free_mib` free. A kernel update that
+/// fills `/boot` leaves the system unbootable.
+fn disk_space(config: &AdvisoriesConfig, updates: &[String]) -> Vec<Advisory> {
+    let packages: Vec<&str> = updates
+        .iter()
+        .filter(|update| !update.ends_with("[ignored]"))
+        .filter_map(|update| update.split_whitespace().next())
+        .collect();
+    if packages.is_empty() {
+        return Vec::new();
+    }
+
+    let mut advisories = Vec::new();
+
+    match installed_size_growth(&packages) {
+        Ok(growth) => {
+            if let Some(free) = free_space(Path::new("/"))
+                && free < growth
+            {
+                advisories.push(Advisory {
+                    message: t!(
+                        "low-space-root",
+                        free = free / MIB,
+                        needed = growth.div_ceil(MIB)
+                    ),
+                    severity: Severity::Warning,
+                });
+            }
+        }
+        Err(e) => error!("Failed to compute the size of the updates: {:#}", e),
+    }
+
+    if config.boot_min_free_mib > 0
+        && let Some(free) = free_space(Path::new("/boot"))
+        && free < config.boot_min_free_mib * MIB
+    {
+        advisories.push(Advisory {
+            message: t!(
+                "low-space-boot",
+                free = free / MIB,
+                needed = config.boot_min_free_mib
+            ),
+            severity: Severity::Warning,
+        });
+    }
+
+    advisories
+}
+
+/// How many bytes the installed size of `packages` grows by when they are
+/// updated. The new sizes come from checkupdates' databases, since the
+/// system's may not have been synced since.
+fn installed_size_growth(packages: &[&str]) -> Result<u64> {
+    let mut sync_args = vec!["-Si".to_string()];
+    if let Some(db_path) = checkupdates_db_path().filter(|path| path.exists()) {
+        sync_args.push(format!("--dbpath={}", db_path.display()));
+    }
+
+    let new_size = summed_size(INSTALLED_SIZE, &sync_args, packages)?;
+    let old_size = summed_size(INSTALLED_SIZE, &["-Qi".to_string()], packages)?;
+    Ok(new_size.saturating_sub(old_size))
+}
+
+/// How many bytes downloading the pending `updates` takes. pacman leaves out
+/// the packages already in its cache.
+pub fn download_size(updates: &[String]) -> Result<u64> {
+    let packages: Vec<&str> = updates
+        .iter()
+        .filter_map(|update| update.split_whitespace().next())
+        .collect();
+    if packages.is_empty() {
+        return Ok(0);
+    }
+
+    let mut sync_args = vec!["-Si".to_string()];
+    if let Some(db_path) = checkupdates_db_path().filter(|path| path.exists()) {
+        sync_args.push(format!("--dbpath={}", db_path.display()));
+    }
+    summed_size(DOWNLOAD_SIZE, &sync_args, &packages)
+}
+
+/// The summed `field` sizes of `pacman <args> <packages>`.
+fn summed_size(field: &str, args: &[String], packages: &[&str]) -> Result<u64> {
+    // pacman fails if one of the packages isn't found, e.g. because it is
+    // new, but still prints the others.
+    let output = Command::new("pacman")
+        .args(args)
+        .args(packages)
+        .env("LC_ALL", "C")
+        .output()
+        .context("Failed to run pacman")?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix(field))
+        .filter_map(|value| parse_size(value.trim_start().trim_start_matches(':')))
+        .sum())
+}
+
+/// Parses sizes like "12.34 MiB".
+fn parse_size(size: &str) -> Option<u64> {
+    let (value, unit) = size.trim().split_once(' ')?;
+    let value: f64 = value.parse().ok()?;
+    let (_, factor) = SIZE_UNITS.iter().find(|(name, _)| *name == unit)?;
+    Some((value * factor) as u64)
+}
+
+fn free_space(path: &Path) -> Option<u64> {
+    fs2::available_space(path).ok()
+}
+
+/// How long ago the newest sync database was published. pacman keeps the
+/// mirror's modification time, and checkupdates syncs its copy of the
+/// databases on every check.
+fn newest_database_age_in_secs() -> Option<u64> {
+    fs::read_dir(checkupdates_db_path()?.join("sync"))
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "db"))
+        .filter_map(|path| age_in_secs(&path))
+        .min()
+}
+
+/// Where checkupdates keeps its copy of the databases: `CHECKUPDATES_DB`, or
+/// `checkup-db-<uid>` in the temporary directory.
+fn checkupdates_db_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("CHECKUPDATES_DB") {
+        return Some(PathBuf::from(path));
+    }
+
+    let uid = fs::metadata("/proc/self").ok()?.uid();
+    Some(env::temp_dir().join(format!("checkup-db-{}", uid)))
+}
+
+fn age_in_secs(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    modified.elapsed().ok().map(|age| age.as_secs())
+}
@@ -0,0 +1,101 @@
+use std::{sync::mpsc::Sender, thread};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use zbus::{
+    blocking::{Connection, Proxy},
+    zvariant::{OwnedValue, Value},
+};
+
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+
+/// The panel background the icons have to stand out against.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorScheme {
+    #[default]
+    Dark,
+    Light,
+}
+
+/// Which icon variant to use.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IconTheme {
+    /// Follows the desktop's dark mode setting from the settings portal.
+    #[default]
+    Auto,
+    Dark,
+    Light,
+}
+
+/// Reports the portal's color scheme to `tx` now and every time the user
+/// toggles dark mode.
+pub fn watch_color_scheme(tx: Sender<ColorScheme>) {
+    thread::spawn(move || {
+        if let Err(e) = watch(&tx) {
+            error!("Failed to follow the desktop color scheme: {}", e);
+        }
+    });
+}
+
+fn watch(tx: &Sender<ColorScheme>) -> Result<()> {
+    let connection = Connection::session().context("Failed to connect to the session bus")?;
+    let proxy = Proxy::new(
+        &connection,
+        "org.freedesktop.portal.Desktop",
+        "/org/freedesktop/portal/desktop",
+        "org.freedesktop.portal.Settings",
+    )
+    .context("Failed to create settings portal proxy")?;
+
+    // Subscribe first so a change right after reading the current value isn't missed.
+    let changes = proxy
+        .receive_signal("SettingChanged")
+        .context("Failed to subscribe to setting changes")?;
+
+    let value: OwnedValue = proxy
+        .call("ReadOne", &(APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY))
+        .context("Failed to read the color scheme")?;
+    let color_scheme = parse_color_scheme(&value);
+    info!("Desktop color scheme is {:?}", color_scheme);
+    if tx.send(color_scheme).is_err() {
+        return Ok(());
+    }
+
+    for message in changes {
+        let Ok((namespace, key, value)) =
+            message.body().deserialize::<(String, String, OwnedValue)>()
+        else {
+            continue;
+        };
+
+        if namespace != APPEARANCE_NAMESPACE || key != COLOR_SCHEME_KEY {
+            continue;
+        }
+
+        let color_scheme = parse_color_scheme(&value);
+        info!("Desktop color scheme changed to {:?}", color_scheme);
+        if tx.send(color_scheme).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// The portal reports 1 for "prefer dark", 2 for "prefer light" and 0 for no
+/// preference, which keeps the dark panel icons.
+fn parse_color_scheme(value: &Value) -> ColorScheme {
+    let preference = match value {
+        Value::Value(inner) => u32::try_from(&**inner),
+        value => u32::try_from(value),
+    };
+
+    match preference {
+        Ok(2) => ColorScheme::Light,
+        _ => ColorScheme::Dark,
+    }
+}
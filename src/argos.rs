@@ -0,0 +1,90 @@
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::{config::Config, control, i18n::t};
+
+/// Prints the status of the running instance in the format of the Argos
+/// (GNOME) and Kargos (KDE) extensions: the panel text, then `---` and the
+/// dropdown.
+pub fn print() -> Result<()> {
+    let status = match control::request_status(false) {
+        Ok(status) => serde_json::from_str(&status)?,
+        Err(e) => {
+            println!("⚠ | iconName=dialog-warning-symbolic");
+            println!("---");
+            println!("{}", escape(&format!("{:#}", e)));
+            return Ok(());
+        }
+    };
+
+    print!("{}", render(&status, &Config::load()?));
+    Ok(())
+}
+
+/// Renders the status JSON of the control socket.
+fn render(status: &Value, config: &Config) -> String {
+    let total = status["total_count"].as_u64().unwrap_or_default();
+    let state = status["state"].as_str().unwrap_or_default();
+    let icon = match (state, status["severity"].as_str()) {
+        ("error" | "offline", _) => "dialog-warning-symbolic",
+        ("checking" | "upgrading", _) => "view-refresh-symbolic",
+        (_, Some("critical")) => "software-update-urgent-symbolic",
+        _ if total > 0 => "software-update-available-symbolic",
+        _ => "emblem-ok-symbolic",
+    };
+
+    let mut lines = vec![format!("{} | iconName={}", total, icon), "---".to_string()];
+
+    let updates: Vec<&str> = status["updates"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    lines.push(escape(&t!("pending-updates", count = updates.len())));
+    for update in &updates {
+        lines.push(format!("--{} | font=monospace", escape(update)));
+    }
+    if let Some(error) = status["error"].as_str() {
+        lines.push(escape(&t!("state-error", error = error)));
+    }
+
+    let exe = std::env::current_exe()
+        .map(|exe| exe.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| env!("CARGO_PKG_NAME").to_string());
+    let updates: Vec<String> = updates.iter().map(|update| update.to_string()).collect();
+
+    lines.push("---".to_string());
+    lines.push(format!(
+        "{} | bash={} terminal=true",
+        escape(&t!("update-now")),
+        quote(&config.update_command_for(&updates))
+    ));
+    lines.push(format!(
+        "{} | bash={} terminal=false refresh=true",
+        escape(&t!("check-now")),
+        quote(&format!("{} check", exe))
+    ));
+    let (label, command) = if state == "paused" {
+        (t!("resume-checks"), "resume")
+    } else {
+        (t!("pause-checks"), "pause")
+    };
+    lines.push(format!(
+        "{} | bash={} terminal=false refresh=true",
+        escape(&label),
+        quote(&format!("{} {}", exe, command))
+    ));
+
+    lines.join("\n") + "\n"
+}
+
+/// Keeps `|` in text from starting the attributes.
+fn escape(text: &str) -> String {
+    text.replace('|', "│")
+}
+
+/// Quotes `command` for the `bash` attribute.
+fn quote(command: &str) -> String {
+    format!("'{}'", command.replace('\'', r"'\''"))
+}
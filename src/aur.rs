@@ -0,0 +1,143 @@
+use std::{
+    collections::HashMap,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use chrono::DateTime;
+use serde::Deserialize;
+
+use crate::i18n::t;
+
+pub const ID: &str = "aur";
+pub const NAME: &str = "AUR";
+
+const RPC_URL: &str = "https://aur.archlinux.org/rpc/v5/info";
+const RPC_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many packages are asked about per request, which keeps the URL short.
+const PACKAGES_PER_REQUEST: usize = 100;
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(default)]
+    results: Vec<Package>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Package {
+    name: String,
+    version: String,
+    /// When the package was flagged out-of-date, as a Unix timestamp.
+    out_of_date: Option<i64>,
+    /// `None` for orphaned packages.
+    maintainer: Option<String>,
+}
+
+/// Returns the foreign packages with a newer version in the AUR, formatted
+/// like checkupdates lines (`name old -> new`), and the ones that are
+/// flagged out-of-date or orphaned, annotated with the flags.
+pub fn check() -> Result<Vec<String>> {
+    // pacman exits with 1 when there are no foreign packages.
+    let output = Command::new("pacman")
+        .arg("-Qm")
+        .stderr(Stdio::null())
+        .output()
+        .context("Failed to run pacman")?;
+    let installed: Vec<(String, String)> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(name, version)| (name.to_string(), version.to_string()))
+        .collect();
+    if installed.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut packages = HashMap::new();
+    for chunk in installed.chunks(PACKAGES_PER_REQUEST) {
+        for package in info(chunk.iter().map(|(name, _)| name.as_str()))? {
+            packages.insert(package.name.clone(), package);
+        }
+    }
+
+    let mut lines = Vec::new();
+    // Packages that aren't in the AUR, like local builds, are left out.
+    for (name, installed_version) in &installed {
+        let Some(package) = packages.get(name) else {
+            continue;
+        };
+
+        // Most foreign packages are up to date, only the others need a
+        // vercmp process.
+        let line = if package.version != *installed_version
+            && is_newer(&package.version, installed_version)?
+        {
+            format!("{} {} -> {}", name, installed_version, package.version)
+        } else if package.out_of_date.is_some() || package.maintainer.is_none() {
+            format!("{} {}", name, installed_version)
+        } else {
+            continue;
+        };
+        let flags = flags(package);
+        if flags.is_empty() {
+            lines.push(line);
+        } else {
+            lines.push(format!("{} ({})", line, flags.join(", ")));
+        }
+    }
+
+    Ok(lines)
+}
+
+/// Asks the AUR RPC about `names`.
+fn info<'a>(names: impl Iterator<Item = &'a str>) -> Result<Vec<Package>> {
+    let agent = ureq::AgentBuilder::new().timeout(RPC_TIMEOUT).build();
+    let mut request = agent.get(RPC_URL);
+    for name in names {
+        request = request.query("arg[]", name);
+    }
+
+    let response = request
+        .call()
+        .context("Failed to query the AUR")?
+        .into_string()
+        .context("Failed to read the AUR response")?;
+    let response: Response =
+        serde_json::from_str(&response).context("Failed to parse the AUR response")?;
+    if let Some(error) = response.error {
+        bail!("The AUR refused the query: {}", error);
+    }
+
+    Ok(response.results)
+}
+
+/// What the AUR says about the package, e.g. "⚠ flagged out-of-date since
+/// 2024-05-01".
+fn flags(package: &Package) -> Vec<String> {
+    let mut flags = Vec::new();
+    if let Some(flagged_at) = package.out_of_date {
+        let date = DateTime::from_timestamp(flagged_at, 0)
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        flags.push(t!("aur-out-of-date", date = date));
+    }
+    if package.maintainer.is_none() {
+        flags.push(t!("aur-orphaned"));
+    }
+    flags
+}
+
+/// Whether `version` is newer than `installed`, compared like pacman does.
+fn is_newer(version: &str, installed: &str) -> Result<bool> {
+    let output = Command::new("vercmp")
+        .args([version, installed])
+        .output()
+        .context("Failed to run vercmp")?;
+    let comparison: i32 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .context("Failed to parse vercmp output")?;
+    Ok(comparison > 0)
+}
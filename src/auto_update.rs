@@ -0,0 +1,160 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use zbus::blocking::{Connection, Proxy};
+
+use crate::news;
+
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+/// NetworkManager's `NMMetered` values that mean the connection is metered.
+const NM_METERED_YES: u32 = 1;
+const NM_METERED_GUESS_YES: u32 = 3;
+
+/// Installing updates without asking, when it looks safe.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutoUpdateConfig {
+    /// Installs pending updates after a check through the same pkexec
+    /// command as "Install updates", which needs a polkit rule that lets
+    /// pacman run without a password.
+    pub enabled: bool,
+}
+
+/// Why the pending updates shouldn't be installed unattended right now,
+/// `None` if nothing speaks against it. Updates pacman ignores would leave the
+/// system partially upgraded. News published after `news_seen_at`, the last
+/// upgrade or when the news were marked read, count as unread.
+pub fn blocker(ignored_updates: &[String], news_seen_at: Option<DateTime<Utc>>) -> Option<String> {
+    match news::fetch() {
+        Ok(items) => {
+            let unread = news::published_after(&items, news_seen_at);
+            if !unread.is_empty() {
+                return Some(format!(
+                    "{} unread Arch news items, e.g. {:?}",
+                    unread.len(),
+                    unread[0].title
+                ));
+            }
+        }
+        // The news may announce manual steps, so not knowing them is unsafe.
+        Err(e) => return Some(format!("{:#}", e)),
+    }
+
+    if !ignored_updates.is_empty() {
+        let ignored: Vec<&str> = ignored_updates
+            .iter()
+            .filter_map(|update| update.split_whitespace().next())
+            .collect();
+        return Some(format!(
+            "Updates of ignored packages are pending: {}",
+            ignored.join(", ")
+        ));
+    }
+
+    if !on_ac_power() {
+        return Some("Running on battery".to_string());
+    }
+
+    if is_metered() {
+        return Some("The network connection is metered".to_string());
+    }
+
+    None
+}
+
+/// Whether the machine runs on mains power. Machines without a battery are
+/// assumed to.
+fn on_ac_power() -> bool {
+    let Ok(entries) = fs::read_dir(POWER_SUPPLY_DIR) else {
+        return true;
+    };
+
+    let mut has_battery = false;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match read_attribute(&path, "type").as_deref() {
+            Some("Mains") if read_attribute(&path, "online").as_deref() == Some("1") => {
+                return true;
+            }
+            // Batteries of mice and headsets have the scope "Device".
+            Some("Battery") if read_attribute(&path, "scope").as_deref() != Some("Device") => {
+                has_battery = true;
+            }
+            _ => {}
+        }
+    }
+
+    !has_battery
+}
+
+fn read_attribute(power_supply: &Path, attribute: &str) -> Option<String> {
+    fs::read_to_string(power_supply.join(attribute))
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+/// Whether NetworkManager considers the primary connection metered. Without
+/// NetworkManager the connection is assumed not to be.
+fn is_metered() -> bool {
+    match network_manager_metered() {
+        Ok(metered) => metered == NM_METERED_YES || metered == NM_METERED_GUESS_YES,
+        Err(e) => {
+            info!("Assuming an unmetered connection: {:#}", e);
+            false
+        }
+    }
+}
+
+fn network_manager_metered() -> Result<u32> {
+    let connection = Connection::system().context("Failed to connect to the system bus")?;
+    let proxy = Proxy::new(
+        &connection,
+        "org.freedesktop.NetworkManager",
+        "/org/freedesktop/NetworkManager",
+        "org.freedesktop.NetworkManager",
+    )
+    .context("Failed to create NetworkManager proxy")?;
+
+    proxy
+        .get_property("Metered")
+        .context("Failed to read whether the connection is metered")
+}
+
+/// Every automatic transaction is recorded in
+/// `~/.local/share/arch-updates-rs/auto-update.log`, since nobody watched it.
+pub fn log_transaction(message: &str) {
+    if let Err(e) = append_to_log(message) {
+        error!("Failed to write auto-update log: {:#}", e);
+    }
+}
+
+pub fn log_path() -> Result<PathBuf> {
+    match dirs::data_dir() {
+        Some(dir) => Ok(dir.join("arch-updates-rs").join("auto-update.log")),
+        None => bail!("Failed to get data directory"),
+    }
+}
+
+fn append_to_log(message: &str) -> Result<()> {
+    let log_path = log_path()?;
+    if let Some(dir) = log_path.parent() {
+        fs::create_dir_all(dir).context("Failed to create data directory")?;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .with_context(|| format!("Failed to open {:?}", log_path))?;
+    writeln!(file, "{} {}", Local::now().to_rfc3339(), message)?;
+
+    Ok(())
+}
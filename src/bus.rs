@@ -0,0 +1,78 @@
+use std::sync::{
+    Arc, Mutex,
+    mpsc::{Receiver, Sender, channel},
+};
+
+use crate::{config::Config, state::AppState, status::Status};
+
+/// A change of the app's state, for the frontends to show. Commands to the
+/// app go the other way, as `Event`s.
+#[derive(Debug, Clone)]
+pub enum StateUpdate {
+    /// The state changed, e.g. because a check started.
+    State(AppState),
+    /// What the running upgrade is doing, e.g. the last line pacman printed.
+    Progress(String),
+    /// The status changed, e.g. because a check finished.
+    Status(Box<Status>),
+    /// A check found that these packages were upgraded since the last one.
+    UpgradeDetected(Vec<String>),
+    /// The config file was reloaded.
+    Config(Box<Config>),
+    /// The app is shutting down.
+    Shutdown,
+}
+
+/// Broadcasts state updates from the main loop to every frontend that
+/// subscribed, like the tray. Also keeps the latest status for frontends
+/// that only read it on request, like the control socket.
+#[derive(Clone, Default)]
+pub struct Bus {
+    subscribers: Arc<Mutex<Vec<Sender<StateUpdate>>>>,
+    status: Arc<Mutex<Status>>,
+}
+
+impl Bus {
+    /// Returns a receiver for all updates published from now on.
+    pub fn subscribe(&self) -> Receiver<StateUpdate> {
+        let (tx, rx) = channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// The latest status. Read-only for everyone but the bus.
+    pub fn status(&self) -> Arc<Mutex<Status>> {
+        self.status.clone()
+    }
+
+    /// Sends `update` to every subscriber, forgetting the ones that are
+    /// gone.
+    pub fn publish(&self, update: StateUpdate) {
+        {
+            let mut status = self.status.lock().unwrap();
+            match &update {
+                StateUpdate::State(state) => status.state = state.clone(),
+                StateUpdate::Progress(progress) => status.progress = Some(progress.clone()),
+                StateUpdate::Status(new_status) => *status = (**new_status).clone(),
+                StateUpdate::UpgradeDetected(_)
+                | StateUpdate::Config(_)
+                | StateUpdate::Shutdown => {}
+            }
+        }
+
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(update.clone()).is_ok());
+    }
+
+    /// Publishes the state of `status`.
+    pub fn publish_state(&self, status: &Status) {
+        self.publish(StateUpdate::State(status.state.clone()));
+    }
+
+    /// Publishes `status` as a whole.
+    pub fn publish_status(&self, status: &Status) {
+        self.publish(StateUpdate::Status(Box::new(status.clone())));
+    }
+}
@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use chrono::{Datelike, Days, Local, NaiveDate, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, status::Status};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalendarConfig {
+    pub enabled: bool,
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+}
+
+/// A recurring weekly slot the user reserves for system maintenance.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MaintenanceWindow {
+    pub weekday: Weekday,
+    pub start: NaiveTime,
+    pub duration_minutes: u32,
+    #[serde(default = "default_window_summary")]
+    pub summary: String,
+}
+
+fn default_window_summary() -> String {
+    "Arch Linux maintenance".to_string()
+}
+
+/// Writes an iCalendar file with the configured maintenance windows and a
+/// reminder for when the system should be upgraded next.
+pub fn write(config: &Config, status: &Status) -> Result<PathBuf> {
+    let path = match dirs::data_dir() {
+        Some(dir) => dir.join("arch-updates-rs").join("maintenance.ics"),
+        None => {
+            bail!("Failed to get data directory");
+        }
+    };
+
+    std::fs::create_dir_all(path.parent().unwrap())
+        .context("Failed to create calendar directory")?;
+    std::fs::write(&path, render(config, status)).context("Failed to write calendar")?;
+
+    Ok(path)
+}
+
+fn render(config: &Config, status: &Status) -> String {
+    let today = Local::now().date_naive();
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//arch-updates-rs//maintenance//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+
+    for (index, window) in config.calendar.maintenance_windows.iter().enumerate() {
+        let first_date = next_weekday(today, window.weekday);
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:maintenance-window-{}@arch-updates-rs", index),
+            format!("DTSTAMP:{}", stamp),
+            format!(
+                "DTSTART:{}T{}",
+                first_date.format("%Y%m%d"),
+                window.start.format("%H%M%S")
+            ),
+            format!("DURATION:PT{}M", window.duration_minutes),
+            format!("RRULE:FREQ=WEEKLY;BYDAY={}", weekday_code(window.weekday)),
+            format!("SUMMARY:{}", escape_text(&window.summary)),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+
+    if config.upgrade_warning_days > 0
+        && let Some(last_upgrade) = status.last_upgrade
+    {
+        let due = last_upgrade.with_timezone(&Local).date_naive()
+            + Days::new(config.upgrade_warning_days as u64);
+        let due = due.max(today);
+
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            "UID:upgrade-reminder@arch-updates-rs".to_string(),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART;VALUE=DATE:{}", due.format("%Y%m%d")),
+            format!("DTEND;VALUE=DATE:{}", (due + Days::new(1)).format("%Y%m%d")),
+            format!(
+                "SUMMARY:Upgrade Arch Linux ({} pending updates)",
+                status.updates.len()
+            ),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut calendar = lines.join("\r\n");
+    calendar.push_str("\r\n");
+    calendar
+}
+
+fn next_weekday(from: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let days_ahead =
+        (7 + weekday.num_days_from_monday() - from.weekday().num_days_from_monday()) % 7;
+    from + Days::new(days_ahead as u64)
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
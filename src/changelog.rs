@@ -0,0 +1,93 @@
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+use tracing::info;
+
+use crate::{config::Config, terminal};
+
+/// Menu IDs of pending updates start with this, followed by the package name.
+pub const MENU_ID_PREFIX: &str = "changelog:";
+
+const GITLAB_PACKAGES_URL: &str = "https://gitlab.archlinux.org/archlinux/packaging/packages";
+const AUR_URL: &str = "https://aur.archlinux.org/cgit/aur.git/log/";
+
+/// The menu ID of an update line like `linux 6.9.1-1 -> 6.9.2-1`.
+pub fn menu_id(update: &str) -> Option<String> {
+    let package = update.split_whitespace().next()?;
+    Some(format!("{}{}", MENU_ID_PREFIX, package))
+}
+
+/// Shows what changed in `package`: the changelog the installed package
+/// ships in a terminal, or else the packaging commits in the browser, on
+/// GitLab for the official repositories and on the AUR for the rest.
+pub fn show(config: &Config, package: &str) -> Result<()> {
+    if has_changelog(package) {
+        return terminal::launch(
+            config.terminal.as_deref(),
+            &config.window,
+            &format!("pacman -Qc {} | ${{PAGER:-less}}", package),
+        );
+    }
+
+    let url = commits_url(package);
+    info!("Opening {}", url);
+    Command::new("xdg-open")
+        .arg(&url)
+        .stdin(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to open {}", url))?;
+
+    Ok(())
+}
+
+/// Whether the installed `package` ships a changelog. Most official
+/// packages don't, pacman then fails.
+fn has_changelog(package: &str) -> bool {
+    Command::new("pacman")
+        .args(["-Qc", package])
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|output| output.status.success() && !output.stdout.is_empty())
+}
+
+/// The commit log of the package's build files.
+fn commits_url(package: &str) -> String {
+    // Only the sync databases know packages of the official repositories.
+    let Some(base) = sync_base(package) else {
+        return format!("{}?h={}", AUR_URL, package);
+    };
+
+    format!(
+        "{}/{}/-/commits/main",
+        GITLAB_PACKAGES_URL,
+        gitlab_project(&base)
+    )
+}
+
+/// The package base of `package` in the sync databases, which names its
+/// repository of build files.
+fn sync_base(package: &str) -> Option<String> {
+    let output = Command::new("pacman")
+        .args(["-Si", package])
+        .env("LC_ALL", "C")
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let info = String::from_utf8_lossy(&output.stdout);
+    let base = info
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim() == "Base")
+        .map(|(_, value)| value.trim().to_string());
+    Some(base.unwrap_or_else(|| package.to_string()))
+}
+
+/// GitLab project names can't contain `+`, so e.g. `libsigc++` is
+/// `libsigcplusplus`.
+fn gitlab_project(base: &str) -> String {
+    base.replace("++", "plusplus").replace('+', "plus")
+}
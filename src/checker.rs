@@ -0,0 +1,88 @@
+use std::{
+    sync::{Arc, mpsc::Sender},
+    thread,
+};
+
+use tracing::error;
+
+use crate::{
+    Event,
+    advisories::{self, Advisory},
+    check_updates,
+    config::Config,
+    filters::{self, Filtered},
+    history::History,
+    kernel::{self, KernelMismatch},
+    news::{self, NewsItem},
+    remote::{self, HostStatus},
+    sources::{SourceStatus, Sources},
+};
+
+/// What a check found besides the state the main loop keeps itself.
+pub struct CheckResult {
+    pub filtered: Filtered,
+    pub hosts: Vec<HostStatus>,
+    pub sources: Vec<SourceStatus>,
+    pub advisories: Vec<Advisory>,
+    /// Only estimated if one of the download size thresholds is set.
+    pub download_size: Option<u64>,
+    /// The news feed, if there were updates to hold back and it could be
+    /// fetched.
+    pub news: Option<Vec<NewsItem>>,
+    pub kernel_mismatch: Option<KernelMismatch>,
+}
+
+/// Runs checkupdates, the additional sources and everything else that
+/// depends on the network or other programs on a thread of its own, so the
+/// main loop keeps handling events in the meantime. Reports as
+/// `Event::Checked`, with the error if checkupdates failed.
+pub fn start(config: Config, sources: Arc<Sources>, tx: Sender<Event>) {
+    thread::spawn(move || {
+        let result = match check_updates(false) {
+            Ok(updates) => Ok(Box::new(collect(updates, &config, &sources))),
+            Err(e) => Err(format!("{:#}", e)),
+        };
+        let _ = tx.send(Event::Checked(result));
+    });
+}
+
+fn collect(updates: Vec<String>, config: &Config, sources: &Sources) -> CheckResult {
+    // Also resolves the `IgnoreGroup`s with `pacman -Sg`.
+    let mut filtered = filters::apply(updates, config);
+    // The main loop keeps its own connection, SQLite serializes the writes.
+    if let Ok(history) = History::open() {
+        filters::hold_back_recent(&mut filtered, &history, config);
+    }
+
+    let hosts = remote::check_all(config);
+    let sources = sources.check_all(&config.sources);
+    let advisories = advisories::collect(&config.advisories, &filtered.updates);
+    let download_size = (config.warning_download_mib > 0 || config.critical_download_mib > 0)
+        .then(|| advisories::download_size(&filtered.updates))
+        .and_then(|download_size| match download_size {
+            Ok(download_size) => Some(download_size),
+            Err(e) => {
+                error!("Failed to estimate the download size: {:#}", e);
+                None
+            }
+        });
+    let news = (config.news.enabled && !filtered.updates.is_empty())
+        .then(news::fetch)
+        .and_then(|items| match items {
+            Ok(items) => Some(items),
+            Err(e) => {
+                error!("{:#}", e);
+                None
+            }
+        });
+
+    CheckResult {
+        filtered,
+        hosts,
+        sources,
+        advisories,
+        download_size,
+        news,
+        kernel_mismatch: kernel::mismatch(),
+    }
+}
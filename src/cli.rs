@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Parser)]
+#[command(version, about = "Tray icon showing pending Arch Linux updates")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// What is logged, e.g. `debug` or `info,arch_updates_rs::tray=debug`.
+    /// Overrides `RUST_LOG` and `level` in the `[log]` section of the config.
+    #[arg(long, value_name = "FILTER")]
+    pub log_level: Option<String>,
+    /// Write the log to this file instead of `arch-updates-rs.log` in the
+    /// runtime directory. Overrides `file` in the `[log]` section of the
+    /// config.
+    #[arg(long, value_name = "PATH", conflicts_with = "no_log_file")]
+    pub log_file: Option<PathBuf>,
+    /// Don't write the log to a file.
+    #[arg(long)]
+    pub no_log_file: bool,
+    /// Make the running instance exit and take its place.
+    #[arg(long)]
+    pub replace: bool,
+    /// Check once, print the pending updates and exit with 0 without
+    /// updates, 10 with updates, 11 at the warning threshold, 12 at the
+    /// critical threshold and 1 on errors.
+    #[arg(long, conflicts_with = "replace")]
+    pub oneshot: bool,
+    /// Print a JSON object per line on stdout for every change, like
+    /// `{"event": "state", "state": "checking", ...}`.
+    #[arg(long, conflicts_with = "oneshot")]
+    pub json_stream: bool,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Tell running instances about a pacman transaction. Used by the alpm hooks.
+    PacmanHook {
+        #[arg(value_enum)]
+        when: HookWhen,
+    },
+    /// Print the last known status of the running instance as JSON, without
+    /// waiting for a check.
+    Status {
+        /// Print a one-line summary for bars instead, shortened to the
+        /// configured `max_width`.
+        #[arg(long)]
+        text: bool,
+        /// Print the status and menu actions for the Argos or Kargos
+        /// extensions instead.
+        #[arg(long, conflicts_with = "text")]
+        argos: bool,
+    },
+    /// Print the update statistics of the running instance.
+    Stats {
+        /// Print counters about the daemon itself instead: events processed,
+        /// checks run, failures, coalesced watcher events and memory usage.
+        #[arg(long)]
+        internal: bool,
+    },
+    /// Make the running instance check for updates now. The next scheduled
+    /// check is then a full interval later.
+    Check,
+    /// Pause the checks of the running instance, until `resume`.
+    Pause,
+    /// Resume the checks of the running instance, and check right away.
+    Resume,
+    /// Follow the running instance as an i3bar/swaybar `status_command`.
+    /// Left clicks check now, right clicks pause or resume.
+    Swaybar,
+    /// Follow the running instance, printing a line of text in the
+    /// `[template]` format on every change. For xmobar, dzen and conky.
+    Template {
+        /// Use this format instead of the configured one, e.g.
+        /// `"{icon} {count}"`.
+        #[arg(long)]
+        format: Option<String>,
+    },
+    /// Check or print the config file.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Report parse errors and unknown keys in the config file.
+    Validate,
+    /// Print the effective config, including the defaults of unset options.
+    Dump,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum HookWhen {
+    Pre,
+    Post,
+}
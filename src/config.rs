@@ -0,0 +1,1004 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::mpsc::{Sender, channel},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{Context, Result, bail};
+use notify::{Event as NotifyEvent, EventKind, RecursiveMode, Result as NotifyResult, Watcher};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+use toml::Table;
+use tracing::{error, info, info_span, warn};
+
+use crate::{
+    Event,
+    advisories::AdvisoriesConfig,
+    auto_update::AutoUpdateConfig,
+    calendar::CalendarConfig,
+    digest::DigestConfig,
+    eww::EwwConfig,
+    hooks::HooksConfig,
+    housekeeping::HousekeepingConfig,
+    hyprland::HyprlandConfig,
+    install::InstallConfig,
+    logging::LogConfig,
+    migrations,
+    mqtt::MqttConfig,
+    news::NewsConfig,
+    notification::NotificationConfig,
+    outputs::ThrottleConfig,
+    pacman_conf,
+    rebuild::RebuildConfig,
+    remote::RemoteHost,
+    schedule::CronSchedule,
+    sound::SoundConfig,
+    sources::SourcesConfig,
+    status,
+    summary::SummaryConfig,
+    supervisor,
+    template::TemplateConfig,
+    terminal::{self, WindowHints},
+    tray::{IconConfig, TrayBackend, WidgetConfig},
+    version::EpochDisplay,
+    webhook::WebhookConfig,
+};
+
+/// Names of the config file in order of preference. The format is detected
+/// by the extension, new files are written as TOML.
+const CONFIG_FILE_NAMES: &[&str] = &["config.toml", "config.json", "config.yaml", "config.yml"];
+
+/// Environment variables starting with this override single options, e.g.
+/// `ARCH_UPDATES_RS_INTERVAL=60`.
+const ENV_PREFIX: &str = "ARCH_UPDATES_RS_";
+
+/// Keys that were renamed, with their current name. The old names are still
+/// read, with a warning.
+const DEPRECATED_KEYS: &[(&str, &str)] = &[("inverval_in_seconds", "interval_in_seconds")];
+
+/// How similar an unknown key has to be to a known one to suggest it.
+const SUGGESTION_SIMILARITY: f64 = 0.8;
+
+/// The formats the config file can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => ConfigFormat::Json,
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    /// Parses `contents`. The errors of all formats include the line and
+    /// column of the problem.
+    pub fn parse<T: DeserializeOwned>(self, contents: &str) -> Result<T> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::from_str(contents)?,
+            ConfigFormat::Json => serde_json::from_str(contents)?,
+            ConfigFormat::Yaml => serde_yaml::from_str(contents)?,
+        })
+    }
+
+    /// Parses `contents` into a table, for looking at the keys before
+    /// deserializing. TOML has no null, so nulls in JSON and YAML are left
+    /// out, like the options they stand for.
+    pub fn parse_table(self, contents: &str) -> Result<Table> {
+        if self == ConfigFormat::Toml {
+            return self.parse(contents);
+        }
+
+        let mut value: serde_json::Value = self.parse(contents)?;
+        remove_nulls(&mut value);
+        match toml::Value::try_from(value)? {
+            toml::Value::Table(table) => Ok(table),
+            _ => bail!("The config must be a map of options"),
+        }
+    }
+
+    pub fn to_string<T: Serialize>(self, value: &T) -> Result<String> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::to_string(value)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(value)?,
+            ConfigFormat::Yaml => serde_yaml::to_string(value)?,
+        })
+    }
+}
+
+fn remove_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(remove_nulls);
+        }
+        serde_json::Value::Array(values) => {
+            values.retain(|value| !value.is_null());
+            values.iter_mut().for_each(remove_nulls);
+        }
+        _ => {}
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Schema version of the file, see `migrations`.
+    pub config_version: i64,
+    #[serde(alias = "inverval_in_seconds")]
+    pub interval_in_seconds: u32,
+    /// How much each interval may randomly deviate, in percent.
+    pub interval_jitter_percent: u32,
+    /// Download pending updates into the pacman cache in the background.
+    pub download_updates: bool,
+    /// A cron expression to check at, instead of every interval.
+    pub schedule: Option<CronSchedule>,
+    pub warning_threshold: u32,
+    pub critical_threshold: u32,
+    /// Thresholds for a single source (`pacman`, `developer_tools`,
+    /// `python_tools`, `snap` or `aur`) that replace the global ones.
+    pub thresholds: HashMap<String, Thresholds>,
+    /// Download sizes of the pending updates, in MiB, at which the icon
+    /// shows at least the warning and critical level. `0` turns them off.
+    pub warning_download_mib: u64,
+    pub critical_download_mib: u64,
+    /// Repositories whose updates are left out, e.g. `multilib`.
+    pub ignore_repos: Vec<String>,
+    /// Lists the updates pacman.conf ignores in a submenu of their own,
+    /// instead of leaving them out. They don't count either way.
+    pub show_ignored_updates: bool,
+    /// Updates only count once they were pending this long, for lagging
+    /// behind the newest releases on purpose.
+    pub min_update_age_hours: u32,
+    pub upgrade_warning_days: u32,
+    /// Pending updates show at least the warning level once one of them was
+    /// pending this long. `0` turns it off.
+    pub pending_warning_days: u32,
+    /// Writes to the pacman database closer together than this count as one
+    /// transaction.
+    pub watch_debounce_ms: u64,
+    pub post_update_settle_secs: u64,
+    pub post_update_recheck_secs: u64,
+    pub http_address: Option<String>,
+    /// pacman's database directory. Defaults to `DBPath` from pacman.conf.
+    pub pacman_db_path: Option<PathBuf>,
+    /// The terminal to run commands in, with `{cmd}` standing for the
+    /// command. Defaults to the first installed of foot, alacritty, kitty and
+    /// xterm.
+    pub terminal: Option<String>,
+    /// What **Update now** runs in the terminal.
+    pub update_command: String,
+    pub window: WindowHints,
+    pub webhook: WebhookConfig,
+    pub calendar: CalendarConfig,
+    pub eww: EwwConfig,
+    pub hyprland: HyprlandConfig,
+    pub template: TemplateConfig,
+    pub mqtt: MqttConfig,
+    pub hooks: HooksConfig,
+    pub auto_update: AutoUpdateConfig,
+    pub news: NewsConfig,
+    pub advisories: AdvisoriesConfig,
+    pub housekeeping: HousekeepingConfig,
+    pub log: LogConfig,
+    pub install: InstallConfig,
+    pub rebuild: RebuildConfig,
+    pub notifications: NotificationConfig,
+    pub sound: SoundConfig,
+    pub digest: DigestConfig,
+    pub remote_hosts: Vec<RemoteHost>,
+    pub epoch_display: EpochDisplay,
+    pub sources: SourcesConfig,
+    pub icon: IconConfig,
+    pub tray_backend: TrayBackend,
+    pub widget: WidgetConfig,
+    pub summary: SummaryConfig,
+    pub throttle: ThrottleConfig,
+    /// Actions that run without asking first are set to `false`.
+    pub confirm: HashMap<String, bool>,
+    pub debug_menu: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Thresholds {
+    pub warning: Option<u32>,
+    pub critical: Option<u32>,
+}
+
+impl Config {
+    /// What **Update now** runs for `updates`: the `update_command`, after
+    /// updating the keyring if a new one is pending.
+    pub fn update_command_for(&self, updates: &[String]) -> String {
+        if status::keyring_pending(updates) {
+            format!(
+                "{} && {}",
+                terminal::KEYRING_UPDATE_COMMAND,
+                self.update_command
+            )
+        } else {
+            self.update_command.clone()
+        }
+    }
+
+    /// The warning and critical thresholds of `source`, falling back to the
+    /// global ones.
+    pub fn thresholds_for(&self, source: &str) -> (u32, u32) {
+        let thresholds = self.thresholds.get(source).copied().unwrap_or_default();
+        (
+            thresholds.warning.unwrap_or(self.warning_threshold),
+            thresholds.critical.unwrap_or(self.critical_threshold),
+        )
+    }
+
+    pub fn pacman_db_path(&self) -> PathBuf {
+        self.pacman_db_path
+            .clone()
+            .unwrap_or_else(pacman_conf::db_path)
+    }
+
+    /// The options that differ from `other` but are only read at startup,
+    /// like the ones that decide which menu entries exist.
+    pub fn restart_required(&self, other: &Config) -> Vec<&'static str> {
+        [
+            ("http_address", self.http_address != other.http_address),
+            (
+                "pacman_db_path",
+                self.pacman_db_path != other.pacman_db_path,
+            ),
+            (
+                "watch_debounce_ms",
+                self.watch_debounce_ms != other.watch_debounce_ms,
+            ),
+            ("tray_backend", self.tray_backend != other.tray_backend),
+            ("widget", differs(&self.widget, &other.widget)),
+            ("debug_menu", self.debug_menu != other.debug_menu),
+            (
+                "show_ignored_updates",
+                self.show_ignored_updates != other.show_ignored_updates,
+            ),
+            (
+                "install.enabled",
+                self.install.enabled != other.install.enabled,
+            ),
+            ("news.enabled", self.news.enabled != other.news.enabled),
+            (
+                "rebuild.enabled",
+                self.rebuild.enabled != other.rebuild.enabled,
+            ),
+            (
+                "remote_hosts",
+                differs(&self.remote_hosts, &other.remote_hosts),
+            ),
+            ("sources", differs(&self.sources, &other.sources)),
+            ("icon", differs(&self.icon, &other.icon)),
+            ("summary", differs(&self.summary, &other.summary)),
+        ]
+        .into_iter()
+        .filter_map(|(key, changed)| changed.then_some(key))
+        .collect()
+    }
+
+    /// Whether the action with `action_id` asks for confirmation first.
+    pub fn needs_confirmation(&self, action_id: &str) -> bool {
+        self.confirm.get(action_id).copied().unwrap_or(true)
+    }
+
+    fn create_default_config(config_path: &Path) -> Result<Self> {
+        let config = Self::default();
+        let config_contents =
+            toml::to_string(&config).context("Failed to serialize default config")?;
+        match std::fs::write(config_path, config_contents) {
+            Ok(_) => {
+                info!("Created default config file at {:?}", config_path);
+            }
+            Err(e) => {
+                error!(
+                    "Failed to create default config file at {:?}: {}",
+                    config_path, e
+                );
+            }
+        }
+        Ok(config)
+    }
+    /// The config file in use: the first of `CONFIG_FILE_NAMES` that exists
+    /// in `~/.config/arch-updates-rs/`, or the file at the old location if it
+    /// couldn't be moved from there. Defaults to `config.toml`.
+    pub fn path() -> Result<PathBuf> {
+        let (config_dir, legacy_path) = Self::locations()?;
+        if let Some(config_path) = Self::existing_file(&config_dir) {
+            return Ok(config_path);
+        }
+        if legacy_path.exists() {
+            return Ok(legacy_path);
+        }
+        Ok(config_dir.join(CONFIG_FILE_NAMES[0]))
+    }
+
+    /// The current config directory and the old location of the config file.
+    /// It used to live next to the Hyprland config, which only made sense for
+    /// Hyprland users.
+    fn locations() -> Result<(PathBuf, PathBuf)> {
+        match dirs::config_dir() {
+            Some(dir) => Ok((
+                dir.join("arch-updates-rs"),
+                dir.join("hypr").join("arch-updates-rs.toml"),
+            )),
+            None => {
+                bail!("Failed to get config directory");
+            }
+        }
+    }
+
+    fn existing_file(config_dir: &Path) -> Option<PathBuf> {
+        CONFIG_FILE_NAMES
+            .iter()
+            .map(|name| config_dir.join(name))
+            .find(|path| path.exists())
+    }
+
+    /// Moves a config file from the old location to the current one.
+    fn move_legacy_config() -> Result<()> {
+        let (config_dir, legacy_path) = Self::locations()?;
+        if Self::existing_file(&config_dir).is_some() || !legacy_path.exists() {
+            return Ok(());
+        }
+
+        let config_path = config_dir.join(CONFIG_FILE_NAMES[0]);
+        std::fs::rename(&legacy_path, &config_path).with_context(|| {
+            format!(
+                "Failed to move config from {:?} to {:?}",
+                legacy_path, config_path
+            )
+        })?;
+
+        info!("Moved config from {:?} to {:?}", legacy_path, config_path);
+        Ok(())
+    }
+
+    pub fn load() -> Result<Self> {
+        std::fs::create_dir_all(Self::locations()?.0)
+            .context("Failed to create config directory")?;
+
+        if let Err(e) = Self::move_legacy_config() {
+            warn!("{:#}. Reading it from the old location", e);
+        }
+
+        let config_path = Self::path()?;
+
+        let mut config = if config_path.exists() {
+            Self::read(&config_path)?
+        } else {
+            Self::create_default_config(&config_path)?
+        };
+        config.apply_env_overrides()?;
+
+        Ok(config)
+    }
+
+    /// Reads the config file again after it changed. Unlike `load` this
+    /// never falls back to the defaults, so a deleted or broken file doesn't
+    /// silently reset the running config.
+    pub fn reload() -> Result<Self> {
+        let mut config = Self::read(&Self::path()?)?;
+        config.apply_env_overrides()?;
+        Ok(config)
+    }
+
+    /// Replaces options with the `ARCH_UPDATES_RS_*` environment variables
+    /// that are set, so a systemd drop-in or a test run can change them
+    /// without editing the file.
+    fn apply_env_overrides(&mut self) -> Result<()> {
+        self.apply_overrides(|variable| std::env::var(variable).ok())
+    }
+
+    /// Replaces options with the `ARCH_UPDATES_RS_*` variables `lookup`
+    /// returns a value for.
+    fn apply_overrides(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(interval) = env_override(&lookup, "INTERVAL")? {
+            self.interval_in_seconds = interval;
+        }
+        if let Some(threshold) = env_override(&lookup, "WARNING_THRESHOLD")? {
+            self.warning_threshold = threshold;
+        }
+        if let Some(threshold) = env_override(&lookup, "CRITICAL_THRESHOLD")? {
+            self.critical_threshold = threshold;
+        }
+        if let Some(days) = env_override(&lookup, "UPGRADE_WARNING_DAYS")? {
+            self.upgrade_warning_days = days;
+        }
+        if let Some(debounce) = env_override(&lookup, "WATCH_DEBOUNCE_MS")? {
+            self.watch_debounce_ms = debounce;
+        }
+        if let Some(address) = env_override(&lookup, "HTTP_ADDRESS")? {
+            self.http_address = Some(address);
+        }
+        if let Some(path) = env_override(&lookup, "PACMAN_DB_PATH")? {
+            self.pacman_db_path = Some(path);
+        }
+        if let Some(enabled) = env_override(&lookup, "NOTIFICATIONS")? {
+            self.notifications.enabled = enabled;
+        }
+        if let Some(debug_menu) = env_override(&lookup, "DEBUG_MENU")? {
+            self.debug_menu = debug_menu;
+        }
+        Ok(())
+    }
+
+    fn read(config_path: &Path) -> Result<Self> {
+        let config_contents = match std::fs::read_to_string(config_path) {
+            Ok(contents) => contents,
+            Err(_) => {
+                bail!("Failed to read config file");
+            }
+        };
+
+        let config_contents =
+            migrations::migrate_config(config_path, &config_contents)?.unwrap_or(config_contents);
+
+        let (config, warnings) = Self::parse(config_path, &config_contents)?;
+        for warning in warnings {
+            warn!("{}", warning);
+        }
+
+        Ok(config)
+    }
+
+    /// Parses the config file without changing it, outdated files are only
+    /// migrated in memory. Also returns warnings about deprecated keys.
+    pub fn inspect(config_path: &Path) -> Result<(Self, Vec<String>)> {
+        let config_contents = std::fs::read_to_string(config_path)
+            .with_context(|| format!("Failed to read {:?}", config_path))?;
+        Self::parse(config_path, &config_contents)
+    }
+
+    /// Unknown keys are rejected, they are usually typos that would otherwise
+    /// silently leave an option at its default.
+    fn parse(config_path: &Path, config_contents: &str) -> Result<(Self, Vec<String>)> {
+        let format = ConfigFormat::from_path(config_path);
+
+        let mut table = format
+            .parse_table(config_contents)
+            .with_context(|| format!("Failed to parse {:?}", config_path))?;
+        let migrated = migrations::migrate_config_table(&mut table)?.is_some();
+
+        let unknown = unknown_keys(&table);
+        if !unknown.is_empty() {
+            bail!("Failed to parse {:?}: {}", config_path, unknown.join("; "));
+        }
+
+        // Errors of the original text point at the line, so they are
+        // reported instead if the migrated table doesn't parse either.
+        let config = if migrated {
+            toml::Value::Table(table.clone()).try_into().map_err(|e| {
+                match format.parse::<Self>(config_contents) {
+                    Err(original) => original,
+                    Ok(_) => anyhow::Error::from(e),
+                }
+            })
+        } else {
+            format.parse(config_contents)
+        }
+        .with_context(|| format!("Failed to parse {:?}", config_path))?;
+
+        Ok((config, deprecated_keys(&table)))
+    }
+}
+
+/// Sets one option in the config file, in the table `section` if given,
+/// keeping the rest of the file as it is. The running instance picks the
+/// change up like any other edit.
+pub fn write_option<T>(section: Option<&str>, key: &str, value: T) -> Result<()>
+where
+    T: Into<toml_edit::Value> + Into<toml::Value>,
+{
+    let config_path = Config::path()?;
+    let contents = std::fs::read_to_string(&config_path).context("Failed to read config file")?;
+    let contents = set_option(&config_path, &contents, section, key, value)?;
+
+    std::fs::write(&config_path, contents).context("Failed to write config file")
+}
+
+/// `contents` of the config file at `config_path` with one option set.
+fn set_option<T>(
+    config_path: &Path,
+    contents: &str,
+    section: Option<&str>,
+    key: &str,
+    value: T,
+) -> Result<String>
+where
+    T: Into<toml_edit::Value> + Into<toml::Value>,
+{
+    let format = ConfigFormat::from_path(config_path);
+
+    Ok(match format {
+        // toml_edit keeps the comments and layout of the file.
+        ConfigFormat::Toml => {
+            let mut document: toml_edit::DocumentMut = contents
+                .parse()
+                .with_context(|| format!("Failed to parse {:?}", config_path))?;
+
+            let table = match section {
+                Some(section) => document
+                    .entry(section)
+                    .or_insert(toml_edit::table())
+                    .as_table_mut()
+                    .with_context(|| format!("{} in the config file is not a table", section))?,
+                None => document.as_table_mut(),
+            };
+            table.insert(key, toml_edit::value(value));
+
+            document.to_string()
+        }
+        ConfigFormat::Json | ConfigFormat::Yaml => {
+            let mut document = format
+                .parse_table(contents)
+                .with_context(|| format!("Failed to parse {:?}", config_path))?;
+
+            let table = match section {
+                Some(section) => document
+                    .entry(section)
+                    .or_insert_with(|| Table::new().into())
+                    .as_table_mut()
+                    .with_context(|| format!("{} in the config file is not a table", section))?,
+                None => &mut document,
+            };
+            table.insert(key.to_string(), value.into());
+
+            format.to_string(&document)?
+        }
+    })
+}
+
+/// Whether two values of a section differ, for sections that can't be
+/// compared directly.
+pub fn differs<T: Serialize>(a: &T, b: &T) -> bool {
+    toml::Value::try_from(a).ok() != toml::Value::try_from(b).ok()
+}
+
+/// The value of `ARCH_UPDATES_RS_<name>`, if it is set.
+fn env_override<T>(lookup: impl Fn(&str) -> Option<String>, name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let variable = format!("{}{}", ENV_PREFIX, name);
+    let Some(value) = lookup(&variable) else {
+        return Ok(None);
+    };
+
+    match value.parse() {
+        Ok(value) => {
+            info!("Using {} from the environment", variable);
+            Ok(Some(value))
+        }
+        Err(e) => bail!("Invalid value {:?} for {}: {}", value, variable, e),
+    }
+}
+
+fn deprecated_keys(table: &Table) -> Vec<String> {
+    DEPRECATED_KEYS
+        .iter()
+        .filter(|(old, _)| table.contains_key(*old))
+        .map(|(old, new)| format!("`{}` is deprecated, use `{}` instead", old, new))
+        .collect()
+}
+
+/// The keys of `table` that aren't config options, with a suggestion if one
+/// is spelled similarly.
+fn unknown_keys(table: &Table) -> Vec<String> {
+    let known = serde_json::to_value(Config::default()).unwrap_or_default();
+    let table = serde_json::to_value(table).unwrap_or_default();
+
+    let mut unknown = Vec::new();
+    if let (Some(table), Some(known)) = (table.as_object(), known.as_object()) {
+        find_unknown_keys("", table, known, &mut unknown);
+    }
+    unknown
+}
+
+fn find_unknown_keys(
+    prefix: &str,
+    table: &Map<String, Value>,
+    known: &Map<String, Value>,
+    unknown: &mut Vec<String>,
+) {
+    for (key, value) in table {
+        let path = format!("{}{}", prefix, key);
+        if DEPRECATED_KEYS.iter().any(|(old, _)| *old == path) {
+            continue;
+        }
+        let Some(known_value) = known.get(key) else {
+            let suggestion = known
+                .keys()
+                .map(|known_key| (strsim::jaro_winkler(key, known_key), known_key))
+                .filter(|(similarity, _)| *similarity >= SUGGESTION_SIMILARITY)
+                .max_by(|a, b| a.0.total_cmp(&b.0));
+            unknown.push(match suggestion {
+                Some((_, known_key)) => format!(
+                    "Unknown key `{}`, did you mean `{}{}`?",
+                    path, prefix, known_key
+                ),
+                None => format!("Unknown key `{}`", path),
+            });
+            continue;
+        };
+
+        // Maps such as `thresholds` are empty by default and take any key.
+        if let (Some(table), Some(known)) = (value.as_object(), known_value.as_object())
+            && !known.is_empty()
+        {
+            find_unknown_keys(&format!("{}.", path), table, known, unknown);
+        }
+    }
+}
+
+/// Checks the config file and prints what is wrong with it. Fails if the
+/// file can't be loaded, e.g. because of unknown keys.
+pub fn validate() -> Result<()> {
+    let config_path = Config::path()?;
+    if !config_path.exists() {
+        println!("{:?} doesn't exist, the defaults are used", config_path);
+        return Ok(());
+    }
+
+    let (_, warnings) = Config::inspect(&config_path)?;
+    for warning in &warnings {
+        println!("{}", warning);
+    }
+    println!("{:?} is valid", config_path);
+
+    Ok(())
+}
+
+/// The effective config including all defaults and environment overrides,
+/// in the format of the config file.
+pub fn dump() -> Result<String> {
+    let config_path = Config::path()?;
+    let (mut config, format) = if config_path.exists() {
+        let (config, _) = Config::inspect(&config_path)?;
+        (config, ConfigFormat::from_path(&config_path))
+    } else {
+        (Config::default(), ConfigFormat::Toml)
+    };
+    config.apply_env_overrides()?;
+
+    format.to_string(&config)
+}
+
+/// Sends `Event::ConfigChanged` whenever the config file is written, until
+/// the app shuts down.
+pub fn watch(tx: Sender<Event>) -> Result<JoinHandle<()>> {
+    let config_path = Config::path()?;
+    let config_dir = config_path
+        .parent()
+        .context("Config file has no parent directory")?
+        .to_path_buf();
+
+    let thread = thread::Builder::new()
+        .name("config_watcher".to_string())
+        .spawn(move || {
+            let _span = info_span!("config_watcher").entered();
+            let (watcher_tx, watcher_rx) = channel::<NotifyResult<NotifyEvent>>();
+            let mut watcher = match notify::recommended_watcher(watcher_tx) {
+                Ok(watcher) => watcher,
+                Err(e) => {
+                    error!("Failed to create config watcher: {}", e);
+                    return;
+                }
+            };
+
+            // Editors often replace the file instead of writing to it, which
+            // would end a watch on the file itself.
+            if let Err(e) = watcher.watch(&config_dir, RecursiveMode::NonRecursive) {
+                error!("Failed to watch config directory: {}", e);
+                return;
+            }
+
+            info!("Watching for config changes in {:?}", config_path);
+
+            while let Some(res) = supervisor::recv(&watcher_rx) {
+                match res {
+                    Ok(event) => {
+                        let written =
+                            matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_));
+                        if written
+                            && event.paths.contains(&config_path)
+                            && tx.send(Event::ConfigChanged).is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        error!("config watch error: {}", e);
+                    }
+                }
+            }
+        })
+        .context("Failed to start the config watcher")?;
+
+    Ok(thread)
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            config_version: migrations::CURRENT_CONFIG_VERSION,
+            interval_in_seconds: 1200,
+            interval_jitter_percent: 0,
+            download_updates: false,
+            schedule: None,
+            warning_threshold: 25,
+            critical_threshold: 100,
+            thresholds: HashMap::new(),
+            warning_download_mib: 0,
+            critical_download_mib: 0,
+            ignore_repos: Vec::new(),
+            show_ignored_updates: false,
+            min_update_age_hours: 0,
+            upgrade_warning_days: 14,
+            pending_warning_days: 0,
+            watch_debounce_ms: 1000,
+            post_update_settle_secs: 5,
+            post_update_recheck_secs: 60,
+            http_address: None,
+            pacman_db_path: None,
+            terminal: None,
+            update_command: terminal::DEFAULT_UPDATE_COMMAND.to_string(),
+            window: WindowHints::default(),
+            webhook: WebhookConfig::default(),
+            calendar: CalendarConfig::default(),
+            eww: EwwConfig::default(),
+            hyprland: HyprlandConfig::default(),
+            template: TemplateConfig::default(),
+            mqtt: MqttConfig::default(),
+            hooks: HooksConfig::default(),
+            auto_update: AutoUpdateConfig::default(),
+            news: NewsConfig::default(),
+            advisories: AdvisoriesConfig::default(),
+            housekeeping: HousekeepingConfig::default(),
+            log: LogConfig::default(),
+            install: InstallConfig::default(),
+            rebuild: RebuildConfig::default(),
+            notifications: NotificationConfig::default(),
+            sound: SoundConfig::default(),
+            digest: DigestConfig::default(),
+            remote_hosts: Vec::new(),
+            epoch_display: EpochDisplay::default(),
+            sources: SourcesConfig::default(),
+            icon: IconConfig::default(),
+            tray_backend: TrayBackend::default(),
+            widget: WidgetConfig::default(),
+            summary: SummaryConfig::default(),
+            throttle: ThrottleConfig::default(),
+            confirm: HashMap::new(),
+            debug_menu: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_known_keys_for_typos() {
+        let cases: &[(&str, &[&str])] = &[
+            ("interval_in_seconds = 60", &[]),
+            ("[thresholds.snap]\nwarning = 5", &[]),
+            ("inverval_in_seconds = 60", &[]),
+            (
+                "intervl_in_seconds = 60",
+                &["Unknown key `intervl_in_seconds`, did you mean `interval_in_seconds`?"],
+            ),
+            (
+                "[notifications]\nenabeld = true",
+                &["Unknown key `notifications.enabeld`, did you mean `notifications.enabled`?"],
+            ),
+            ("colour = \"red\"", &["Unknown key `colour`"]),
+        ];
+
+        for (contents, expected) in cases {
+            let table: Table = contents.parse().unwrap();
+            assert_eq!(unknown_keys(&table), *expected, "{}", contents);
+        }
+    }
+
+    #[test]
+    fn parses_all_formats_alike() {
+        let cases = [
+            (
+                "config.toml",
+                "interval_in_seconds = 60\n\n[notifications]\nenabled = false\n",
+            ),
+            (
+                "config.json",
+                "{\"interval_in_seconds\": 60, \"http_address\": null, \"notifications\": {\"enabled\": false}}",
+            ),
+            (
+                "config.yaml",
+                "interval_in_seconds: 60\nhttp_address: ~\nnotifications:\n  enabled: false\n",
+            ),
+            (
+                "config.yml",
+                "inverval_in_seconds: 60\nnotifications:\n  enabled: false\n",
+            ),
+        ];
+
+        let (expected, _) = Config::parse(Path::new(cases[0].0), cases[0].1).unwrap();
+        assert_eq!(expected.interval_in_seconds, 60);
+        for (file_name, contents) in cases {
+            let (config, _) = Config::parse(Path::new(file_name), contents).unwrap();
+            assert!(!differs(&config, &expected), "{}", file_name);
+        }
+    }
+
+    #[test]
+    fn loads_dumped_configs() {
+        let config = Config {
+            interval_in_seconds: 60,
+            http_address: Some("127.0.0.1:8080".to_string()),
+            ..Config::default()
+        };
+
+        for file_name in ["config.toml", "config.json", "config.yaml"] {
+            let path = Path::new(file_name);
+            for config in [&Config::default(), &config] {
+                let dumped = ConfigFormat::from_path(path).to_string(config).unwrap();
+                let (loaded, _) = Config::parse(path, &dumped)
+                    .unwrap_or_else(|e| panic!("{}: {:#}\n{}", file_name, e, dumped));
+                assert!(!differs(&loaded, config), "{}", file_name);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_where_parsing_failed() {
+        let cases = [
+            (
+                "config.toml",
+                "debug_menu = false\ninterval_in_seconds =\n",
+                "line 2",
+            ),
+            ("config.toml", "interval_in_seconds = \"soon\"\n", "line 1"),
+            (
+                "config.json",
+                "{\n  \"interval_in_seconds\": \"soon\"\n}",
+                "line 2",
+            ),
+            (
+                "config.yaml",
+                "debug_menu: false\ninterval_in_seconds: soon\n",
+                "line 2",
+            ),
+            (
+                "config.json",
+                "{\"intervl_in_seconds\": 60}",
+                "did you mean",
+            ),
+        ];
+
+        for (file_name, contents, expected) in cases {
+            let error = Config::parse(Path::new(file_name), contents).unwrap_err();
+            assert!(
+                format!("{:#}", error).contains(expected),
+                "{}: {:#}",
+                file_name,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let variables = [
+            ("ARCH_UPDATES_RS_INTERVAL", "60"),
+            ("ARCH_UPDATES_RS_WARNING_THRESHOLD", "5"),
+            ("ARCH_UPDATES_RS_HTTP_ADDRESS", "127.0.0.1:9000"),
+            ("ARCH_UPDATES_RS_NOTIFICATIONS", "false"),
+        ];
+        let lookup = |name: &str| {
+            variables
+                .iter()
+                .find(|(variable, _)| *variable == name)
+                .map(|(_, value)| value.to_string())
+        };
+
+        let mut config = Config::default();
+        config.apply_overrides(lookup).unwrap();
+
+        assert_eq!(config.interval_in_seconds, 60);
+        assert_eq!(config.warning_threshold, 5);
+        assert_eq!(config.http_address.as_deref(), Some("127.0.0.1:9000"));
+        assert!(!config.notifications.enabled);
+        assert_eq!(
+            config.critical_threshold,
+            Config::default().critical_threshold
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_environment_values() {
+        let mut config = Config::default();
+        let error = config
+            .apply_overrides(|name| {
+                (name == "ARCH_UPDATES_RS_INTERVAL").then(|| "hourly".to_string())
+            })
+            .unwrap_err();
+
+        assert!(
+            error
+                .to_string()
+                .starts_with("Invalid value \"hourly\" for ARCH_UPDATES_RS_INTERVAL"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn sets_options_and_keeps_the_rest_of_the_file() {
+        let toml =
+            "# How often to check\ninterval_in_seconds = 3600\n\n[notifications]\nenabled = true\n";
+        let cases: &[(&str, &str, Option<&str>, &str)] = &[
+            (
+                "config.toml",
+                toml,
+                None,
+                "# How often to check\ninterval_in_seconds = 3600\ndebug_menu = true\n\n[notifications]\nenabled = true\n",
+            ),
+            (
+                "config.toml",
+                toml,
+                Some("notifications"),
+                "# How often to check\ninterval_in_seconds = 3600\n\n[notifications]\nenabled = true\ndebug_menu = true\n",
+            ),
+            (
+                "config.toml",
+                "",
+                Some("news"),
+                "[news]\ndebug_menu = true\n",
+            ),
+            (
+                "config.json",
+                "{\"interval_in_seconds\": 3600}",
+                None,
+                "{\n  \"debug_menu\": true,\n  \"interval_in_seconds\": 3600\n}",
+            ),
+            (
+                "config.yaml",
+                "interval_in_seconds: 3600\n",
+                Some("news"),
+                "interval_in_seconds: 3600\nnews:\n  debug_menu: true\n",
+            ),
+        ];
+
+        for (file_name, contents, section, expected) in cases {
+            let updated =
+                set_option(Path::new(file_name), contents, *section, "debug_menu", true).unwrap();
+            assert_eq!(&updated, expected, "{} in {:?}", file_name, section);
+        }
+    }
+
+    #[test]
+    fn refuses_to_replace_a_value_with_a_section() {
+        assert!(
+            set_option(
+                Path::new("config.toml"),
+                "news = 1\n",
+                Some("news"),
+                "enabled",
+                true
+            )
+            .is_err()
+        );
+    }
+}
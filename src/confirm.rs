@@ -0,0 +1,215 @@
+use anyhow::Result;
+use notify_rust::Notification;
+use tracing::error;
+
+use crate::{config, i18n::t};
+
+const RUN_ACTION: &str = "run";
+const DONT_ASK_AGAIN_ACTION: &str = "dont-ask-again";
+
+/// The user's answer to a confirmation dialog.
+pub enum Confirmation {
+    Cancelled,
+    Confirmed { dont_ask_again: bool },
+}
+
+/// Asks before running `command`, showing `details` about what it affects.
+/// Uses a GTK dialog where GTK is available, otherwise a notification.
+/// Must be called on the tray thread.
+pub fn ask(title: &str, command: &str, details: &str) -> Confirmation {
+    #[cfg(feature = "gtk")]
+    if gtk::init().is_ok() {
+        return ask_with_dialog(title, command, details);
+    }
+
+    #[cfg(feature = "gtk4")]
+    match ask_with_gtk4_dialog(title, command, details) {
+        Ok(confirmation) => return confirmation,
+        Err(e) => error!("Failed to show confirmation dialog: {:#}", e),
+    }
+
+    ask_with_notification(title, command, details)
+}
+
+#[cfg(feature = "gtk")]
+fn ask_with_dialog(title: &str, command: &str, details: &str) -> Confirmation {
+    use gtk::prelude::*;
+
+    let dialog = gtk::MessageDialog::new(
+        None::<&gtk::Window>,
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::None,
+        &format!("{}?", title),
+    );
+    dialog.set_title("arch-updates-rs");
+    dialog.set_keep_above(true);
+    dialog.set_secondary_text(Some(&format!(
+        "{}\n\n    {}\n\n{}",
+        t!("confirm-will-run"),
+        command,
+        details
+    )));
+    dialog.add_buttons(&[
+        (t!("confirm-cancel").as_str(), gtk::ResponseType::Cancel),
+        (t!("confirm-run").as_str(), gtk::ResponseType::Accept),
+    ]);
+    dialog.set_default_response(gtk::ResponseType::Cancel);
+
+    let dont_ask_again = gtk::CheckButton::with_label(&t!("confirm-dont-ask-again"));
+    dialog.content_area().add(&dont_ask_again);
+    dont_ask_again.show();
+
+    let response = dialog.run();
+    let dont_ask_again = dont_ask_again.is_active();
+    dialog.close();
+
+    if response == gtk::ResponseType::Accept {
+        Confirmation::Confirmed { dont_ask_again }
+    } else {
+        Confirmation::Cancelled
+    }
+}
+
+/// Asks with a dialog on the GTK thread, waiting for the answer.
+#[cfg(feature = "gtk4")]
+fn ask_with_gtk4_dialog(title: &str, command: &str, details: &str) -> Result<Confirmation> {
+    use std::{
+        cell::RefCell,
+        rc::Rc,
+        sync::mpsc::{Sender, channel},
+    };
+
+    use anyhow::Context;
+
+    let heading = format!("{}?", title);
+    let body = format!(
+        "{}\n\n    {}\n\n{}",
+        t!("confirm-will-run"),
+        command,
+        details
+    );
+    let (answer_tx, answer) = channel();
+
+    crate::gtk4_thread::invoke(move || {
+        use gtk4::prelude::*;
+
+        let window = gtk4::Window::builder()
+            .title("arch-updates-rs")
+            .modal(true)
+            .resizable(false)
+            .build();
+
+        let heading = gtk4::Label::new(Some(&heading));
+        heading.add_css_class("title-2");
+        heading.set_xalign(0.0);
+        let body = gtk4::Label::new(Some(&body));
+        body.set_wrap(true);
+        body.set_xalign(0.0);
+        body.set_selectable(true);
+        let dont_ask_again = gtk4::CheckButton::with_label(&t!("confirm-dont-ask-again"));
+
+        let cancel = gtk4::Button::with_label(&t!("confirm-cancel"));
+        let run = gtk4::Button::with_label(&t!("confirm-run"));
+        run.add_css_class("suggested-action");
+        let buttons = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+        buttons.set_halign(gtk4::Align::End);
+        buttons.append(&cancel);
+        buttons.append(&run);
+
+        let content = gtk4::Box::new(gtk4::Orientation::Vertical, 12);
+        content.set_margin_top(18);
+        content.set_margin_bottom(18);
+        content.set_margin_start(18);
+        content.set_margin_end(18);
+        content.append(&heading);
+        content.append(&body);
+        content.append(&dont_ask_again);
+        content.append(&buttons);
+        window.set_child(Some(&content));
+        window.set_default_widget(Some(&cancel));
+
+        // Only the first answer counts, closing the window after a button
+        // was clicked doesn't cancel.
+        let answer_tx = Rc::new(RefCell::new(Some(answer_tx)));
+        let send = |answer_tx: &RefCell<Option<Sender<Confirmation>>>, confirmation| {
+            if let Some(answer_tx) = answer_tx.borrow_mut().take() {
+                let _ = answer_tx.send(confirmation);
+            }
+        };
+
+        cancel.connect_clicked({
+            let window = window.clone();
+            let answer_tx = answer_tx.clone();
+            move |_| {
+                send(&answer_tx, Confirmation::Cancelled);
+                window.close();
+            }
+        });
+        run.connect_clicked({
+            let window = window.clone();
+            let answer_tx = answer_tx.clone();
+            move |_| {
+                send(
+                    &answer_tx,
+                    Confirmation::Confirmed {
+                        dont_ask_again: dont_ask_again.is_active(),
+                    },
+                );
+                window.close();
+            }
+        });
+        window.connect_close_request(move |_| {
+            send(&answer_tx, Confirmation::Cancelled);
+            glib::Propagation::Proceed
+        });
+
+        window.present();
+    })?;
+
+    answer.recv().context("The confirmation dialog went away")
+}
+
+/// Asks with a notification whose actions stand in for the dialog's buttons.
+/// Dismissing it cancels.
+fn ask_with_notification(title: &str, command: &str, details: &str) -> Confirmation {
+    let handle = match Notification::new()
+        .appname("arch-updates-rs")
+        .summary(&format!("{}?", title))
+        .body(&format!(
+            "{}\n\n    {}\n\n{}",
+            t!("confirm-will-run"),
+            command,
+            details
+        ))
+        .action(RUN_ACTION, &t!("confirm-run"))
+        .action(DONT_ASK_AGAIN_ACTION, &t!("confirm-dont-ask-again"))
+        .action("cancel", &t!("confirm-cancel"))
+        .show()
+    {
+        Ok(handle) => handle,
+        Err(e) => {
+            error!("Failed to show confirmation: {}", e);
+            return Confirmation::Cancelled;
+        }
+    };
+
+    let mut confirmation = Confirmation::Cancelled;
+    handle.wait_for_action(|action| {
+        confirmation = match action {
+            RUN_ACTION => Confirmation::Confirmed {
+                dont_ask_again: false,
+            },
+            DONT_ASK_AGAIN_ACTION => Confirmation::Confirmed {
+                dont_ask_again: true,
+            },
+            _ => Confirmation::Cancelled,
+        }
+    });
+    confirmation
+}
+
+/// Turns off the confirmation of `action_id` in the config file.
+pub fn dont_ask_again(action_id: &str) -> Result<()> {
+    config::write_option(Some("confirm"), action_id, false)
+}
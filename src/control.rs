@@ -0,0 +1,337 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::{
+        fs::{FileTypeExt, MetadataExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    sync::{
+        Mutex,
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, bail};
+use tracing::{error, info, info_span, warn};
+
+use crate::{
+    Event, activation,
+    bus::{Bus, StateUpdate},
+    metrics, runtime_dir,
+    status::{Status, StatusResponse},
+    summary::{SummaryConfig, summary},
+};
+
+const SOCKET_NAME: &str = "arch-updates-rs.sock";
+
+/// How long a client gets to send its command, so one that never does can't
+/// hold up the commands of the others.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the pacman hooks wait for the instances. A socket that doesn't
+/// take the message in time is skipped, so no user can hold up pacman.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(1);
+
+const TRANSACTION_STARTED: &str = "transaction-started";
+const TRANSACTION_FINISHED: &str = "transaction-finished";
+const STATUS: &str = "status";
+const SUMMARY: &str = "summary";
+const METRICS: &str = "metrics";
+const CHECK: &str = "check";
+const PAUSE: &str = "pause";
+const RESUME: &str = "resume";
+const WATCH: &str = "watch";
+const QUIT: &str = "quit";
+
+/// Listens on the control socket in the runtime directory and forwards the
+/// commands to the main loop, or answers them from the `bus`. Must only be
+/// called while holding the instance lock, since it replaces any existing
+/// socket.
+pub fn listen(tx: Sender<Event>, bus: Bus, summary_config: SummaryConfig) -> Result<()> {
+    let listener = match activation::take(activation::CONTROL) {
+        Some(socket) => {
+            info!("Listening for commands on the socket from systemd");
+            UnixListener::from(socket)
+        }
+        None => {
+            let socket_path = runtime_dir()?.join(SOCKET_NAME);
+
+            // A leftover socket from a previous run would make bind fail.
+            if socket_path.exists() {
+                fs::remove_file(&socket_path).context("Failed to remove stale control socket")?;
+            }
+
+            let listener = UnixListener::bind(&socket_path)
+                .with_context(|| format!("Failed to bind control socket {:?}", socket_path))?;
+            info!("Listening for commands on {:?}", socket_path);
+            listener
+        }
+    };
+
+    let status = bus.status();
+    thread::spawn(move || {
+        let _span = info_span!("control").entered();
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error!("Failed to accept control connection: {}", e);
+                    continue;
+                }
+            };
+
+            if let Err(e) = stream.set_read_timeout(Some(READ_TIMEOUT)) {
+                error!("Failed to set control connection timeout: {}", e);
+                continue;
+            }
+            let mut command = String::new();
+            if let Err(e) = BufReader::new(&stream).read_line(&mut command) {
+                error!("Failed to read control command: {}", e);
+                continue;
+            }
+
+            let event = match command.trim() {
+                TRANSACTION_STARTED => Event::TransactionStarted,
+                TRANSACTION_FINISHED => Event::TransactionFinished,
+                CHECK => Event::Checking,
+                PAUSE => Event::Pause,
+                RESUME => Event::Resume,
+                QUIT => Event::Shutdown,
+                STATUS => {
+                    let response = {
+                        let status = status.lock().unwrap();
+                        serde_json::to_string(&StatusResponse::new(&status))
+                    };
+
+                    let result = match response {
+                        Ok(response) => writeln!(&stream, "{}", response),
+                        Err(e) => {
+                            error!("Failed to serialize status: {}", e);
+                            continue;
+                        }
+                    };
+                    if let Err(e) = result {
+                        error!("Failed to send status: {}", e);
+                    }
+                    continue;
+                }
+                METRICS => {
+                    let result = match serde_json::to_string(&metrics::snapshot()) {
+                        Ok(response) => writeln!(&stream, "{}", response),
+                        Err(e) => {
+                            error!("Failed to serialize metrics: {}", e);
+                            continue;
+                        }
+                    };
+                    if let Err(e) = result {
+                        error!("Failed to send metrics: {}", e);
+                    }
+                    continue;
+                }
+                SUMMARY => {
+                    let summary = summary(&status.lock().unwrap(), &summary_config);
+                    if let Err(e) = writeln!(&stream, "{}", summary) {
+                        error!("Failed to send summary: {}", e);
+                    }
+                    continue;
+                }
+                WATCH => {
+                    let updates = bus.subscribe();
+                    let status = status.clone();
+                    thread::spawn(move || {
+                        let _span = info_span!("control").entered();
+                        stream_status(&stream, &status, &updates);
+                    });
+                    continue;
+                }
+                command => {
+                    warn!("Unknown control command {:?}", command);
+                    continue;
+                }
+            };
+
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Writes the status to `stream` now and after every update, until the app
+/// shuts down or the other end goes away.
+fn stream_status(mut stream: &UnixStream, status: &Mutex<Status>, updates: &Receiver<StateUpdate>) {
+    loop {
+        let response = {
+            let status = status.lock().unwrap();
+            serde_json::to_string(&StatusResponse::new(&status))
+        };
+        let result = match response {
+            Ok(response) => writeln!(stream, "{}", response),
+            Err(e) => {
+                error!("Failed to serialize status: {}", e);
+                return;
+            }
+        };
+        if result.is_err() {
+            return;
+        }
+
+        match updates.recv() {
+            Ok(StateUpdate::Shutdown) | Err(_) => return,
+            Ok(_) => {}
+        }
+    }
+}
+
+/// Asks this user's running instance for its last known status, as JSON or as
+/// a one-line summary.
+pub fn request_status(text: bool) -> Result<String> {
+    request(if text { SUMMARY } else { STATUS })
+}
+
+/// Asks this user's running instance for its update statistics, or for its
+/// internal counters, as pretty-printed JSON.
+pub fn request_stats(internal: bool) -> Result<String> {
+    let response: serde_json::Value = if internal {
+        serde_json::from_str(&request(METRICS)?)?
+    } else {
+        let status: serde_json::Value = serde_json::from_str(&request(STATUS)?)?;
+        status["statistics"].clone()
+    };
+
+    Ok(serde_json::to_string_pretty(&response)?)
+}
+
+/// Asks this user's running instance to check for updates now.
+pub fn request_check() -> Result<()> {
+    send(CHECK)
+}
+
+/// Asks this user's running instance to pause or resume its checks.
+pub fn request_pause(pause: bool) -> Result<()> {
+    send(if pause { PAUSE } else { RESUME })
+}
+
+/// Follows the status of this user's running instance, calling `on_status`
+/// with the current status and then after every change. Returns when the
+/// instance exits.
+pub fn watch_status(mut on_status: impl FnMut(serde_json::Value) -> Result<()>) -> Result<()> {
+    let socket_path = runtime_dir()?.join(SOCKET_NAME);
+    let mut stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("Failed to connect to {:?}. Is it running?", socket_path))?;
+
+    writeln!(stream, "{}", WATCH).context("Failed to send command")?;
+
+    for line in BufReader::new(stream).lines() {
+        let line = line.context("Failed to read status")?;
+        on_status(serde_json::from_str(&line).context("Failed to parse status")?)?;
+    }
+
+    bail!("The running instance exited")
+}
+
+/// Asks this user's running instance to exit.
+pub fn request_quit() -> Result<()> {
+    send(QUIT)
+}
+
+/// Sends a command that has no response.
+fn send(command: &str) -> Result<()> {
+    let socket_path = runtime_dir()?.join(SOCKET_NAME);
+    let mut stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("Failed to connect to {:?}. Is it running?", socket_path))?;
+
+    writeln!(stream, "{}", command).context("Failed to send command")
+}
+
+fn request(command: &str) -> Result<String> {
+    let socket_path = runtime_dir()?.join(SOCKET_NAME);
+    let mut stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("Failed to connect to {:?}. Is it running?", socket_path))?;
+
+    writeln!(stream, "{}", command).context("Failed to send command")?;
+
+    let mut response = String::new();
+    BufReader::new(stream)
+        .read_line(&mut response)
+        .context("Failed to read response")?;
+
+    Ok(response.trim_end().to_string())
+}
+
+/// Tells every running instance, of any user, about a pacman transaction.
+/// Called from the alpm hooks, which run as root.
+pub fn notify_transaction(started: bool) {
+    let command = if started {
+        TRANSACTION_STARTED
+    } else {
+        TRANSACTION_FINISHED
+    };
+
+    let mut pending = instance_sockets();
+    let (tx, rx) = mpsc::channel();
+    for socket_path in pending.clone() {
+        let tx = tx.clone();
+        // Connecting blocks while the backlog of the socket is full and std
+        // has no connect timeout for Unix sockets, so every instance gets a
+        // thread. The ones still stuck end with the hook.
+        thread::spawn(move || {
+            let result = UnixStream::connect(&socket_path).and_then(|mut stream| {
+                stream.set_write_timeout(Some(NOTIFY_TIMEOUT))?;
+                writeln!(stream, "{}", command)
+            });
+            let _ = tx.send((socket_path, result));
+        });
+    }
+
+    let deadline = Instant::now() + NOTIFY_TIMEOUT;
+    while !pending.is_empty() {
+        let Ok((socket_path, result)) =
+            rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        else {
+            break;
+        };
+        pending.retain(|pending_path| *pending_path != socket_path);
+
+        // Sockets of instances that are no longer running refuse connections.
+        if let Err(e) = result {
+            eprintln!("Failed to notify {:?}: {}", socket_path, e);
+        }
+    }
+
+    for socket_path in pending {
+        eprintln!("Skipped {:?}, it didn't answer in time", socket_path);
+    }
+}
+
+/// Control sockets in every user's runtime directory, `/run/user/<uid>`.
+/// The hooks run as root, so only sockets owned by the user the directory
+/// belongs to are trusted. The fallback directories `runtime_dir` uses
+/// without XDG_RUNTIME_DIR are left out, since other users can create them.
+fn instance_sockets() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir("/run/user") else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let dir = entry.path();
+            let uid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let dir_metadata = fs::symlink_metadata(&dir).ok()?;
+            if !dir_metadata.is_dir() || dir_metadata.uid() != uid {
+                return None;
+            }
+
+            let socket_path = dir.join(SOCKET_NAME);
+            let socket_metadata = fs::symlink_metadata(&socket_path).ok()?;
+            (socket_metadata.file_type().is_socket() && socket_metadata.uid() == uid)
+                .then_some(socket_path)
+        })
+        .collect()
+}
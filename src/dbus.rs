@@ -0,0 +1,66 @@
+use std::sync::mpsc::Sender;
+
+use anyhow::{Context, Result};
+use zbus::{
+    blocking::{Connection, Proxy, connection::Builder},
+    interface,
+};
+
+use crate::Event;
+
+/// The well-known name the running instance owns on the session bus.
+const NAME: &str = "io.github.collins_lagat.ArchUpdatesRs";
+const PATH: &str = "/io/github/collins_lagat/ArchUpdatesRs";
+const INTERFACE: &str = "io.github.collins_lagat.ArchUpdatesRs";
+
+/// Commands to the running instance over D-Bus, like the ones of the control
+/// socket.
+struct Service {
+    tx: Sender<Event>,
+}
+
+#[interface(name = "io.github.collins_lagat.ArchUpdatesRs")]
+impl Service {
+    /// Checks for updates now.
+    fn check_now(&self) {
+        let _ = self.tx.send(Event::Checking);
+    }
+
+    /// Checks for updates now. Called when the program is started again.
+    fn raise(&self) {
+        let _ = self.tx.send(Event::Checking);
+    }
+
+    fn pause(&self) {
+        let _ = self.tx.send(Event::Pause);
+    }
+
+    fn resume(&self) {
+        let _ = self.tx.send(Event::Resume);
+    }
+
+    fn quit(&self) {
+        let _ = self.tx.send(Event::Shutdown);
+    }
+}
+
+/// Owns the well-known name and serves the commands until the returned
+/// connection is dropped. Must only be called while holding the instance
+/// lock.
+pub fn serve(tx: Sender<Event>) -> Result<Connection> {
+    // Fails instead of queueing while someone else owns the name.
+    Builder::session()
+        .and_then(|builder| builder.name(NAME))
+        .and_then(|builder| builder.serve_at(PATH, Service { tx }))
+        .and_then(|builder| builder.build())
+        .with_context(|| format!("Failed to own {} on the session bus", NAME))
+}
+
+/// Asks the running instance to check for updates now, because the program
+/// was started again.
+pub fn raise() -> Result<()> {
+    let connection = Connection::session().context("Failed to connect to the session bus")?;
+    Proxy::new(&connection, NAME, PATH, INTERFACE)
+        .and_then(|proxy| proxy.call::<_, _, ()>("Raise", &()))
+        .with_context(|| format!("Failed to call Raise on {}", NAME))
+}
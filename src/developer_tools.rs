@@ -0,0 +1,44 @@
+use anyhow::Result;
+
+use crate::sources::run_if_installed;
+
+pub const ID: &str = "developer_tools";
+pub const NAME: &str = "Developer tools";
+
+/// Returns outdated rustup toolchains and cargo-installed binaries, formatted
+/// like checkupdates lines (`name old -> new`). Tools that aren't installed are
+/// skipped.
+pub fn check() -> Result<Vec<String>> {
+    let mut updates = Vec::new();
+
+    if let Some(output) = run_if_installed("rustup", &["check"])? {
+        updates.extend(output.lines().filter_map(parse_rustup_line));
+    }
+
+    if let Some(output) = run_if_installed("cargo", &["install-update", "--list"])? {
+        updates.extend(output.lines().filter_map(parse_cargo_update_line));
+    }
+
+    Ok(updates)
+}
+
+/// `stable-x86_64-unknown-linux-gnu - Update available : 1.78.0 (9b00956e5 2024-04-29) -> 1.79.0 (129f3b996 2024-06-10)`
+fn parse_rustup_line(line: &str) -> Option<String> {
+    let (name, rest) = line.split_once(" - Update available : ")?;
+    let (old, new) = rest.split_once(" -> ")?;
+    Some(format!(
+        "{} {} -> {}",
+        name.trim(),
+        old.split_whitespace().next()?,
+        new.split_whitespace().next()?
+    ))
+}
+
+/// `ripgrep    v14.0.0    v14.1.0    Yes`
+fn parse_cargo_update_line(line: &str) -> Option<String> {
+    let columns: Vec<&str> = line.split_whitespace().collect();
+    match columns.as_slice() {
+        [name, old, new, "Yes"] => Some(format!("{} {} -> {}", name, old, new)),
+        _ => None,
+    }
+}
@@ -0,0 +1,130 @@
+use std::{sync::mpsc::Sender, thread, time::Duration};
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveTime, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    Event,
+    history::History,
+    i18n::t,
+    notification,
+    status::{Status, elapsed_since},
+    tray::format_elapsed,
+};
+
+/// How often to check whether the digest is due.
+const POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// A digest that is more than this late, e.g. because the machine was off,
+/// is skipped until the next week.
+const MAX_DELAY_SECS: i64 = 60 * 60 * 24;
+
+const SECONDS_PER_WEEK: i64 = 60 * 60 * 24 * 7;
+
+/// A weekly notification about the week's updates.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DigestConfig {
+    pub enabled: bool,
+    pub weekday: Weekday,
+    /// Local time of day.
+    pub time: NaiveTime,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            weekday: Weekday::Mon,
+            time: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        }
+    }
+}
+
+/// Sends the digest when it is due. The time it was last sent is kept in the
+/// history, so it is sent once a week however often the program restarts.
+pub fn send_if_due(config: &DigestConfig, history: Option<&History>, status: &Status) {
+    if !config.enabled {
+        return;
+    }
+    let Some(history) = history else {
+        return;
+    };
+
+    let now = Local::now();
+    let Some(due_at) = last_due_time(config, now) else {
+        return;
+    };
+    let last_sent = match history.last_digest() {
+        Ok(last_sent) => last_sent,
+        Err(e) => {
+            error!("{:#}", e);
+            return;
+        }
+    };
+    if last_sent.is_some_and(|last_sent| last_sent >= due_at.timestamp())
+        || now.timestamp() - due_at.timestamp() > MAX_DELAY_SECS
+    {
+        return;
+    }
+
+    let updated = match history.upgraded_since(now.timestamp() - SECONDS_PER_WEEK) {
+        Ok(updated) => updated,
+        Err(e) => {
+            error!("{:#}", e);
+            return;
+        }
+    };
+    let last_upgrade = status
+        .last_upgrade
+        .map(|last_upgrade| format_elapsed(elapsed_since(last_upgrade)))
+        .unwrap_or_else(|| t!("digest-never"));
+
+    info!("Sending the weekly digest");
+    if let Err(e) = notification::send(
+        &t!("digest-summary"),
+        &t!(
+            "digest-body",
+            updated = updated,
+            pending = status.updates.len(),
+            last_upgrade = last_upgrade
+        ),
+    ) {
+        error!("{:#}", e);
+    }
+    if let Err(e) = history.set_last_digest(now.timestamp()) {
+        error!("{:#}", e);
+    }
+}
+
+/// The latest time at or before `now` the digest was due.
+fn last_due_time(config: &DigestConfig, now: DateTime<Local>) -> Option<DateTime<Utc>> {
+    let days_since =
+        (now.weekday().num_days_from_monday() + 7 - config.weekday.num_days_from_monday()) % 7;
+    let mut date = now
+        .date_naive()
+        .checked_sub_days(Days::new(days_since.into()))?;
+    loop {
+        // Times skipped by a DST change fall on no day.
+        let due_at = Local
+            .from_local_datetime(&date.and_time(config.time))
+            .earliest();
+        if let Some(due_at) = due_at
+            && due_at <= now
+        {
+            return Some(due_at.with_timezone(&Utc));
+        }
+        date = date.checked_sub_days(Days::new(7))?;
+    }
+}
+
+/// Sends `Event::Digest` regularly, so the main loop can check whether the
+/// digest is due.
+pub fn schedule(tx: Sender<Event>) {
+    thread::spawn(move || {
+        while tx.send(Event::Digest).is_ok() {
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
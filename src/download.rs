@@ -0,0 +1,30 @@
+use std::{sync::mpsc::Sender, thread};
+
+use tracing::{error, info};
+
+use crate::{Event, check_updates};
+
+/// Downloads the pending updates into the pacman cache with `checkupdates -d`,
+/// so the next `pacman -Syu` doesn't have to. Reports the packages that were
+/// downloaded as `Event::DownloadFinished`.
+pub fn start(tx: Sender<Event>) {
+    thread::spawn(move || {
+        info!("Downloading pending updates");
+        let downloaded = match check_updates(true) {
+            Ok(updates) => {
+                info!(
+                    event = "download",
+                    updates = updates.len(),
+                    "Downloaded {} pending updates",
+                    updates.len()
+                );
+                Some(updates)
+            }
+            Err(e) => {
+                error!("Failed to download updates: {:#}", e);
+                None
+            }
+        };
+        let _ = tx.send(Event::DownloadFinished(downloaded));
+    });
+}
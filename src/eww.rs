@@ -0,0 +1,65 @@
+use std::{
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::{runtime_dir, state_file, status::Status};
+
+const STATE_FILE_NAME: &str = "arch-updates-rs-eww.json";
+
+/// Keeps a state file for eww's `deflisten`, and optionally pushes each change
+/// into a `defvar` with `eww update`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct EwwConfig {
+    pub enabled: bool,
+    /// The `defvar` to update, e.g. `arch_updates`.
+    pub variable: Option<String>,
+}
+
+/// Where the state for eww is written.
+fn eww_state_file() -> Result<PathBuf> {
+    Ok(runtime_dir()?.join(STATE_FILE_NAME))
+}
+
+/// Writes the state for eww and updates the `defvar`, if one is set.
+pub fn publish(config: &EwwConfig, status: &Status) -> Result<()> {
+    let payload = state_file::payload(status).to_string();
+
+    // Replaced in one step, so that `tail -F` never reads half a line.
+    state_file::write_atomic(&eww_state_file()?, &format!("{}\n", payload))?;
+
+    if let Some(variable) = &config.variable {
+        update_variable(variable, &payload);
+    }
+
+    Ok(())
+}
+
+fn update_variable(variable: &str, payload: &str) {
+    let child = Command::new("eww")
+        .arg("update")
+        .arg(format!("{}={}", variable, payload))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn();
+
+    // Fails while the eww daemon isn't running, which the state file covers.
+    match child {
+        Ok(mut child) => {
+            thread::spawn(move || match child.wait() {
+                Ok(exit_status) if !exit_status.success() => {
+                    warn!("eww update exited with {}", exit_status)
+                }
+                Ok(_) => {}
+                Err(e) => error!("Failed to wait for eww update: {}", e),
+            });
+        }
+        Err(e) => error!("Failed to run eww update: {}", e),
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    process::Command,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use tracing::{error, info};
+
+use crate::{
+    config::Config,
+    history::{self, History},
+    pacman_conf::{self, PacmanConf},
+};
+
+/// The repository of each package.
+type PackageRepos = HashMap<String, String>;
+
+/// The result of the last `pacman -Sl`, with the time the sync databases
+/// were refreshed then. Listing every package takes a while and only changes
+/// with a refresh.
+static PACKAGE_REPOS: Mutex<Option<(SystemTime, Arc<PackageRepos>)>> = Mutex::new(None);
+
+/// The pending updates after leaving out the ones the config says not to
+/// count.
+#[derive(Debug, Default)]
+pub struct Filtered {
+    pub updates: Vec<String>,
+    /// Updates pacman won't install because of `IgnorePkg` or `IgnoreGroup`
+    /// in pacman.conf.
+    pub ignored: Vec<String>,
+    /// Updates first seen less than `min_update_age_hours` ago.
+    pub recent: Vec<String>,
+    /// When the longest pending of `updates` was first seen, as a Unix
+    /// timestamp. Only known with the history.
+    pub oldest_seen: Option<i64>,
+}
+
+/// Leaves out the pending updates that shouldn't be counted, before anything
+/// else sees them.
+pub fn apply(updates: Vec<String>, config: &Config) -> Filtered {
+    let updates = without_ignored_repos(updates, config);
+
+    let conf = match PacmanConf::read() {
+        Ok(conf) => conf,
+        Err(e) => {
+            error!("{:#}", e);
+            PacmanConf::default()
+        }
+    };
+    // pacman marks the ones it knows to skip itself.
+    let (ignored, updates) = updates.into_iter().partition(|update| {
+        update.ends_with("[ignored]")
+            || update
+                .split_whitespace()
+                .next()
+                .is_some_and(|name| conf.is_ignored(name))
+    });
+
+    Filtered {
+        updates,
+        ignored,
+        recent: Vec::new(),
+        oldest_seen: None,
+    }
+}
+
+/// Records when each update was first seen, remembers the oldest and holds
+/// back the ones seen less than `min_update_age_hours` ago.
+pub fn hold_back_recent(filtered: &mut Filtered, history: &History, config: &Config) {
+    let first_seen = match history.first_seen(&filtered.updates) {
+        Ok(first_seen) => first_seen,
+        Err(e) => {
+            error!("{:#}", e);
+            return;
+        }
+    };
+    filtered.oldest_seen = first_seen.iter().copied().min();
+    if config.min_update_age_hours == 0 {
+        return;
+    }
+
+    let cutoff = history::unix_timestamp() - i64::from(config.min_update_age_hours) * 60 * 60;
+    let (updates, recent) = std::mem::take(&mut filtered.updates)
+        .into_iter()
+        .zip(first_seen)
+        .partition::<Vec<_>, _>(|(_, first_seen)| *first_seen <= cutoff);
+    filtered.oldest_seen = updates.iter().map(|(_, first_seen)| *first_seen).min();
+    filtered.updates = updates.into_iter().map(|(update, _)| update).collect();
+    filtered.recent = recent.into_iter().map(|(update, _)| update).collect();
+    if !filtered.recent.is_empty() {
+        info!(
+            "Holding back {} updates seen less than {} hours ago",
+            filtered.recent.len(),
+            config.min_update_age_hours
+        );
+    }
+}
+
+/// Leaves out the updates of the repositories in `ignore_repos`.
+fn without_ignored_repos(updates: Vec<String>, config: &Config) -> Vec<String> {
+    if config.ignore_repos.is_empty() {
+        return updates;
+    }
+
+    let repos = match package_repos(&config.pacman_db_path()) {
+        Ok(repos) => repos,
+        Err(e) => {
+            error!("Failed to look up the repositories of updates: {:#}", e);
+            return updates;
+        }
+    };
+
+    let (ignored, updates): (Vec<String>, Vec<String>) = updates.into_iter().partition(|update| {
+        update
+            .split_whitespace()
+            .next()
+            .and_then(|name| repos.get(name))
+            .is_some_and(|repo| config.ignore_repos.contains(repo))
+    });
+    if !ignored.is_empty() {
+        info!(
+            "Ignoring {} updates from {}",
+            ignored.len(),
+            config.ignore_repos.join(", ")
+        );
+    }
+
+    updates
+}
+
+/// The repository of every package in the sync databases, from the cache
+/// unless the databases were refreshed since.
+pub fn package_repos(pacman_db_path: &Path) -> Result<Arc<PackageRepos>> {
+    let synced_at = pacman_conf::last_sync(pacman_db_path);
+    let mut cached = PACKAGE_REPOS.lock().unwrap();
+    if let (Some(synced_at), Some((cached_at, repos))) = (synced_at, &*cached)
+        && synced_at == *cached_at
+    {
+        return Ok(repos.clone());
+    }
+
+    let repos = Arc::new(list_package_repos()?);
+    *cached = synced_at.map(|synced_at| (synced_at, repos.clone()));
+    Ok(repos)
+}
+
+/// The repository of every package in the sync databases. The first
+/// repository in pacman.conf wins, like for pacman.
+fn list_package_repos() -> Result<PackageRepos> {
+    let output = Command::new("pacman")
+        .arg("-Sl")
+        .output()
+        .context("Failed to run pacman")?;
+
+    let mut repos = HashMap::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut columns = line.split_whitespace();
+        if let (Some(repo), Some(name)) = (columns.next(), columns.next()) {
+            repos
+                .entry(name.to_string())
+                .or_insert_with(|| repo.to_string());
+        }
+    }
+
+    Ok(repos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filtered(updates: &[&str]) -> Filtered {
+        Filtered {
+            updates: updates.iter().map(|update| update.to_string()).collect(),
+            ..Filtered::default()
+        }
+    }
+
+    #[test]
+    fn holds_back_updates_seen_less_than_the_minimum_age_ago() {
+        let hour = 60 * 60;
+        let now = history::unix_timestamp();
+        let history = History::in_memory();
+        history.set_first_seen("firefox", "126.0-1", now - 48 * hour);
+        history.set_first_seen("linux", "6.9.2-1", now - 2 * hour);
+        let pending = [
+            "firefox 125.0-1 -> 126.0-1",
+            "linux 6.9.1-1 -> 6.9.2-1",
+            "nss 3.100-1 -> 3.101-1",
+        ];
+
+        let cases: &[(u32, &[&str], &[&str])] = &[
+            (0, &pending, &[]),
+            (1, &pending[..2], &pending[2..]),
+            (24, &pending[..1], &pending[1..]),
+            (72, &[], &pending),
+        ];
+        for (min_update_age_hours, updates, recent) in cases {
+            let config = Config {
+                min_update_age_hours: *min_update_age_hours,
+                ..Config::default()
+            };
+            let mut filtered = filtered(&pending);
+
+            hold_back_recent(&mut filtered, &history, &config);
+
+            assert_eq!(filtered.updates, *updates, "{} hours", min_update_age_hours);
+            assert_eq!(filtered.recent, *recent, "{} hours", min_update_age_hours);
+        }
+    }
+
+    #[test]
+    fn remembers_the_oldest_update_that_counts() {
+        let hour = 60 * 60;
+        let now = history::unix_timestamp();
+        let history = History::in_memory();
+        history.set_first_seen("firefox", "126.0-1", now - 48 * hour);
+        history.set_first_seen("linux", "6.9.2-1", now - 2 * hour);
+        let pending = ["firefox 125.0-1 -> 126.0-1", "linux 6.9.1-1 -> 6.9.2-1"];
+
+        let cases = [
+            (0, Some(now - 48 * hour)),
+            (24, Some(now - 48 * hour)),
+            // Held back updates don't count as pending.
+            (72, None),
+        ];
+        for (min_update_age_hours, oldest_seen) in cases {
+            let config = Config {
+                min_update_age_hours,
+                ..Config::default()
+            };
+            let mut filtered = filtered(&pending);
+
+            hold_back_recent(&mut filtered, &history, &config);
+
+            assert_eq!(
+                filtered.oldest_seen, oldest_seen,
+                "{} hours",
+                min_update_age_hours
+            );
+        }
+
+        let mut filtered = filtered(&["linux 6.9.1-1 -> 6.9.2-1"]);
+        hold_back_recent(&mut filtered, &history, &Config::default());
+        assert_eq!(filtered.oldest_seen, Some(now - 2 * hour));
+    }
+}
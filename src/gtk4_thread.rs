@@ -0,0 +1,44 @@
+use std::{
+    sync::{OnceLock, mpsc::channel},
+    thread,
+};
+
+use anyhow::Result;
+use tracing::info_span;
+
+/// Whether GTK could be initialized on its thread, once it was started.
+static STARTED: OnceLock<Result<(), String>> = OnceLock::new();
+
+/// Runs `f` on the thread that owns GTK, starting it on first use. GTK 4
+/// can't be initialized again from another thread, and the tray thread is
+/// restarted after failures, so every window lives on a thread of its own.
+pub fn invoke(f: impl FnOnce() + Send + 'static) -> Result<()> {
+    STARTED
+        .get_or_init(start)
+        .clone()
+        .map_err(anyhow::Error::msg)?;
+    glib::MainContext::default().invoke(f);
+    Ok(())
+}
+
+fn start() -> Result<(), String> {
+    let (started_tx, started) = channel();
+    thread::Builder::new()
+        .name("gtk".to_string())
+        .spawn(move || {
+            let _span = info_span!("gtk").entered();
+            // Also makes this thread the owner of the default main context,
+            // which `invoke` hands the closures to.
+            let result = gtk4::init().map_err(|e| format!("Failed to initialize GTK: {}", e));
+            let initialized = result.is_ok();
+            let _ = started_tx.send(result);
+            if initialized {
+                glib::MainLoop::new(None, false).run();
+            }
+        })
+        .map_err(|e| format!("Failed to start the GTK thread: {}", e))?;
+
+    started
+        .recv()
+        .unwrap_or_else(|_| Err("The GTK thread stopped".to_string()))
+}
@@ -0,0 +1,474 @@
+use std::{
+    sync::{Arc, Mutex, mpsc::Receiver, mpsc::Sender},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use tracing::{error, info};
+use tray_icon::{
+    Icon, TrayIconBuilder,
+    menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, Submenu},
+};
+
+use crate::{
+    Event,
+    appearance::{ColorScheme, IconTheme},
+    bus::StateUpdate,
+    changelog,
+    config::Config,
+    i18n::t,
+    maintenance::Action,
+    privileges, sources,
+    tray::{
+        ACKNOWLEDGE_ALL_MENU_ID, DEBUG_SNAPSHOT_MENU_ID, DOWNLOAD_UPDATES_MENU_ID,
+        INSTALL_UPDATES_MENU_ID, IconConfig, IconKind, MARK_NEWS_READ_MENU_ID, MenuLabel,
+        MenuSection, PAUSE_MENU_ID, SHOW_UPDATES_MENU_ID, TraySink, TrayState, UPDATE_NOW_MENU_ID,
+        local_prefix, render_icon, retry_until_shown, run_menu_entry, save_download_updates,
+        snapshot_header, write_diagnostics_snapshot,
+    },
+};
+
+/// The tray icon and the menu entries that change at runtime.
+struct GtkTray {
+    tray_icon: tray_icon::TrayIcon,
+    icon_config: IconConfig,
+    color_scheme: ColorScheme,
+    current_icon: IconKind,
+    list_of_updates_submenu: Submenu,
+    host_submenus: Vec<Submenu>,
+    source_submenus: Vec<Submenu>,
+    ignored_submenu: Option<Submenu>,
+    rebuild_submenu: Option<Submenu>,
+    statistics_submenu: Submenu,
+    update_now_item: MenuItem,
+    mark_news_read_item: Option<MenuItem>,
+    last_checked_item: MenuItem,
+    last_upgraded_item: MenuItem,
+    oldest_pending_item: MenuItem,
+    pause_item: MenuItem,
+    menu: Menu,
+    /// Where the advisories go in the menu, after the update submenus.
+    advisory_position: usize,
+    advisory_items: Vec<MenuItem>,
+}
+
+impl GtkTray {
+    /// Renders the current icon again for the new color scheme.
+    fn set_color_scheme(&mut self, color_scheme: ColorScheme) -> Result<()> {
+        self.color_scheme = color_scheme;
+        self.set_icon(self.current_icon)
+    }
+}
+
+impl TraySink for GtkTray {
+    fn set_icon(&mut self, icon: IconKind) -> Result<()> {
+        let image = render_icon(icon, &self.icon_config, self.color_scheme)?;
+        let (width, height) = image.dimensions();
+        let rendered =
+            Icon::from_rgba(image.into_raw(), width, height).context("Failed to create icon")?;
+        self.tray_icon
+            .set_icon(Some(rendered))
+            .context("Failed to set icon")?;
+        self.current_icon = icon;
+        Ok(())
+    }
+
+    fn set_tooltip(&mut self, tooltip: &str) -> Result<()> {
+        self.tray_icon
+            .set_tooltip(Some(tooltip))
+            .context("Failed to set tooltip")
+    }
+
+    fn set_submenu(
+        &mut self,
+        section: MenuSection,
+        title: &str,
+        lines: &[String],
+        enabled: bool,
+    ) -> Result<()> {
+        // Hosts, sources and checkrebuild only get a submenu at startup.
+        let submenu = match section {
+            MenuSection::Local => Some(&self.list_of_updates_submenu),
+            MenuSection::Host(index) => self.host_submenus.get(index),
+            MenuSection::Source(index) => self.source_submenus.get(index),
+            MenuSection::Ignored => self.ignored_submenu.as_ref(),
+            MenuSection::Rebuild => self.rebuild_submenu.as_ref(),
+            MenuSection::Statistics => Some(&self.statistics_submenu),
+        };
+        let Some(submenu) = submenu else {
+            return Ok(());
+        };
+
+        submenu.set_text(title);
+        // Clicking one of this machine's updates shows its changelog.
+        let clickable = section == MenuSection::Local;
+        replace_submenu_items(submenu, lines, enabled, clickable)
+            .with_context(|| format!("Failed to update menu items of {}", title))
+    }
+
+    fn set_label(&mut self, label: MenuLabel, text: &str) -> Result<()> {
+        match label {
+            MenuLabel::UpdateNow => self
+                .update_now_item
+                .set_text(privileges::action_label(text)),
+            MenuLabel::MarkNewsRead => {
+                if let Some(item) = &self.mark_news_read_item {
+                    item.set_text(text);
+                }
+            }
+            MenuLabel::LastChecked => self.last_checked_item.set_text(text),
+            MenuLabel::LastUpgraded => self.last_upgraded_item.set_text(text),
+            MenuLabel::OldestPending => self.oldest_pending_item.set_text(text),
+            MenuLabel::Pause => self.pause_item.set_text(text),
+        }
+        Ok(())
+    }
+
+    fn set_advisories(&mut self, messages: &[String]) -> Result<()> {
+        let current: Vec<String> = self.advisory_items.iter().map(MenuItem::text).collect();
+        if current == messages {
+            return Ok(());
+        }
+
+        for item in self.advisory_items.drain(..) {
+            self.menu
+                .remove(&item)
+                .context("Failed to remove advisory")?;
+        }
+        for (index, message) in messages.iter().enumerate() {
+            let item = MenuItem::new(message, true, None);
+            self.menu
+                .insert(&item, self.advisory_position + index)
+                .context("Failed to add advisory")?;
+            self.advisory_items.push(item);
+        }
+        Ok(())
+    }
+}
+
+/// Shows the tray icon with GTK and libappindicator until the app shuts
+/// down, or until the tray fails and has to be restarted.
+pub fn run(
+    mut config: Config,
+    app_tx: Sender<Event>,
+    updates: Arc<Mutex<Receiver<StateUpdate>>>,
+    color_schemes: Arc<Mutex<Receiver<ColorScheme>>>,
+) {
+    if retry_until_shown(|| gtk::init().context("Failed to initialize GTK")).is_none() {
+        return;
+    }
+
+    let color_scheme = match config.icon.theme {
+        IconTheme::Light => ColorScheme::Light,
+        IconTheme::Dark | IconTheme::Auto => ColorScheme::Dark,
+    };
+
+    let menu = Menu::new();
+
+    let list_of_updates_submenu = Submenu::new(
+        t!(
+            "local-pending-updates",
+            prefix = local_prefix(&config),
+            count = 0
+        ),
+        true,
+    );
+    let update_now_item = MenuItem::with_id(
+        UPDATE_NOW_MENU_ID,
+        privileges::action_label(&t!("update-now")),
+        true,
+        None,
+    );
+    let show_updates_item = MenuItem::with_id(SHOW_UPDATES_MENU_ID, t!("show-updates"), true, None);
+    let install_updates_item =
+        MenuItem::with_id(INSTALL_UPDATES_MENU_ID, t!("install-updates"), true, None);
+    let mark_news_read_item = config
+        .news
+        .enabled
+        .then(|| MenuItem::with_id(MARK_NEWS_READ_MENU_ID, t!("mark-news-read"), true, None));
+    let acknowledge_all_item =
+        MenuItem::with_id(ACKNOWLEDGE_ALL_MENU_ID, t!("acknowledge-all"), true, None);
+    let download_updates_item = CheckMenuItem::with_id(
+        DOWNLOAD_UPDATES_MENU_ID,
+        t!("download-in-background"),
+        true,
+        config.download_updates,
+        None,
+    );
+    let pause_item = MenuItem::with_id(PAUSE_MENU_ID, t!("pause-checks"), true, None);
+    let last_checked_item = MenuItem::new(t!("last-checked-never"), false, None);
+    let last_upgraded_item = MenuItem::new(t!("last-upgraded-unknown"), false, None);
+    let oldest_pending_item = MenuItem::new(t!("oldest-pending-none"), false, None);
+    let statistics_submenu = Submenu::new(t!("statistics"), true);
+
+    // One submenu per remote host, right below this machine's updates.
+    let host_submenus: Vec<Submenu> = config
+        .remote_hosts
+        .iter()
+        .map(|host| Submenu::new(t!("section-not-checked", name = host.name()), true))
+        .collect();
+
+    if let Err(e) = menu.append(&list_of_updates_submenu) {
+        error!("Failed to append menu item: {}", e);
+        return;
+    }
+
+    // Then one submenu per enabled non-pacman source.
+    let source_submenus: Vec<Submenu> = sources::enabled(&config.sources)
+        .into_iter()
+        .map(|name| Submenu::new(t!("section-not-checked", name = name), true))
+        .collect();
+
+    for submenu in host_submenus.iter().chain(&source_submenus) {
+        if let Err(e) = menu.append(submenu) {
+            error!("Failed to append menu item: {}", e);
+            return;
+        }
+    }
+
+    let ignored_submenu = config
+        .show_ignored_updates
+        .then(|| Submenu::new(t!("ignored-updates", count = 0), true));
+    if let Some(submenu) = &ignored_submenu
+        && let Err(e) = menu.append(submenu)
+    {
+        error!("Failed to append menu item: {}", e);
+        return;
+    }
+
+    let rebuild_submenu = config
+        .rebuild
+        .enabled
+        .then(|| Submenu::new(t!("needs-rebuild-not-checked"), false));
+    if let Some(submenu) = &rebuild_submenu
+        && let Err(e) = menu.append(submenu)
+    {
+        error!("Failed to append menu item: {}", e);
+        return;
+    }
+
+    let advisory_position = 1
+        + host_submenus.len()
+        + source_submenus.len()
+        + usize::from(ignored_submenu.is_some())
+        + usize::from(rebuild_submenu.is_some());
+
+    if let Err(e) = menu.append_items(&[&show_updates_item, &update_now_item]) {
+        error!("Failed to append menu item: {}", e);
+        return;
+    }
+
+    if config.install.enabled
+        && let Err(e) = menu.append(&install_updates_item)
+    {
+        error!("Failed to append menu item: {}", e);
+        return;
+    }
+
+    if let Some(item) = &mark_news_read_item
+        && let Err(e) = menu.append(item)
+    {
+        error!("Failed to append menu item: {}", e);
+        return;
+    }
+
+    if let Err(e) = menu.append_items(&[
+        &acknowledge_all_item,
+        &download_updates_item,
+        &pause_item,
+        &last_checked_item,
+        &last_upgraded_item,
+        &oldest_pending_item,
+        &statistics_submenu,
+    ]) {
+        error!("Failed to append menu item: {}", e);
+        return;
+    }
+
+    let maintenance_submenu = Submenu::new(t!("maintenance"), true);
+    for action in Action::ALL {
+        let item = MenuItem::with_id(
+            action.id(),
+            privileges::action_label(&action.label()),
+            true,
+            None,
+        );
+        if let Err(e) = maintenance_submenu.append(&item) {
+            error!("Failed to append menu item: {}", e);
+            return;
+        }
+    }
+
+    if let Err(e) = menu.append(&maintenance_submenu) {
+        error!("Failed to append menu item: {}", e);
+        return;
+    }
+
+    if config.debug_menu {
+        let debug_submenu = Submenu::new(t!("debug"), true);
+        let snapshot_item =
+            MenuItem::with_id(DEBUG_SNAPSHOT_MENU_ID, t!("save-menu-snapshot"), true, None);
+
+        if let Err(e) = debug_submenu.append_items(&[&snapshot_item]) {
+            error!("Failed to append menu item: {}", e);
+            return;
+        }
+
+        if let Err(e) = menu.append_items(&[&debug_submenu]) {
+            error!("Failed to append menu item: {}", e);
+            return;
+        }
+    }
+
+    let Some(tray_icon) = retry_until_shown(|| {
+        TrayIconBuilder::new()
+            .with_menu(Box::new(menu.clone()))
+            .build()
+            .context("Failed to build tray icon")
+    }) else {
+        return;
+    };
+
+    let mut tray = GtkTray {
+        tray_icon,
+        icon_config: config.icon.clone(),
+        color_scheme,
+        current_icon: IconKind::NoUpdates,
+        list_of_updates_submenu,
+        host_submenus,
+        source_submenus,
+        ignored_submenu,
+        rebuild_submenu,
+        statistics_submenu,
+        update_now_item,
+        mark_news_read_item,
+        last_checked_item,
+        last_upgraded_item,
+        oldest_pending_item,
+        pause_item,
+        menu,
+        advisory_position,
+        advisory_items: Vec::new(),
+    };
+
+    if let Err(e) = tray.set_icon(IconKind::NoUpdates) {
+        error!("{:#}", e);
+        return;
+    };
+
+    let mut state = TrayState::new(&config);
+
+    glib::timeout_add_local(Duration::from_millis(100), move || {
+        while let Ok(menu_event) = MenuEvent::receiver().try_recv() {
+            if menu_event.id == DOWNLOAD_UPDATES_MENU_ID {
+                // The item is already toggled.
+                let enabled = download_updates_item.is_checked();
+                if !save_download_updates(&mut config, enabled) {
+                    download_updates_item.set_checked(!enabled);
+                }
+            } else if menu_event.id == DEBUG_SNAPSHOT_MENU_ID {
+                let mut snapshot = snapshot_header(&state.status, &config);
+                snapshot.push_str(&submenu_snapshot(&tray.list_of_updates_submenu));
+                for submenu in tray.host_submenus.iter().chain(&tray.source_submenus) {
+                    snapshot.push_str(&submenu_snapshot(submenu));
+                }
+                if let Some(submenu) = &tray.ignored_submenu {
+                    snapshot.push_str(&submenu_snapshot(submenu));
+                }
+                if let Some(submenu) = &tray.rebuild_submenu {
+                    snapshot.push_str(&submenu_snapshot(submenu));
+                }
+                for item in &tray.advisory_items {
+                    snapshot.push_str(&format!("  {}\n", item.text()));
+                }
+                snapshot.push_str(&format!("  {}\n", show_updates_item.text()));
+                snapshot.push_str(&format!("  {}\n", tray.update_now_item.text()));
+                if config.install.enabled {
+                    snapshot.push_str(&format!("  {}\n", install_updates_item.text()));
+                }
+                if let Some(item) = &tray.mark_news_read_item {
+                    snapshot.push_str(&format!("  {}\n", item.text()));
+                }
+                snapshot.push_str(&format!("  {}\n", acknowledge_all_item.text()));
+                snapshot.push_str(&format!("  {}\n", download_updates_item.text()));
+                snapshot.push_str(&format!("  {}\n", tray.pause_item.text()));
+                snapshot.push_str(&format!("  {}\n", tray.last_checked_item.text()));
+                snapshot.push_str(&format!("  {}\n", tray.last_upgraded_item.text()));
+                snapshot.push_str(&format!("  {}\n", tray.oldest_pending_item.text()));
+                snapshot.push_str(&submenu_snapshot(&tray.statistics_submenu));
+
+                match write_diagnostics_snapshot(&snapshot) {
+                    Ok(path) => info!("Saved menu snapshot to {:?}", path),
+                    Err(e) => error!("Failed to save menu snapshot: {}", e),
+                }
+            } else {
+                run_menu_entry(&menu_event.id.0, &mut config, &state.status, &app_tx);
+            }
+        }
+
+        while let Ok(color_scheme) = color_schemes.lock().unwrap().try_recv() {
+            if let Err(e) = tray.set_color_scheme(color_scheme) {
+                error!("Failed to update tray: {:#}", e);
+                gtk::main_quit();
+                return glib::ControlFlow::Break;
+            }
+        }
+
+        while let Ok(update) = updates.lock().unwrap().try_recv() {
+            match state.apply(&mut tray, update, &mut config) {
+                Ok(true) => {}
+                Ok(false) => {
+                    gtk::main_quit();
+                    return glib::ControlFlow::Break;
+                }
+                Err(e) => {
+                    // Ends this run of the tray thread, so that it is
+                    // restarted.
+                    error!("Failed to update tray: {:#}", e);
+                    gtk::main_quit();
+                    return glib::ControlFlow::Break;
+                }
+            }
+        }
+
+        state.refresh_elapsed(&mut tray, &config);
+
+        glib::ControlFlow::Continue
+    });
+
+    gtk::main();
+}
+
+/// Replaces the plain menu items of `submenu` with one item per entry of `texts`.
+fn replace_submenu_items(
+    submenu: &Submenu,
+    texts: &[String],
+    enabled: bool,
+    clickable: bool,
+) -> tray_icon::menu::Result<()> {
+    for item in submenu.items() {
+        if let Some(_item) = item.as_menuitem() {
+            submenu.remove(_item)?;
+        }
+    }
+
+    for text in texts {
+        let item = match changelog::menu_id(text).filter(|_| clickable) {
+            Some(id) => MenuItem::with_id(id, text, enabled, None),
+            None => MenuItem::new(text, enabled, None),
+        };
+        submenu.append(&item)?;
+    }
+
+    Ok(())
+}
+
+fn submenu_snapshot(submenu: &Submenu) -> String {
+    let mut snapshot = format!("  {}\n", submenu.text());
+    for item in submenu.items() {
+        if let Some(_item) = item.as_menuitem() {
+            snapshot.push_str(&format!("    {}\n", _item.text()));
+        }
+    }
+    snapshot
+}
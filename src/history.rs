@@ -0,0 +1,364 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+
+use crate::migrations;
+
+const SECONDS_PER_WEEK: i64 = 60 * 60 * 24 * 7;
+
+/// How far back "recent" reaches for the biggest batch statistic.
+const RECENT_BATCH_SECONDS: i64 = 60 * 60 * 24 * 30;
+
+const MOST_UPDATED_PACKAGES_LIMIT: u32 = 5;
+
+/// How many of the latest checks are kept for the dashboard sparkline.
+const RECENT_CHECKS_LIMIT: u32 = 48;
+
+/// Aggregates over the recorded upgrades, shown in the tray's statistics submenu.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Statistics {
+    pub average_updates_per_week: f64,
+    pub biggest_recent_batch: Option<u32>,
+    pub most_updated_packages: Vec<(String, u32)>,
+    /// Pending update counts of the latest checks, oldest first.
+    pub recent_pending_counts: Vec<u32>,
+}
+
+/// Persistent record of every check result and detected upgrade transaction.
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    pub fn open() -> Result<Self> {
+        let data_dir = match dirs::data_dir() {
+            Some(dir) => dir.join("arch-updates-rs"),
+            None => {
+                bail!("Failed to get data directory");
+            }
+        };
+
+        std::fs::create_dir_all(&data_dir).context("Failed to create data directory")?;
+
+        let db_path = data_dir.join("history.db");
+        let conn = Connection::open(&db_path).context("Failed to open history database")?;
+
+        migrations::migrate_history(&conn, &db_path)?;
+
+        Ok(Self { conn })
+    }
+
+    /// A database that only lives as long as the value, for the tests.
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::migrate_history(&conn, std::path::Path::new(":memory:")).unwrap();
+        Self { conn }
+    }
+
+    /// Pretends `package` in `version` was first seen at `timestamp`.
+    #[cfg(test)]
+    pub fn set_first_seen(&self, package: &str, version: &str, timestamp: i64) {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO first_seen (package, version, timestamp)
+                VALUES (?1, ?2, ?3)",
+                params![package, version, timestamp],
+            )
+            .unwrap();
+    }
+
+    pub fn record_check(&self, updates: &[String]) -> Result<()> {
+        self.insert("checks", updates)
+    }
+
+    pub fn record_upgrade(&self, packages: &[String]) -> Result<()> {
+        self.insert("upgrades", packages)
+    }
+
+    pub fn statistics(&self) -> Result<Statistics> {
+        let now = unix_timestamp();
+
+        let (first_upgrade, total_updates): (Option<i64>, i64) = self
+            .conn
+            .query_row(
+                "SELECT MIN(timestamp), COALESCE(SUM(count), 0) FROM upgrades",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .context("Failed to query upgrade totals")?;
+
+        let average_updates_per_week = match first_upgrade {
+            Some(first_upgrade) => {
+                let weeks = ((now - first_upgrade) as f64 / SECONDS_PER_WEEK as f64).max(1.0);
+                total_updates as f64 / weeks
+            }
+            None => 0.0,
+        };
+
+        let biggest_recent_batch: Option<u32> = self
+            .conn
+            .query_row(
+                "SELECT MAX(count) FROM upgrades WHERE timestamp >= ?1",
+                params![now - RECENT_BATCH_SECONDS],
+                |row| row.get(0),
+            )
+            .context("Failed to query biggest recent batch")?;
+
+        // Entries are stored as checkupdates lines ("name old -> new"), so the
+        // package name is everything before the first space.
+        let mut statement = self
+            .conn
+            .prepare(
+                "SELECT substr(value, 1, instr(value || ' ', ' ') - 1) AS name, COUNT(*) AS times
+                FROM upgrades, json_each(upgrades.packages)
+                GROUP BY name
+                ORDER BY times DESC, name ASC
+                LIMIT ?1",
+            )
+            .context("Failed to prepare most updated packages query")?;
+        let most_updated_packages = statement
+            .query_map(params![MOST_UPDATED_PACKAGES_LIMIT], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .context("Failed to query most updated packages")?
+            .collect::<rusqlite::Result<Vec<(String, u32)>>>()?;
+
+        let mut statement = self
+            .conn
+            .prepare("SELECT count FROM checks ORDER BY id DESC LIMIT ?1")
+            .context("Failed to prepare recent checks query")?;
+        let mut recent_pending_counts = statement
+            .query_map(params![RECENT_CHECKS_LIMIT], |row| row.get(0))
+            .context("Failed to query recent checks")?
+            .collect::<rusqlite::Result<Vec<u32>>>()?;
+        recent_pending_counts.reverse();
+
+        Ok(Statistics {
+            average_updates_per_week,
+            biggest_recent_batch,
+            most_updated_packages,
+            recent_pending_counts,
+        })
+    }
+
+    /// When each of the pending `updates` was first seen, as unix timestamp,
+    /// in the same order. Updates seen for the first time are recorded now,
+    /// the ones no longer pending are forgotten.
+    pub fn first_seen(&self, updates: &[String]) -> Result<Vec<i64>> {
+        let now = unix_timestamp();
+        let versions: Vec<(&str, &str)> = updates
+            .iter()
+            .map(|update| {
+                // "name old -> new", the new version is the last word.
+                let mut words = update.split_whitespace();
+                let name = words.next().unwrap_or_default();
+                (name, words.next_back().unwrap_or_default())
+            })
+            .collect();
+
+        let transaction = self
+            .conn
+            .unchecked_transaction()
+            .context("Failed to start recording first seen updates")?;
+        transaction
+            .execute(
+                "DELETE FROM first_seen WHERE (package, version) NOT IN
+                    (SELECT json_extract(value, '$[0]'), json_extract(value, '$[1]')
+                    FROM json_each(?1))",
+                params![serde_json::to_string(&versions)?],
+            )
+            .context("Failed to forget updates that are no longer pending")?;
+
+        let mut timestamps = Vec::with_capacity(versions.len());
+        for (name, version) in &versions {
+            transaction
+                .execute(
+                    "INSERT OR IGNORE INTO first_seen (package, version, timestamp)
+                    VALUES (?1, ?2, ?3)",
+                    params![name, version, now],
+                )
+                .context("Failed to record first seen update")?;
+            timestamps.push(
+                transaction
+                    .query_row(
+                        "SELECT timestamp FROM first_seen WHERE package = ?1 AND version = ?2",
+                        params![name, version],
+                        |row| row.get(0),
+                    )
+                    .context("Failed to query when an update was first seen")?,
+            );
+        }
+        transaction
+            .commit()
+            .context("Failed to record first seen updates")?;
+
+        Ok(timestamps)
+    }
+
+    /// Deletes the check results older than `max_age_days`. The upgrades are
+    /// kept, the statistics are computed from them.
+    pub fn prune_checks(&self, max_age_days: u32) -> Result<usize> {
+        let cutoff = unix_timestamp() - max_age_days as i64 * 60 * 60 * 24;
+        self.conn
+            .execute("DELETE FROM checks WHERE timestamp < ?1", params![cutoff])
+            .context("Failed to prune checks")
+    }
+
+    /// Gives the space of deleted rows back to the file system.
+    pub fn vacuum(&self) -> Result<()> {
+        self.conn
+            .execute_batch("VACUUM")
+            .context("Failed to vacuum history database")
+    }
+
+    /// When housekeeping last ran, as unix timestamp.
+    pub fn last_housekeeping(&self) -> Result<Option<i64>> {
+        self.meta("last_housekeeping")
+            .context("Failed to read last housekeeping")
+    }
+
+    pub fn set_last_housekeeping(&self, timestamp: i64) -> Result<()> {
+        self.set_meta("last_housekeeping", timestamp)
+            .context("Failed to record housekeeping")
+    }
+
+    /// How many packages the upgrades since `timestamp` updated.
+    pub fn upgraded_since(&self, timestamp: i64) -> Result<u64> {
+        self.conn
+            .query_row(
+                "SELECT COALESCE(SUM(count), 0) FROM upgrades WHERE timestamp >= ?1",
+                params![timestamp],
+                |row| row.get(0),
+            )
+            .context("Failed to query recent upgrades")
+    }
+
+    /// When the weekly digest was last sent, as unix timestamp.
+    pub fn last_digest(&self) -> Result<Option<i64>> {
+        self.meta("last_digest")
+            .context("Failed to read when the digest was sent")
+    }
+
+    pub fn set_last_digest(&self, timestamp: i64) -> Result<()> {
+        self.set_meta("last_digest", timestamp)
+            .context("Failed to record that the digest was sent")
+    }
+
+    /// When the user last marked the Arch news read, as unix timestamp.
+    pub fn news_read_at(&self) -> Result<Option<i64>> {
+        self.meta("news_read_at")
+            .context("Failed to read when the news were read")
+    }
+
+    pub fn set_news_read_at(&self, timestamp: i64) -> Result<()> {
+        self.set_meta("news_read_at", timestamp)
+            .context("Failed to record that the news were read")
+    }
+
+    fn meta(&self, key: &str) -> rusqlite::Result<Option<i64>> {
+        self.conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    fn set_meta(&self, key: &str, value: i64) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    fn insert(&self, table: &str, packages: &[String]) -> Result<()> {
+        let timestamp = unix_timestamp();
+        let packages_json = serde_json::to_string(packages)?;
+
+        self.conn
+            .execute(
+                &format!(
+                    "INSERT INTO {} (timestamp, count, packages) VALUES (?1, ?2, ?3)",
+                    table
+                ),
+                params![timestamp, packages.len() as i64, packages_json],
+            )
+            .with_context(|| format!("Failed to insert into {}", table))?;
+
+        Ok(())
+    }
+}
+
+pub fn unix_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn updates(updates: &[&str]) -> Vec<String> {
+        updates.iter().map(|update| update.to_string()).collect()
+    }
+
+    #[test]
+    fn remembers_when_updates_were_first_seen() {
+        let history = History::in_memory();
+        history.set_first_seen("firefox", "126.0-1", 1000);
+
+        let first_seen = history
+            .first_seen(&updates(&[
+                "firefox 125.0-1 -> 126.0-1",
+                "linux 6.9.1-1 -> 6.9.2-1",
+            ]))
+            .unwrap();
+        assert_eq!(first_seen[0], 1000);
+        assert!(first_seen[1] >= unix_timestamp() - 5);
+        let linux_seen = first_seen[1];
+
+        // A newer version is a new update, and firefox is no longer pending.
+        history.set_first_seen("linux", "6.9.2-1", 2000);
+        let first_seen = history
+            .first_seen(&updates(&[
+                "linux 6.9.1-1 -> 6.9.2-1",
+                "nss 3.100-1 -> 3.101-1",
+                "firefox 125.0-1 -> 126.0.1-1",
+            ]))
+            .unwrap();
+        assert_eq!(first_seen[0], 2000);
+        assert!(first_seen[1] >= linux_seen);
+        assert!(first_seen[2] >= linux_seen);
+
+        let forgotten: i64 = history
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM first_seen WHERE package = 'firefox' AND version = '126.0-1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(forgotten, 0);
+    }
+
+    #[test]
+    fn forgets_everything_without_updates() {
+        let history = History::in_memory();
+        history.set_first_seen("firefox", "126.0-1", 1000);
+
+        assert_eq!(history.first_seen(&[]).unwrap(), Vec::<i64>::new());
+
+        let remaining: i64 = history
+            .conn
+            .query_row("SELECT COUNT(*) FROM first_seen", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    process::{Command, Stdio},
+    thread,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::status::Status;
+
+/// Shell commands run on events, with the details passed as environment
+/// variables.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    pub on_updates_found: Option<String>,
+    pub on_no_updates: Option<String>,
+    pub on_upgrade_detected: Option<String>,
+}
+
+/// Runs the hooks matching the transition from `previous` to `current`.
+pub fn run_for_check(config: &HooksConfig, previous: &Status, current: &Status) {
+    if current.updates.is_empty() {
+        // The first check has no previous state to compare against.
+        if previous.last_checked.is_none() || !previous.updates.is_empty() {
+            run("on_no_updates", &config.on_no_updates, current, &[]);
+        }
+    } else if previous.updates != current.updates {
+        run(
+            "on_updates_found",
+            &config.on_updates_found,
+            current,
+            &current.updates,
+        );
+    }
+}
+
+/// Runs the `on_upgrade_detected` hook with the packages that were upgraded.
+pub fn run_for_upgrade(config: &HooksConfig, current: &Status, upgraded_packages: &[String]) {
+    run(
+        "on_upgrade_detected",
+        &config.on_upgrade_detected,
+        current,
+        upgraded_packages,
+    );
+}
+
+fn run(name: &'static str, command: &Option<String>, status: &Status, packages: &[String]) {
+    let Some(command) = command else {
+        return;
+    };
+
+    info!("Running {} hook", name);
+
+    let child = Command::new("sh")
+        .args(["-c", command])
+        .env("UPDATE_COUNT", status.updates.len().to_string())
+        .env("UPDATE_LIST", packages.join("\n"))
+        .env("UPDATE_SEVERITY", status.severity.as_str())
+        .stdin(Stdio::null())
+        .spawn();
+
+    match child {
+        Ok(mut child) => {
+            thread::spawn(move || match child.wait() {
+                Ok(exit_status) if !exit_status.success() => {
+                    warn!("{} hook exited with {}", name, exit_status)
+                }
+                Ok(_) => {}
+                Err(e) => error!("Failed to wait for {} hook: {}", name, e),
+            });
+        }
+        Err(e) => error!("Failed to run {} hook: {}", name, e),
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    fs::{self, OpenOptions},
+    path::Path,
+    sync::mpsc::Sender,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    Event, auto_update,
+    history::{History, unix_timestamp},
+    sources::{Sources, SourcesConfig},
+};
+
+const HOUSEKEEPING_INTERVAL_SECS: i64 = 60 * 60 * 24 * 7;
+
+/// How often to check whether housekeeping is due. The daemon rarely runs
+/// for a whole week, so the time of the last run is stored in the history.
+const POLL_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct HousekeepingConfig {
+    /// Check results older than this are deleted from the history. The
+    /// upgrades, which the statistics are based on, are kept.
+    pub history_retention_days: u32,
+}
+
+impl Default for HousekeepingConfig {
+    fn default() -> Self {
+        Self {
+            history_retention_days: 365,
+        }
+    }
+}
+
+/// Keeps the daemon's own data from growing without bound.
+pub struct Housekeeping {
+    last_run: Option<i64>,
+}
+
+impl Housekeeping {
+    pub fn new(history: Option<&History>) -> Self {
+        let last_run = history.and_then(|history| match history.last_housekeeping() {
+            Ok(last_run) => last_run,
+            Err(e) => {
+                error!("{:#}", e);
+                None
+            }
+        });
+
+        Self { last_run }
+    }
+
+    /// Runs the weekly housekeeping if the last run is a week ago.
+    pub fn run_if_due(
+        &mut self,
+        config: &HousekeepingConfig,
+        history: Option<&History>,
+        sources: &Sources,
+        sources_config: &SourcesConfig,
+    ) {
+        let now = unix_timestamp();
+        if self
+            .last_run
+            .is_some_and(|last_run| now - last_run < HOUSEKEEPING_INTERVAL_SECS)
+        {
+            return;
+        }
+
+        info!("Running weekly housekeeping");
+
+        if let Some(history) = history {
+            match history.prune_checks(config.history_retention_days) {
+                Ok(pruned) => info!("Pruned {} old checks from the history", pruned),
+                Err(e) => error!("{:#}", e),
+            }
+            if let Err(e) = history.vacuum() {
+                error!("{:#}", e);
+            }
+            if let Err(e) = history.set_last_housekeeping(now) {
+                error!("{:#}", e);
+            }
+        }
+
+        // The program's own log is rotated by size as it is written.
+        if let Err(e) = auto_update::log_path().and_then(|log_path| rotate_log(&log_path)) {
+            error!("Failed to rotate log: {:#}", e);
+        }
+
+        sources.expire_cache(sources_config);
+
+        self.last_run = Some(now);
+    }
+}
+
+/// Sends `Event::Housekeeping` regularly, so the main loop can check whether
+/// housekeeping is due.
+pub fn schedule(tx: Sender<Event>) {
+    thread::spawn(move || {
+        while tx.send(Event::Housekeeping).is_ok() {
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+/// Keeps the previous week's log as `<name>.1`. The log is copied and
+/// truncated rather than renamed, since the logger keeps it open in append
+/// mode.
+fn rotate_log(log_path: &Path) -> Result<()> {
+    if !log_path.exists() {
+        return Ok(());
+    }
+
+    let mut rotated_path = log_path.as_os_str().to_owned();
+    rotated_path.push(".1");
+
+    fs::copy(log_path, &rotated_path).with_context(|| format!("Failed to copy {:?}", log_path))?;
+    OpenOptions::new()
+        .write(true)
+        .open(log_path)
+        .and_then(|file| file.set_len(0))
+        .with_context(|| format!("Failed to truncate {:?}", log_path))?;
+
+    Ok(())
+}
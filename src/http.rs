@@ -0,0 +1,87 @@
+use std::{
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use anyhow::{Result, anyhow};
+use tiny_http::{Header, Response, Server};
+use tracing::{error, info};
+
+use crate::{
+    activation, metrics,
+    status::{Status, StatusResponse},
+};
+
+const DASHBOARD_HTML: &str = include_str!("../assets/dashboard.html");
+
+/// Serves the current status as JSON on `GET /status`, the daemon's own
+/// counters on `GET /metrics`, and a dashboard rendering the status on `GET /`.
+/// Serves on the socket systemd passed, if it passed one, otherwise on
+/// `address`, if set.
+pub fn serve(address: Option<&str>, status: Arc<Mutex<Status>>) -> Result<()> {
+    let server = if let Some(socket) = activation::take(activation::HTTP) {
+        let listener = TcpListener::from(socket);
+        info!("Serving status on {:?} from systemd", listener.local_addr());
+        Server::from_listener(listener, None)
+            .map_err(|e| anyhow!("Failed to start HTTP server on systemd's socket: {}", e))?
+    } else if let Some(address) = address {
+        let server = Server::http(address)
+            .map_err(|e| anyhow!("Failed to start HTTP server on {}: {}", address, e))?;
+        info!("Serving status on http://{}/", address);
+        server
+    } else {
+        return Ok(());
+    };
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let result = match request.url() {
+                "/" => {
+                    let content_type =
+                        Header::from_bytes(&b"Content-Type"[..], &b"text/html; charset=utf-8"[..])
+                            .unwrap();
+                    request.respond(Response::from_string(DASHBOARD_HTML).with_header(content_type))
+                }
+                "/status" => {
+                    let body = {
+                        let status = status.lock().unwrap();
+                        serde_json::to_string(&StatusResponse::new(&status))
+                    };
+
+                    match body {
+                        Ok(body) => {
+                            let content_type =
+                                Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                                    .unwrap();
+                            request.respond(Response::from_string(body).with_header(content_type))
+                        }
+                        Err(e) => {
+                            error!("Failed to serialize status: {}", e);
+                            request.respond(Response::empty(500))
+                        }
+                    }
+                }
+                "/metrics" => match serde_json::to_string(&metrics::snapshot()) {
+                    Ok(body) => {
+                        let content_type =
+                            Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                                .unwrap();
+                        request.respond(Response::from_string(body).with_header(content_type))
+                    }
+                    Err(e) => {
+                        error!("Failed to serialize metrics: {}", e);
+                        request.respond(Response::empty(500))
+                    }
+                },
+                _ => request.respond(Response::empty(404)),
+            };
+
+            if let Err(e) = result {
+                error!("Failed to respond to HTTP request: {}", e);
+            }
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::{
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    i18n::t,
+    state::AppState,
+    status::{Severity, Status},
+};
+
+/// Notifications shown by Hyprland itself when the state changes.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct HyprlandConfig {
+    pub enabled: bool,
+    /// How long a notification stays on screen.
+    pub notify_secs: u64,
+}
+
+impl Default for HyprlandConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            notify_secs: 5,
+        }
+    }
+}
+
+/// The icons of Hyprland's `notify` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Icon {
+    Warning = 0,
+    Info = 1,
+    Error = 3,
+    Ok = 5,
+}
+
+/// A notification about the state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    icon: Icon,
+    text: String,
+}
+
+/// What to tell about `status`. `None` for the states that pass by quickly,
+/// like a running check.
+pub fn message(status: &Status) -> Option<Message> {
+    let (icon, text) = match &status.state {
+        AppState::Idle if status.last_checked.is_some() => {
+            (Icon::Ok, t!("pending-updates", count = 0))
+        }
+        AppState::UpdatesAvailable { count } => {
+            let icon = match status.severity {
+                Severity::None | Severity::Normal => Icon::Info,
+                Severity::Warning => Icon::Warning,
+                Severity::Critical => Icon::Error,
+            };
+            (icon, t!("pending-updates", count = count))
+        }
+        AppState::Error { error } => (Icon::Error, t!("state-error", error = error)),
+        AppState::Paused => (Icon::Info, t!("state-paused")),
+        AppState::Offline => (Icon::Warning, t!("state-offline")),
+        AppState::Idle | AppState::Checking | AppState::Upgrading => return None,
+    };
+
+    Some(Message {
+        icon,
+        // Commands end at the first newline.
+        text: text.replace('\n', " "),
+    })
+}
+
+/// The command socket of the Hyprland instance the program runs in.
+fn socket_path() -> Result<PathBuf> {
+    let signature =
+        std::env::var("HYPRLAND_INSTANCE_SIGNATURE").context("Hyprland is not running")?;
+
+    // Hyprland 0.40 moved its sockets from /tmp into the runtime directory.
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        let path = PathBuf::from(dir)
+            .join("hypr")
+            .join(&signature)
+            .join(".socket.sock");
+        if path.exists() {
+            return Ok(path);
+        }
+    }
+
+    Ok(PathBuf::from("/tmp/hypr")
+        .join(signature)
+        .join(".socket.sock"))
+}
+
+/// Shows `message` as a Hyprland notification.
+pub fn notify(config: &HyprlandConfig, message: &Message) -> Result<()> {
+    let mut stream =
+        UnixStream::connect(socket_path()?).context("Failed to connect to Hyprland")?;
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .context("Failed to connect to Hyprland")?;
+    write!(
+        stream,
+        "notify {} {} 0 arch-updates-rs: {}",
+        message.icon as i32,
+        config.notify_secs * 1000,
+        message.text
+    )
+    .context("Failed to send notification to Hyprland")?;
+
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .context("Failed to read Hyprland's reply")?;
+    if reply.trim() != "ok" {
+        bail!("Hyprland refused the notification: {}", reply.trim());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use std::{env, fs, path::PathBuf, sync::OnceLock};
+
+use fluent_bundle::{FluentArgs, FluentResource, concurrent::FluentBundle};
+use tracing::{info, warn};
+use unic_langid::LanguageIdentifier;
+
+/// The English messages. Messages a translation leaves out fall back to
+/// them.
+const ENGLISH: &str = include_str!("../locales/en/main.ftl");
+
+/// Translations that aren't built in are read from
+/// `<dir>/<locale>/main.ftl`, the user's data directory first.
+const SYSTEM_LOCALES_DIR: &str = "/usr/share/arch-updates-rs/locales";
+
+/// Consulted in this order, like gettext does.
+const LOCALE_VARIABLES: &[&str] = &["LC_ALL", "LC_MESSAGES", "LANG"];
+
+/// Looks up the message `id` of the user's language and fills in `args`,
+/// e.g. `t!("pending-updates", count = 3)`.
+macro_rules! t {
+    ($id:literal) => {
+        $crate::i18n::translate($id, &[])
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::translate($id, &[$((stringify!($name), $value.to_string())),+])
+    };
+}
+pub(crate) use t;
+
+type Bundle = FluentBundle<FluentResource>;
+
+struct Catalogs {
+    translation: Option<Bundle>,
+    english: Bundle,
+}
+
+fn catalogs() -> &'static Catalogs {
+    static CATALOGS: OnceLock<Catalogs> = OnceLock::new();
+
+    CATALOGS.get_or_init(|| Catalogs {
+        translation: requested_locales()
+            .iter()
+            .take_while(|locale| !locale.starts_with("en"))
+            .find_map(|locale| load_translation(locale)),
+        english: bundle("en", ENGLISH.to_string()),
+    })
+}
+
+pub fn translate(id: &str, args: &[(&str, String)]) -> String {
+    let catalogs = catalogs();
+    match catalogs
+        .translation
+        .iter()
+        .find_map(|translation| format(translation, id, args))
+        .or_else(|| format(&catalogs.english, id, args))
+    {
+        Some(text) => text,
+        None => {
+            warn!("Missing message {:?}", id);
+            id.to_string()
+        }
+    }
+}
+
+/// The message `id` of `bundle` with `args` filled in, or `None` if the
+/// bundle doesn't have it.
+fn format(bundle: &Bundle, id: &str, args: &[(&str, String)]) -> Option<String> {
+    let pattern = bundle.get_message(id)?.value()?;
+
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        // Numbers pick the plural variant, e.g. `[one]` for a count of 1.
+        match value.parse::<i64>() {
+            Ok(number) if number.to_string() == *value => fluent_args.set(*name, number),
+            _ => fluent_args.set(*name, value.as_str()),
+        }
+    }
+
+    let mut errors = Vec::new();
+    let text = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+    for error in errors {
+        warn!("Failed to format message {:?}: {}", id, error);
+    }
+    Some(text.into_owned())
+}
+
+/// The locales to try, e.g. `de-AT` and then `de` for `LANG=de_AT.UTF-8`.
+fn requested_locales() -> Vec<String> {
+    let Some(value) = LOCALE_VARIABLES
+        .iter()
+        .filter_map(|variable| env::var(variable).ok())
+        .find(|value| !value.is_empty())
+    else {
+        return Vec::new();
+    };
+
+    let name = value.split(['.', '@']).next().unwrap_or_default();
+    if name.is_empty() || name == "C" || name == "POSIX" {
+        return Vec::new();
+    }
+
+    let locale = name.replace('_', "-");
+    let mut locales = vec![locale.clone()];
+    if let Some((language, _)) = locale.split_once('-') {
+        locales.push(language.to_string());
+    }
+    locales
+}
+
+fn load_translation(locale: &str) -> Option<Bundle> {
+    let user_dir = dirs::data_dir().map(|dir| dir.join("arch-updates-rs").join("locales"));
+    user_dir
+        .into_iter()
+        .chain([PathBuf::from(SYSTEM_LOCALES_DIR)])
+        .map(|dir| dir.join(locale).join("main.ftl"))
+        .find_map(|path| {
+            let source = fs::read_to_string(&path).ok()?;
+            info!("Using the {} translation from {:?}", locale, path);
+            Some(bundle(locale, source))
+        })
+}
+
+/// The messages of the Fluent file `source`. Messages with syntax errors are
+/// left out, the rest of the file still works.
+fn bundle(locale: &str, source: String) -> Bundle {
+    let resource = FluentResource::try_new(source).unwrap_or_else(|(resource, errors)| {
+        for error in errors {
+            warn!(
+                "Skipping a message of the {} translation: {}",
+                locale, error
+            );
+        }
+        resource
+    });
+
+    let language = locale.parse::<LanguageIdentifier>().unwrap_or_default();
+    let mut bundle = FluentBundle::new_concurrent(vec![language]);
+    // The isolation marks show up as boxes in some tray implementations.
+    bundle.set_use_isolating(false);
+    if let Err(errors) = bundle.add_resource(resource) {
+        for error in errors {
+            warn!(
+                "Skipping a message of the {} translation: {}",
+                locale, error
+            );
+        }
+    }
+    bundle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+pending-updates = { $count ->
+    [one] { $count } pending update
+   *[other] { $count } pending updates
+}
+section-pending-updates = { $name }: { $count } pending updates
+";
+
+    #[test]
+    fn picks_the_plural_variant() {
+        let bundle = bundle("en", SOURCE.to_string());
+        let cases = [
+            (
+                "pending-updates",
+                &[("count", "0")][..],
+                "0 pending updates",
+            ),
+            ("pending-updates", &[("count", "1")][..], "1 pending update"),
+            (
+                "pending-updates",
+                &[("count", "2")][..],
+                "2 pending updates",
+            ),
+            (
+                "section-pending-updates",
+                &[("name", "007"), ("count", "3")][..],
+                "007: 3 pending updates",
+            ),
+        ];
+
+        for (id, args, expected) in cases {
+            let args: Vec<(&str, String)> = args
+                .iter()
+                .map(|(name, value)| (*name, value.to_string()))
+                .collect();
+            assert_eq!(format(&bundle, id, &args).as_deref(), Some(expected));
+        }
+        assert_eq!(format(&bundle, "missing", &[]), None);
+    }
+
+    #[test]
+    fn english_has_every_message_in_one_piece() {
+        let resource = FluentResource::try_new(ENGLISH.to_string());
+        assert!(resource.is_ok(), "{:?}", resource.err().map(|(_, e)| e));
+        assert_eq!(
+            translate("new-updates", &[("count", "1".to_string())]),
+            "1 new update"
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+    sync::mpsc::Sender,
+    thread,
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{Event, pacman_log};
+
+/// Runs the upgrade without a terminal. pkexec asks for the password through
+/// the desktop's polkit agent.
+const INSTALL_COMMAND: &[&str] = &["pkexec", "pacman", "-Syu", "--noconfirm"];
+
+/// Exit codes of pkexec when the authorization was dismissed or failed.
+const PKEXEC_NOT_AUTHORIZED: i32 = 126;
+const PKEXEC_AUTHENTICATION_FAILED: i32 = 127;
+
+/// Installing updates from the menu, without a terminal.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct InstallConfig {
+    /// Adds the "Install updates" action. `--noconfirm` answers every
+    /// question with the default, so this is off unless asked for.
+    pub enabled: bool,
+}
+
+/// Installs the pending updates in the background. Every line pacman prints
+/// is reported as `Event::Progress`, the outcome as `Event::InstallFinished`.
+/// The output is more detailed than the log, so the log isn't followed.
+pub fn start(tx: Sender<Event>) {
+    thread::spawn(move || {
+        let result = run(&tx);
+        match &result {
+            Ok(()) => info!(event = "install", "Installed updates"),
+            Err(e) => error!("Failed to install updates: {:#}", e),
+        }
+        let _ = tx.send(Event::InstallFinished(
+            result.map_err(|e| format!("{:#}", e)),
+        ));
+    });
+}
+
+fn run(tx: &Sender<Event>) -> Result<()> {
+    info!("Installing updates with {}", INSTALL_COMMAND.join(" "));
+
+    let mut child = Command::new(INSTALL_COMMAND[0])
+        .args(&INSTALL_COMMAND[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to start pkexec")?;
+
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            let line = line.context("Failed to read pacman output")?;
+            let line = line.trim();
+            if !line.is_empty() {
+                let _ = tx.send(Event::Progress(pacman_log::progress_from_output(line)));
+            }
+        }
+    }
+
+    let status = child.wait()?;
+    match status.code() {
+        Some(0) => Ok(()),
+        Some(PKEXEC_NOT_AUTHORIZED) | Some(PKEXEC_AUTHENTICATION_FAILED) => {
+            bail!("Authorization was cancelled or failed")
+        }
+        _ => bail!("pacman exited with {}", status),
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, bail};
+use fs2::FileExt;
+use tracing::{info, warn};
+
+use crate::control;
+
+const LOCK_FILE_NAME: &str = "arch-updates-rs.lock";
+
+/// How long `--replace` waits for the running instance to exit. Shutting
+/// down waits up to 2 seconds for the threads.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(5);
+const REPLACE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Takes the instance lock in `runtime_dir` and writes this process's PID
+/// into the lock file. A lock whose holder is no longer running, which
+/// happens on file systems that keep locks after a crash, is taken over.
+/// Returns `None` while another instance runs.
+pub fn lock(runtime_dir: &Path) -> Result<Option<File>> {
+    let lock_path = runtime_dir.join(LOCK_FILE_NAME);
+    let mut lock_file = open(&lock_path)?;
+    if lock_file.try_lock_exclusive().is_err() {
+        // Versions that didn't write their PID are assumed to be running.
+        match holder(&mut lock_file) {
+            Some(pid) if !is_running(pid) => {
+                warn!(
+                    "The lock is held by process {}, which is no longer running. Taking it over",
+                    pid
+                );
+            }
+            _ => return Ok(None),
+        }
+
+        // The stale lock stays with the old file, so start over with a new one.
+        fs::remove_file(&lock_path).context("Failed to remove stale lock file")?;
+        lock_file = open(&lock_path)?;
+        if lock_file.try_lock_exclusive().is_err() {
+            // Another instance took it over first.
+            return Ok(None);
+        }
+    }
+
+    lock_file.set_len(0).context("Failed to write lock file")?;
+    lock_file.rewind().context("Failed to write lock file")?;
+    writeln!(lock_file, "{}", std::process::id()).context("Failed to write lock file")?;
+
+    Ok(Some(lock_file))
+}
+
+/// Asks the running instance to exit and takes the lock once it did.
+pub fn replace(runtime_dir: &Path) -> Result<File> {
+    if let Some(lock_file) = lock(runtime_dir)? {
+        return Ok(lock_file);
+    }
+
+    info!("Asking the running instance to exit");
+    control::request_quit()?;
+
+    let deadline = Instant::now() + REPLACE_TIMEOUT;
+    while Instant::now() < deadline {
+        thread::sleep(REPLACE_POLL_INTERVAL);
+        if let Some(lock_file) = lock(runtime_dir)? {
+            return Ok(lock_file);
+        }
+    }
+
+    bail!(
+        "The running instance didn't exit within {} seconds",
+        REPLACE_TIMEOUT.as_secs()
+    )
+}
+
+/// Opens the lock file without truncating it, which would erase the PID of
+/// the instance holding it.
+fn open(lock_path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path)
+        .context("Failed to create lock file")
+}
+
+/// The PID written into the lock file by the instance holding it.
+fn holder(lock_file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    lock_file.rewind().ok()?;
+    lock_file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+/// Whether a process with `pid` exists. A process that got the PID after the
+/// instance died keeps the lock in place, like before PIDs were written.
+fn is_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
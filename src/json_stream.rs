@@ -0,0 +1,61 @@
+use std::{
+    io::{Write, stdout},
+    sync::mpsc::Receiver,
+    thread::{self, JoinHandle},
+};
+
+use chrono::Utc;
+use serde_json::{Value, json};
+use tracing::{error, info_span};
+
+use crate::{bus::StateUpdate, state_file};
+
+/// Prints one JSON object per line on stdout for every update, for programs
+/// that supervise the app, until it shuts down.
+pub fn start(updates: Receiver<StateUpdate>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let _span = info_span!("json-stream").entered();
+        for update in updates {
+            if matches!(update, StateUpdate::Config(_)) {
+                continue;
+            }
+            let shutdown = matches!(update, StateUpdate::Shutdown);
+
+            let mut event = match update {
+                StateUpdate::State(state) => {
+                    let mut event = json!({ "event": "state" });
+                    // `{"state": "error", "error": "..."}` and the like.
+                    if let Ok(Value::Object(state)) = serde_json::to_value(&state) {
+                        event.as_object_mut().unwrap().extend(state);
+                    }
+                    event
+                }
+                StateUpdate::Status(status) => {
+                    let mut event = json!({ "event": "status" });
+                    if let Value::Object(payload) = state_file::payload(&status) {
+                        event.as_object_mut().unwrap().extend(payload);
+                    }
+                    event
+                }
+                StateUpdate::Progress(progress) => {
+                    json!({ "event": "progress", "progress": progress })
+                }
+                StateUpdate::UpgradeDetected(packages) => {
+                    json!({ "event": "upgrade_detected", "packages": packages })
+                }
+                StateUpdate::Shutdown => json!({ "event": "shutdown" }),
+                StateUpdate::Config(_) => continue,
+            };
+            event["time"] = json!(Utc::now());
+
+            // Stdout is line buffered, so every event is flushed on its own.
+            if let Err(e) = writeln!(stdout(), "{}", event) {
+                error!("Failed to write event to stdout: {}", e);
+                return;
+            }
+            if shutdown {
+                return;
+            }
+        }
+    })
+}
@@ -0,0 +1,50 @@
+use std::fs;
+
+use serde::Serialize;
+
+/// Same as `uname -r`.
+const RUNNING_RELEASE_PATH: &str = "/proc/sys/kernel/osrelease";
+
+/// Kernel packages install their modules to `<release>/` in here, along
+/// with the kernel image as `vmlinuz`.
+const MODULES_DIR: &str = "/usr/lib/modules";
+
+/// The running kernel isn't installed anymore, because it was upgraded
+/// without a reboot since. Its modules are gone, so hardware that needs a
+/// module that wasn't loaded yet doesn't work, and hibernation may fail.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KernelMismatch {
+    pub running: String,
+    pub installed: Vec<String>,
+}
+
+/// Compares the running kernel's release with the installed kernels.
+pub fn mismatch() -> Option<KernelMismatch> {
+    let running = fs::read_to_string(RUNNING_RELEASE_PATH)
+        .ok()?
+        .trim()
+        .to_string();
+    let installed = installed_releases();
+
+    // Without any packaged kernel, e.g. in a container, there is nothing to
+    // compare against.
+    if installed.is_empty() || installed.contains(&running) {
+        return None;
+    }
+
+    Some(KernelMismatch { running, installed })
+}
+
+fn installed_releases() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(MODULES_DIR) else {
+        return Vec::new();
+    };
+
+    let mut releases: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.path().join("vmlinuz").exists())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    releases.sort();
+    releases
+}
@@ -0,0 +1,333 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry, filter::filter_fn, fmt, layer::SubscriberExt, reload,
+    util::SubscriberInitExt,
+};
+
+use crate::runtime_dir;
+
+const LOG_FILE_NAME: &str = "arch-updates-rs.log";
+
+const MIB: u64 = 1024 * 1024;
+
+/// The log settings. Logging starts before the config is read, so they
+/// start out as the defaults with the command line flags, and the config is
+/// applied with `apply`.
+static SETTINGS: Mutex<LogConfig> = Mutex::new(LogConfig::DEFAULT);
+
+/// The `--log-level` and `--log-file` flags, which win over the config.
+static OVERRIDES: OnceLock<Overrides> = OnceLock::new();
+
+/// Swaps the filter when the config changes.
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// The open log file, or `None` if logging to a file is disabled.
+static LOG_FILE: Mutex<Option<RotatingFile>> = Mutex::new(None);
+
+/// Whether journald is running.
+static JOURNALD_AVAILABLE: OnceLock<bool> = OnceLock::new();
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// What is logged, in the syntax of `RUST_LOG`: a level like `debug`,
+    /// optionally with levels per module like `info,arch_updates_rs::tray=debug`.
+    pub level: String,
+    /// Where the log is written, `arch-updates-rs.log` in the runtime
+    /// directory by default. An empty path disables the log file.
+    pub file: Option<PathBuf>,
+    /// The log is rotated once it would grow past this size. `0` lets it
+    /// grow without limit.
+    pub max_size_mib: u64,
+    /// How many files are kept, the current one included: `<name>`,
+    /// `<name>.1`, `<name>.2` and so on.
+    pub max_files: u32,
+    /// Log to the systemd journal instead of the log file.
+    pub journald: bool,
+}
+
+impl LogConfig {
+    const DEFAULT: Self = Self {
+        level: String::new(),
+        file: None,
+        max_size_mib: 1,
+        max_files: 3,
+        journald: false,
+    };
+
+    fn with_overrides(&self) -> Self {
+        let mut config = self.clone();
+        if let Some(overrides) = OVERRIDES.get() {
+            if let Some(level) = &overrides.level {
+                config.level = level.clone();
+            }
+            if let Some(file) = &overrides.file {
+                config.file = Some(file.clone());
+            }
+        }
+        config
+    }
+
+    /// The flag wins over `RUST_LOG`, which wins over the config.
+    fn filter(&self) -> Result<EnvFilter> {
+        let from_flag = OVERRIDES
+            .get()
+            .is_some_and(|overrides| overrides.level.is_some());
+        let directives = match std::env::var(EnvFilter::DEFAULT_ENV) {
+            Ok(directives) if !from_flag => directives,
+            _ if self.level.is_empty() => DEFAULT_LEVEL.to_string(),
+            _ => self.level.clone(),
+        };
+        EnvFilter::try_new(&directives)
+            .with_context(|| format!("Invalid log level {:?}", directives))
+    }
+
+    /// Returns the path of the log file, or `None` if it is disabled.
+    fn file_path(&self) -> Result<Option<PathBuf>> {
+        match &self.file {
+            Some(file) if file.as_os_str().is_empty() => Ok(None),
+            Some(file) => Ok(Some(file.clone())),
+            None => {
+                let runtime_dir = runtime_dir()
+                    .context("Failed to get runtime directory when setting up logging")?;
+                Ok(Some(runtime_dir.join(LOG_FILE_NAME)))
+            }
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: DEFAULT_LEVEL.to_string(),
+            ..Self::DEFAULT
+        }
+    }
+}
+
+const DEFAULT_LEVEL: &str = "info";
+
+/// Log settings given on the command line.
+#[derive(Debug, Default)]
+pub struct Overrides {
+    pub level: Option<String>,
+    /// An empty path disables the log file.
+    pub file: Option<PathBuf>,
+}
+
+/// Applies the log settings from the config, unless they were given on the
+/// command line.
+pub fn apply(config: &LogConfig) {
+    let settings = config.with_overrides();
+    let switched_to_journal = settings.journald && !SETTINGS.lock().unwrap().journald;
+    *SETTINGS.lock().unwrap() = settings.clone();
+
+    match settings.filter() {
+        Ok(filter) => {
+            if let Some(handle) = FILTER.get()
+                && let Err(e) = handle.reload(filter)
+            {
+                error!("Failed to change the log level: {}", e);
+            }
+        }
+        Err(e) => error!("{:#}", e),
+    }
+
+    if let Err(e) = open_log_file(&settings) {
+        error!("{:#}", e);
+    }
+
+    if switched_to_journal {
+        if journald_available() {
+            tracing::info!("Logging to the systemd journal");
+        } else {
+            tracing::warn!("The systemd journal is not available, logging to the log file instead");
+        }
+    }
+}
+
+fn journald_available() -> bool {
+    JOURNALD_AVAILABLE.get().copied().unwrap_or(false)
+}
+
+/// Whether records go to the journal rather than the log file.
+fn logging_to_journal() -> bool {
+    SETTINGS.lock().unwrap().journald && journald_available()
+}
+
+/// Logs to the terminal and to `arch-updates-rs.log` in the runtime
+/// directory, or to the systemd journal. Journal records carry the fields
+/// of the event and its spans, e.g. `EVENT=check` and `UPDATES=12`.
+pub fn setup(overrides: Overrides) {
+    OVERRIDES.get_or_init(|| overrides);
+    let settings = LogConfig::default().with_overrides();
+    *SETTINGS.lock().unwrap() = settings.clone();
+
+    if let Err(e) = open_log_file(&settings) {
+        eprintln!("{:#}", e);
+    }
+
+    let filter = settings.filter().unwrap_or_else(|e| {
+        eprintln!("{:#}", e);
+        EnvFilter::new(DEFAULT_LEVEL)
+    });
+    let (filter, filter_handle) = reload::Layer::new(filter);
+    FILTER.get_or_init(|| filter_handle);
+
+    let journald = tracing_journald::layer().ok().map(|layer| {
+        layer
+            .with_field_prefix(None)
+            .with_syslog_identifier(env!("CARGO_PKG_NAME").to_string())
+            .with_filter(filter_fn(|_| logging_to_journal()))
+    });
+    JOURNALD_AVAILABLE.get_or_init(|| journald.is_some());
+
+    if let Err(e) = tracing_subscriber::registry()
+        .with(filter)
+        // Keeps stdout for output, like the `--json-stream` events.
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .with(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(|| LogFile)
+                .with_filter(filter_fn(|_| !logging_to_journal())),
+        )
+        .with(journald)
+        .try_init()
+    {
+        eprintln!("Failed to initialize logging: {}", e);
+    }
+}
+
+/// Opens the log file the settings ask for, unless it is already open, and
+/// marks the start of the session in it.
+fn open_log_file(settings: &LogConfig) -> Result<()> {
+    let path = settings.file_path()?;
+    let mut log_file = LOG_FILE.lock().unwrap();
+    if log_file.as_ref().map(|file| &file.path) == path.as_ref() {
+        return Ok(());
+    }
+
+    *log_file = None;
+    let Some(path) = path else {
+        return Ok(());
+    };
+    let mut file = RotatingFile::open(path.clone())
+        .with_context(|| format!("Failed to open log file {:?}", path))?;
+
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    writeln!(
+        file,
+        "\n===== arch-updates-rs {} session started (pid {}, unix time {}) =====",
+        env!("CARGO_PKG_VERSION"),
+        std::process::id(),
+        started_at
+    )
+    .context("Failed to write session separator to log file")?;
+
+    *log_file = Some(file);
+    Ok(())
+}
+
+/// Writes to the open log file, if there is one.
+struct LogFile;
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match LOG_FILE.lock().unwrap().as_mut() {
+            Some(file) => file.write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match LOG_FILE.lock().unwrap().as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A log file that is rotated when it reaches `max_size_mib`.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file, size })
+    }
+
+    /// Moves `<name>` to `<name>.1`, `<name>.1` to `<name>.2` and so on,
+    /// dropping the oldest file, and starts a new one.
+    fn rotate(&mut self, max_files: u32) -> io::Result<()> {
+        for index in (1..max_files).rev() {
+            let from = if index == 1 {
+                self.path.clone()
+            } else {
+                numbered(&self.path, index - 1)
+            };
+            match fs::rename(&from, numbered(&self.path, index)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let (max_size_mib, max_files) = {
+            let settings = SETTINGS.lock().unwrap();
+            (settings.max_size_mib, settings.max_files)
+        };
+        let max_size = max_size_mib * MIB;
+        if max_size > 0
+            && self.size > 0
+            && self.size + buf.len() as u64 > max_size
+            && let Err(e) = self.rotate(max_files)
+        {
+            // There is no log to report this to.
+            eprintln!("Failed to rotate {:?}: {}", self.path, e);
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn numbered(path: &Path, index: u32) -> PathBuf {
+    let mut numbered = path.as_os_str().to_owned();
+    numbered.push(format!(".{}", index));
+    PathBuf::from(numbered)
+}
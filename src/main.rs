@@ -1,103 +1,191 @@
+mod activation;
+mod advisories;
+mod appearance;
+mod argos;
+mod aur;
+mod auto_update;
+mod bus;
+mod calendar;
+mod changelog;
+mod checker;
+mod cli;
+mod config;
+mod confirm;
+mod control;
+mod dbus;
+mod developer_tools;
+mod digest;
+mod download;
+mod eww;
+mod filters;
+#[cfg(feature = "gtk4")]
+mod gtk4_thread;
+#[cfg(feature = "gtk")]
+mod gtk_tray;
+mod history;
+mod hooks;
+mod housekeeping;
+#[cfg(feature = "http")]
+mod http;
+mod hyprland;
+mod i18n;
+mod install;
+mod instance;
+mod json_stream;
+mod kernel;
+mod logging;
+mod maintenance;
+mod metrics;
+mod migrations;
+mod mqtt;
+mod news;
+mod notification;
+mod outputs;
+mod pacman_conf;
+mod pacman_log;
+mod privileges;
+mod python_tools;
+mod rebuild;
+mod remote;
+mod schedule;
+mod snap;
+mod sni;
+mod sound;
+mod sources;
+mod ssh;
+mod state;
+mod state_file;
+mod status;
+mod summary;
+mod supervisor;
+mod swaybar;
+mod template;
+mod terminal;
+mod tray;
+#[cfg(any(feature = "gtk", feature = "gtk4"))]
+mod updates_window;
+mod version;
+mod webhook;
+#[cfg(any(feature = "layer-shell", feature = "layer-shell-gtk4"))]
+mod widget;
+
 use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-    path::Path,
+    fs,
+    io::{BufRead, BufReader, Read},
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
     process::{Command, Stdio},
-    sync::mpsc::{Sender, channel},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{RecvTimeoutError, Sender, channel},
+    },
     thread,
     time::{Duration, Instant},
 };
 
 use anyhow::{Context, Result, bail};
-use fs2::FileExt;
-use log::{LevelFilter, error, info};
+use bus::{Bus, StateUpdate};
+use checker::CheckResult;
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use cli::{Cli, Command as CliCommand, ConfigCommand, HookWhen};
+use config::Config;
+use history::History;
+use housekeeping::Housekeeping;
+use i18n::t;
 use notify::{
     self, Event as NotifyEvent, EventKind, Result as NotifyResult, Watcher,
     event::{AccessKind, AccessMode, CreateKind},
 };
-use serde::{Deserialize, Serialize};
+use outputs::Outputs;
+use pacman_log::PacmanLog;
 use signal_hook::{
     consts::{SIGINT, SIGTERM},
     iterator::Signals,
 };
-use simplelog::{
-    ColorChoice, CombinedLogger, Config as LogConfig, TermLogger, TerminalMode, WriteLogger,
-};
-use tray_icon::Icon;
-
-const PACMAN_DIR: &str = "/var/lib/pacman/local";
-
-const CHECKING_ICON_BYTES: &[u8] = include_bytes!("../assets/checking.png");
-const NO_UPDATES_ICON_BYTES: &[u8] = include_bytes!("../assets/no-updates.png");
-const UPDATES_ICON_BYTES: &[u8] = include_bytes!("../assets/updates.png");
-const UPDATES_WARNING_LEVEL_ICON_BYTES: &[u8] = include_bytes!("../assets/updates-warn.png");
-const UPDATES_CRITICAL_LEVEL_ICON_BYTES: &[u8] = include_bytes!("../assets/updates-critical.png");
-const UPDATING_ICON_BYTES: &[u8] = include_bytes!("../assets/updating.png");
-
+use sources::Sources;
+use state::{AppState, Transition};
+use status::{Severity, Status};
+use supervisor::ThreadFailure;
+use tracing::{error, info, info_span, warn};
+use tray::setup_tray_icon;
+
+// GTK 4 refuses to start in a process that has GTK 3 loaded.
+#[cfg(all(feature = "gtk", feature = "gtk4"))]
+compile_error!(
+    "The gtk and gtk4 features exclude each other, build with `--no-default-features --features gtk4`"
+);
+
+/// Upper bound on how long to wait for a pacman transaction to finish before
+/// checking anyway.
+const TRANSACTION_WAIT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+const TRANSACTION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often the watcher checks that the pacman database directory it
+/// watches is still the one on disk.
+const WATCH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Exit code used when another instance already holds the lock, so supervisors
+/// can tell it apart from a real failure.
+const ALREADY_RUNNING_EXIT_CODE: i32 = 2;
+
+/// Exit codes of `--oneshot` with pending updates, by severity. No updates
+/// exit with 0 and errors with 1, like other commands.
+const UPDATES_EXIT_CODE: i32 = 10;
+const WARNING_EXIT_CODE: i32 = 11;
+const CRITICAL_EXIT_CODE: i32 = 12;
+
+/// What the main loop acts on: commands from the frontends, like
+/// `InstallUpdates`, and reports from the workers. Changes of the state go
+/// the other way, as `StateUpdate`s on the bus.
 enum Event {
-    Updates(Vec<String>),
     Checking,
+    /// A check started with `Checking` is done. Holds what it found, or the
+    /// error if checkupdates failed.
+    Checked(Result<Box<CheckResult>, String>),
     Updating,
+    /// Reported by the pre-transaction pacman hook.
+    TransactionStarted,
+    /// Reported by the post-transaction pacman hook.
+    TransactionFinished,
+    /// The config file was written.
+    ConfigChanged,
+    /// The user chose "Snooze" on an update notification.
+    SnoozeNotifications,
+    /// The user paused checks, from the menu or with `arch-updates-rs pause`.
+    Pause,
+    Resume,
+    /// The user chose "Acknowledge all" to deal with the backlog later.
+    AcknowledgeAll,
+    /// Time to check whether the weekly housekeeping is due.
+    Housekeeping,
+    /// Time to check whether the weekly digest is due.
+    Digest,
+    /// The background download of the pending updates is done. Holds the
+    /// packages that were downloaded, `None` if it failed.
+    DownloadFinished(Option<Vec<String>>),
+    /// checkrebuild finished. Holds the packages that need a rebuild, `None`
+    /// if it failed.
+    RebuildChecked(Option<Vec<String>>),
+    /// The user chose "Mark news read".
+    MarkNewsRead,
+    /// The user chose "Install updates".
+    InstallUpdates,
+    /// What the running upgrade is doing, e.g. the last line pacman printed.
+    Progress(String),
+    /// The upgrade started with "Install updates" ended, with the error if it
+    /// failed.
+    InstallFinished(Result<(), String>),
+    /// A worker thread stopped and is about to be restarted.
+    ThreadFailed(ThreadFailure),
     Shutdown,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
-struct Config {
-    inverval_in_seconds: u32,
-    warning_threshold: u32,
-    critical_threshold: u32,
-}
-
-impl Config {
-    fn create_default_config(config_path: &Path) -> Self {
-        let config = Self::default();
-        let config_contents = toml::to_string(&config).unwrap();
-        match std::fs::write(config_path, config_contents) {
-            Ok(_) => {
-                info!("Created default config file at {:?}", config_path);
-            }
-            Err(e) => {
-                error!(
-                    "Failed to create default config file at {:?}: {}",
-                    config_path, e
-                );
-            }
-        }
-        config
-    }
-    fn load() -> Result<Self> {
-        let config_path = match dirs::config_dir() {
-            Some(dir) => dir.join("hypr").join("arch-updates-rs.toml"),
-            None => {
-                bail!("Failed to get config directory");
-            }
-        };
-
-        if !config_path.exists() {
-            let config = Self::create_default_config(&config_path);
-            return Ok(config);
-        }
-
-        let config_contents = match std::fs::read_to_string(config_path) {
-            Ok(contents) => contents,
-            Err(_) => {
-                bail!("Failed to read config file");
-            }
-        };
-        let config: Self = toml::from_str(&config_contents)?;
-
-        Ok(config)
-    }
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            inverval_in_seconds: 1200,
-            warning_threshold: 25,
-            critical_threshold: 100,
-        }
-    }
+/// The device and inode of `dir`, which change when it is replaced.
+fn directory_identity(dir: &Path) -> Result<(u64, u64)> {
+    let metadata = fs::metadata(dir).with_context(|| format!("Failed to read {:?}", dir))?;
+    Ok((metadata.dev(), metadata.ino()))
 }
 
 struct Debouncer {
@@ -122,31 +210,102 @@ impl Debouncer {
 }
 
 fn main() -> Result<()> {
-    setup_logging();
-    verify_checkupdates_is_installed()?;
+    let cli = Cli::parse();
 
-    let runtime_dir = match std::env::var("XDG_RUNTIME_DIR") {
-        Ok(dir) => dir,
-        Err(_) => {
-            bail!("Failed to get XDG_RUNTIME_DIR");
+    match cli.command {
+        Some(CliCommand::PacmanHook { when }) => {
+            control::notify_transaction(matches!(when, HookWhen::Pre));
+            return Ok(());
         }
-    };
-    let lock_path = format!("{}/arch-updates-rs.lock", runtime_dir);
-    let lock_file = match File::create(&lock_path) {
-        Ok(file) => file,
-        Err(_) => {
-            bail!("Failed to create lock file");
+        Some(CliCommand::Status { argos: true, .. }) => {
+            return argos::print();
+        }
+        Some(CliCommand::Status { text, .. }) => {
+            println!("{}", control::request_status(text)?);
+            return Ok(());
         }
+        Some(CliCommand::Stats { internal }) => {
+            println!("{}", control::request_stats(internal)?);
+            return Ok(());
+        }
+        Some(CliCommand::Check) => {
+            control::request_check()?;
+            return Ok(());
+        }
+        Some(CliCommand::Pause) => {
+            control::request_pause(true)?;
+            return Ok(());
+        }
+        Some(CliCommand::Resume) => {
+            control::request_pause(false)?;
+            return Ok(());
+        }
+        Some(CliCommand::Swaybar) => {
+            return swaybar::run();
+        }
+        Some(CliCommand::Template { format }) => {
+            return template::follow(format);
+        }
+        Some(CliCommand::Config { command }) => {
+            match command {
+                ConfigCommand::Validate => config::validate()?,
+                ConfigCommand::Dump => print!("{}", config::dump()?),
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
+    if cli.oneshot {
+        let code = check_once()?;
+        std::process::exit(code);
+    }
+
+    logging::setup(logging::Overrides {
+        level: cli.log_level,
+        file: if cli.no_log_file {
+            Some(PathBuf::new())
+        } else {
+            cli.log_file
+        },
+    });
+    activation::receive_sockets();
+    supervisor::install_panic_hook();
+    metrics::start();
+
+    let runtime_dir = runtime_dir()?;
+    if std::env::var_os("XDG_RUNTIME_DIR").is_none() {
+        warn!(
+            "XDG_RUNTIME_DIR is not set. Using {:?} for runtime files",
+            runtime_dir
+        );
+    }
+
+    verify_checkupdates_is_installed()?;
+
+    let lock = if cli.replace {
+        Some(instance::replace(&runtime_dir)?)
+    } else {
+        instance::lock(&runtime_dir)?
     };
+    let Some(_lock_file) = lock else {
+        // Starting the program again checks for updates, where D-Bus works.
+        if dbus::raise().is_ok() {
+            info!("Another instance is running. Asked it to check for updates");
+            return Ok(());
+        }
 
-    if lock_file.try_lock_exclusive().is_err() {
         error!("Failed to acquire lock. Another instance is running.");
-        bail!("Exiting");
-    }
+        if let Err(e) = notification::send(&t!("already-running"), &t!("already-running-body")) {
+            error!("{}", e);
+        }
+        std::process::exit(ALREADY_RUNNING_EXIT_CODE);
+    };
 
     info!("Lock acquired");
 
-    let config = Config::load()?;
+    let mut config = Config::load()?;
+    logging::apply(&config.log);
 
     let (tx, rx) = channel::<Event>();
 
@@ -154,113 +313,757 @@ fn main() -> Result<()> {
 
     let signal_tx = tx.clone();
     thread::spawn(move || {
+        let _span = info_span!("signals").entered();
         for signal in signals.forever() {
             info!("Received signal {:?}", signal);
-            signal_tx.send(Event::Shutdown).unwrap();
+            if signal_tx.send(Event::Shutdown).is_err() {
+                return;
+            }
         }
     });
 
+    // Joined on shutdown.
+    let mut threads = Vec::new();
+
+    let bus = Bus::default();
+
     let tray_icon_config = config.clone();
     let _tx = tx.clone();
-    let tray_icon_tx = setup_tray_icon(tray_icon_config, _tx);
+    threads.extend(setup_tray_icon(tray_icon_config, _tx, bus.subscribe()));
 
-    let timer_config = config.clone();
-    let timer_tx = tx.clone();
-    thread::spawn(move || {
-        loop {
-            info!("Next check in {} seconds", timer_config.inverval_in_seconds);
-            thread::sleep(std::time::Duration::from_secs(
-                timer_config.inverval_in_seconds as u64,
-            ));
-            timer_tx.send(Event::Checking).unwrap();
-        }
-    });
+    if cli.json_stream {
+        threads.push(json_stream::start(bus.subscribe()));
+    }
 
-    let watcher_gtk_tx = tray_icon_tx.clone();
-    thread::spawn(move || {
+    let (scheduler, timer_thread) = schedule::start(config.clone(), tx.clone());
+    threads.extend(timer_thread);
+
+    let pacman_db_path = config.pacman_db_path();
+
+    let watcher_tx = tx.clone();
+    let watch_debounce = Duration::from_millis(config.watch_debounce_ms);
+    let pacman_local_dir = pacman_db_path.join("local");
+    let mut watched_before = false;
+    let watcher_thread = supervisor::supervise_fallible("watcher", tx.clone(), move || {
+        let _span = info_span!("watcher").entered();
         let (tx, rx) = channel::<NotifyResult<NotifyEvent>>();
-        let mut watcher = match notify::recommended_watcher(tx) {
-            Ok(watcher) => watcher,
-            Err(e) => {
-                error!("Failed to create watcher: {}", e);
-                return;
-            }
-        };
+        let mut watcher = notify::recommended_watcher(tx).context("Failed to create watcher")?;
+        watcher
+            .watch(&pacman_local_dir, notify::RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {:?}", pacman_local_dir))?;
+        let watched_dir = directory_identity(&pacman_local_dir)?;
 
-        if let Err(e) = watcher.watch(Path::new(PACMAN_DIR), notify::RecursiveMode::Recursive) {
-            error!("Failed to watch directory: {}", e);
-            return;
-        }
+        info!("Watching for updates in {:?}", pacman_local_dir);
 
-        info!("Watching for updates in {:?}", PACMAN_DIR);
+        // Upgrades while the watch was down went unnoticed.
+        if watched_before && watcher_tx.send(Event::Checking).is_err() {
+            return Ok(());
+        }
+        watched_before = true;
 
-        let mut debouncer = Debouncer::new(Duration::from_millis(1000));
+        let mut debouncer = Debouncer::new(watch_debounce);
 
-        for res in rx {
-            match res {
-                Ok(event) => match event.kind {
+        loop {
+            match supervisor::recv_timeout(&rx, WATCH_CHECK_INTERVAL) {
+                Ok(Ok(event)) => match event.kind {
                     EventKind::Create(CreateKind::File)
                     | EventKind::Create(CreateKind::Folder)
                     | EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
                         info!("event: {:?}", event);
                         if debouncer.debounce() {
-                            watcher_gtk_tx.send(Event::Updating).unwrap();
+                            if watcher_tx.send(Event::Updating).is_err() {
+                                return Ok(());
+                            }
+                        } else {
+                            metrics::increment(&metrics::COUNTERS.watcher_events_coalesced);
                         }
                     }
+                    // The watch ends with the directory.
+                    EventKind::Remove(_) if event.paths.contains(&pacman_local_dir) => {
+                        bail!("{:?} was removed", pacman_local_dir);
+                    }
                     _ => {}
                 },
-                Err(e) => {
-                    error!("watch error: {}", e);
+                Ok(Err(e)) => return Err(e).context("The watch failed"),
+                // A directory that was replaced, e.g. by a bind mount, sends no
+                // events, so this is checked now and then.
+                Err(RecvTimeoutError::Timeout) => {
+                    if directory_identity(&pacman_local_dir)? != watched_dir {
+                        bail!("{:?} was replaced", pacman_local_dir);
+                    }
                 }
-            };
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
         }
     });
 
+    threads.extend(watcher_thread);
+
+    match config::watch(tx.clone()) {
+        Ok(thread) => threads.push(thread),
+        Err(e) => error!("{}", e),
+    }
+
+    let history = match History::open() {
+        Ok(history) => Some(history),
+        Err(e) => {
+            error!("Failed to open history database: {}", e);
+            None
+        }
+    };
+
+    let mut outputs = Outputs::new(&config);
+    let sources = Arc::new(Sources::default());
+    let mut pacman_log = PacmanLog::new();
+    let mut housekeeping = Housekeeping::new(history.as_ref());
+
+    let mut status = Status::default();
+    let mut upgrade_detected = false;
+    let mut transaction_in_progress = false;
+    let recheck_pending = Arc::new(AtomicBool::new(false));
+    let mut notifications_snoozed_until: Option<Instant> = None;
+    let mut acknowledged_updates: Option<Vec<String>> = None;
+    let mut download_running = false;
+    let mut rebuild_running = false;
+    let mut news_read_at = history
+        .as_ref()
+        .and_then(|history| match history.news_read_at() {
+            Ok(timestamp) => timestamp.and_then(|timestamp| DateTime::from_timestamp(timestamp, 0)),
+            Err(e) => {
+                error!("{:#}", e);
+                None
+            }
+        });
+    let mut install_running = false;
+    let mut automatic_install = false;
+    let following_transaction = Arc::new(AtomicBool::new(false));
+    // Updates whose automatic installation failed aren't retried every check.
+    let mut auto_update_failed_for: Option<Vec<String>> = None;
+
+    if let Err(e) = control::listen(tx.clone(), bus.clone(), config.summary.clone()) {
+        error!("{}", e);
+    }
+
+    // Kept until exit, since dropping it gives up the name.
+    let _dbus_connection = match dbus::serve(tx.clone()) {
+        Ok(connection) => Some(connection),
+        Err(e) => {
+            warn!("{:#}", e);
+            None
+        }
+    };
+
+    #[cfg(feature = "http")]
+    if let Err(e) = http::serve(config.http_address.as_deref(), bus.status()) {
+        error!("{}", e);
+    }
+
+    #[cfg(not(feature = "http"))]
+    if let Some(address) = &config.http_address {
+        warn!(
+            "http_address is set to {} but arch-updates-rs was built without the http feature",
+            address
+        );
+    }
+
     tx.send(Event::Checking).unwrap();
+    housekeeping::schedule(tx.clone());
+    digest::schedule(tx.clone());
 
     loop {
-        let event = match rx.recv() {
-            Ok(event) => event,
-            Err(_) => {
-                error!("Failed to receive event");
-                break;
-            }
+        // Wake up for throttled outputs that became due in the meantime.
+        let event = match outputs.next_due() {
+            Some(timeout) => match rx.recv_timeout(timeout) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => {
+                    outputs.flush(&config, &status);
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    error!("Failed to receive event");
+                    break;
+                }
+            },
+            None => match rx.recv() {
+                Ok(event) => event,
+                Err(_) => {
+                    error!("Failed to receive event");
+                    break;
+                }
+            },
         };
 
+        metrics::increment(&metrics::COUNTERS.events_processed);
+
         match event {
             Event::Checking => {
-                tray_icon_tx.send(Event::Checking).unwrap();
+                let _span = info_span!("checker").entered();
+                if transaction_in_progress {
+                    // The post-transaction hook never runs if the transaction
+                    // failed, so don't trust the flag once pacman is done.
+                    if !transaction_finished(&pacman_db_path) {
+                        info!("Skipping check while a pacman transaction is running");
+                        continue;
+                    }
+                    transaction_in_progress = false;
+                }
+
+                scheduler.restart();
+                if !status.transition(Transition::CheckStarted) {
+                    continue;
+                }
+                outputs.publish_state(&config, &status);
+                bus.publish_state(&status);
 
-                let list_of_updates = match check_updates() {
-                    Ok(list_of_updates) => list_of_updates,
+                metrics::increment(&metrics::COUNTERS.checks_run);
+                checker::start(config.clone(), sources.clone(), tx.clone());
+            }
+            Event::Checked(result) => {
+                let _span = info_span!("checker").entered();
+                let check = match result {
+                    Ok(check) => *check,
                     Err(e) => {
                         error!("Failed to check for updates: {}", e);
-                        break;
+                        metrics::increment(&metrics::COUNTERS.check_failures);
+                        // The previous result stays, it is the best there is.
+                        status.transition(if network_online() {
+                            Transition::CheckFailed { error: e }
+                        } else {
+                            Transition::WentOffline
+                        });
+                        outputs.publish_state(&config, &status);
+                        bus.publish_state(&status);
+                        continue;
                     }
                 };
+                let filtered = check.filtered;
+                let list_of_updates = filtered.updates;
 
                 let num_of_updates = list_of_updates.len();
 
-                info!("{} Updates available!", num_of_updates);
+                info!(
+                    event = "check",
+                    updates = num_of_updates,
+                    "{} Updates available!",
+                    num_of_updates
+                );
+
+                let upgraded_packages =
+                    upgrade_detected.then(|| upgraded_packages(&status.updates, &list_of_updates));
+                upgrade_detected = false;
+
+                if let Some(history) = &history {
+                    if let Err(e) = history.record_check(&list_of_updates) {
+                        error!("Failed to record check: {}", e);
+                    }
+
+                    if let Some(upgraded_packages) = &upgraded_packages
+                        && let Err(e) = history.record_upgrade(upgraded_packages)
+                    {
+                        error!("Failed to record upgrade: {}", e);
+                    }
+                }
+
+                if let Err(e) = pacman_log.update() {
+                    error!("Failed to read the pacman log: {:#}", e);
+                }
+                let last_upgrade = pacman_log
+                    .last_full_upgrade()
+                    .map(|last_upgrade| last_upgrade.with_timezone(&Utc));
+
+                let statistics = history
+                    .as_ref()
+                    .and_then(|history| match history.statistics() {
+                        Ok(statistics) => Some(statistics),
+                        Err(e) => {
+                            error!("Failed to compute statistics: {}", e);
+                            None
+                        }
+                    });
+
+                let previous_status = status;
+                status = Status::new(
+                    list_of_updates,
+                    last_upgrade,
+                    statistics,
+                    check.hosts,
+                    check.sources,
+                    &config,
+                );
+                status.ignored_updates = filtered.ignored;
+                status.recent_updates = filtered.recent;
+                status.oldest_pending = filtered
+                    .oldest_seen
+                    .and_then(|oldest_seen| DateTime::from_timestamp(oldest_seen, 0));
+                status.state = previous_status.state.clone();
+                status.transition(Transition::CheckFinished {
+                    updates: status.updates.len(),
+                });
+                status.config_error = previous_status.config_error.clone();
+                status.thread_failures = previous_status.thread_failures.clone();
+                if !status.updates.is_empty() {
+                    status.partial_upgrade =
+                        partial_upgrade_packages(&pacman_db_path, status.last_upgrade, &pacman_log);
+                }
+                status.advisories = check.advisories;
+                status.download_size = check.download_size;
+                status.kernel_mismatch = check.kernel_mismatch;
+                status.update_severity(&config);
+                status.downloaded =
+                    previous_status.downloaded && previous_status.updates == status.updates;
+
+                status.needs_rebuild = previous_status.needs_rebuild.clone();
+
+                let news_seen_at = status.last_upgrade.max(news_read_at);
+                if config.news.enabled && !status.updates.is_empty() {
+                    status.unread_news = match &check.news {
+                        Some(items) => news::published_after(items, news_seen_at),
+                        None => previous_status.unread_news.clone(),
+                    };
+                }
+
+                // Sonames only change with upgrades, so there is nothing new
+                // to find in between.
+                if config.rebuild.enabled
+                    && (upgraded_packages.is_some() || previous_status.last_checked.is_none())
+                    && !rebuild_running
+                {
+                    rebuild_running = true;
+                    rebuild::start(tx.clone());
+                }
+
+                if config.download_updates
+                    && !status.updates.is_empty()
+                    && !status.downloaded
+                    && !download_running
+                {
+                    download_running = true;
+                    download::start(tx.clone());
+                }
+
+                // An acknowledgement lasts until packages show up that weren't
+                // pending when it was given.
+                if let Some(acknowledged) = &acknowledged_updates {
+                    if added_updates(acknowledged, &status.updates).is_empty() {
+                        status.acknowledged = true;
+                        status.update_severity(&config);
+                    } else {
+                        acknowledged_updates = None;
+                    }
+                }
+
+                // The first check has nothing to compare against, so
+                // everything pending is new.
+                let new_updates = added_updates(&previous_status.updates, &status.updates);
+                if config.notifications.escalate_only_on_new
+                    && new_updates.is_empty()
+                    && previous_status.last_checked.is_some()
+                {
+                    status.severity = status.severity.min(previous_status.severity);
+                }
+
+                let auto_update = config.auto_update.enabled
+                    && !status.updates.is_empty()
+                    && !install_running
+                    && auto_update_failed_for.as_ref() != Some(&status.updates)
+                    && match auto_update::blocker(&status.ignored_updates, news_seen_at) {
+                        Some(reason) => {
+                            info!("Not installing updates automatically: {}", reason);
+                            false
+                        }
+                        None => true,
+                    };
+                if auto_update {
+                    auto_update::log_transaction(&format!(
+                        "Installing {} updates: {}",
+                        status.updates.len(),
+                        status.updates.join(", ")
+                    ));
+                    install_running = true;
+                    automatic_install = true;
+                    install::start(tx.clone());
+                } else if notifications_snoozed_until.is_none_or(|until| Instant::now() >= until) {
+                    notification::notify_updates(
+                        &config,
+                        tx.clone(),
+                        status.severity,
+                        &status.updates,
+                        &new_updates,
+                    );
+                }
+                // The first check only finds what was already pending.
+                let crossed_critical = status.severity == Severity::Critical
+                    && previous_status.severity < Severity::Critical;
+                if previous_status.last_checked.is_some()
+                    && (!new_updates.is_empty() || crossed_critical)
+                {
+                    sound::play(&config.sound);
+                }
+                hooks::run_for_check(&config.hooks, &previous_status, &status);
+                if let Some(upgraded_packages) = upgraded_packages {
+                    hooks::run_for_upgrade(&config.hooks, &status, &upgraded_packages);
+                    bus.publish(StateUpdate::UpgradeDetected(upgraded_packages));
+                }
+
+                outputs.publish(&config, &status);
+
+                bus.publish_status(&status);
+            }
+            Event::Housekeeping => {
+                housekeeping.run_if_due(
+                    &config.housekeeping,
+                    history.as_ref(),
+                    &sources,
+                    &config.sources,
+                );
+            }
+            Event::Digest => {
+                digest::send_if_due(&config.digest, history.as_ref(), &status);
+            }
+            Event::DownloadFinished(downloaded) => {
+                download_running = false;
+                // Updates that showed up since the check aren't in the
+                // cache yet, the next check downloads them.
+                if downloaded.is_some_and(|downloaded| downloaded == status.updates) {
+                    status.downloaded = true;
+
+                    outputs.publish(&config, &status);
+                    bus.publish_status(&status);
+                }
+            }
+            Event::RebuildChecked(packages) => {
+                rebuild_running = false;
+                if let Some(packages) = packages {
+                    status.needs_rebuild = packages;
+
+                    outputs.publish(&config, &status);
+                    bus.publish_status(&status);
+                }
+            }
+            Event::InstallUpdates => {
+                if install_running || transaction_in_progress {
+                    info!("Not installing updates while a pacman transaction is running");
+                    continue;
+                }
+                install_running = true;
+                install::start(tx.clone());
+            }
+            Event::Progress(progress) => {
+                if status.state != AppState::Upgrading {
+                    if !status.transition(Transition::UpgradeStarted) {
+                        continue;
+                    }
+                    outputs.publish_state(&config, &status);
+                    bus.publish_state(&status);
+                }
+                status.progress = Some(progress.clone());
+                bus.publish(StateUpdate::Progress(progress));
+            }
+            Event::InstallFinished(result) => {
+                install_running = false;
+                status.progress = None;
+
+                if automatic_install {
+                    automatic_install = false;
+                    match &result {
+                        Ok(()) => {
+                            auto_update::log_transaction("Finished");
+                            auto_update_failed_for = None;
+                        }
+                        Err(message) => {
+                            auto_update::log_transaction(&format!("Failed: {}", message));
+                            auto_update_failed_for = Some(status.updates.clone());
+                        }
+                    }
+                }
+
+                if let Err(message) = result {
+                    if let Err(e) = notification::send(&t!("install-failed"), &message) {
+                        error!("{}", e);
+                    }
+                    status.transition(Transition::UpgradeEnded {
+                        updates: status.updates.len(),
+                    });
+                    outputs.publish_state(&config, &status);
+                    bus.publish_status(&status);
+                } else if !recheck_pending.load(Ordering::SeqCst) {
+                    // Nothing was written to the database if there was
+                    // nothing to upgrade, so the watcher didn't schedule a
+                    // check.
+                    tx.send(Event::Checking).unwrap();
+                }
+            }
+            Event::AcknowledgeAll => {
+                info!(
+                    event = "acknowledge",
+                    updates = status.updates.len(),
+                    "Acknowledged {} pending updates",
+                    status.updates.len()
+                );
+                acknowledged_updates = Some(status.updates.clone());
+                status.acknowledged = true;
+                status.update_severity(&config);
+
+                outputs.publish(&config, &status);
+                bus.publish_status(&status);
+            }
+            Event::MarkNewsRead => {
+                info!("Marked {} Arch news items read", status.unread_news.len());
+                news_read_at = Some(Utc::now());
+                if let Some(history) = &history
+                    && let Err(e) = history.set_news_read_at(history::unix_timestamp())
+                {
+                    error!("{:#}", e);
+                }
+                status.unread_news.clear();
+
+                outputs.publish(&config, &status);
+                bus.publish_status(&status);
+            }
+            Event::Pause => {
+                if status.transition(Transition::Pause) {
+                    info!("Paused checks");
+                    outputs.publish_state(&config, &status);
+                    bus.publish_state(&status);
+                }
+            }
+            Event::Resume => {
+                if status.transition(Transition::Resume {
+                    updates: status.updates.len(),
+                }) {
+                    info!("Resumed checks");
+                    outputs.publish_state(&config, &status);
+                    bus.publish_state(&status);
+                    tx.send(Event::Checking).unwrap();
+                }
+            }
+            Event::SnoozeNotifications => {
+                notifications_snoozed_until =
+                    Some(Instant::now() + Duration::from_secs(config.notifications.snooze_secs));
+            }
+            Event::ConfigChanged => {
+                match Config::reload() {
+                    Ok(new_config) => {
+                        info!("Reloaded config");
+                        let restart_required = config.restart_required(&new_config);
+                        if !restart_required.is_empty() {
+                            warn!(
+                                "Changes to {} only apply after a restart",
+                                restart_required.join(", ")
+                            );
+                        }
+                        logging::apply(&new_config.log);
+                        scheduler.reconfigure(&new_config);
+                        outputs.reconfigure(&config, &new_config);
+                        bus.publish(StateUpdate::Config(Box::new(new_config.clone())));
+                        config = new_config;
+                        status.config_error = None;
+                    }
+                    Err(e) => {
+                        // Editors may write the file several times per save,
+                        // so only new errors are reported.
+                        let message = format!("{:#}", e);
+                        if status.config_error.as_ref() == Some(&message) {
+                            continue;
+                        }
+
+                        error!("Keeping the previous config: {}", message);
+                        if let Err(e) = notification::send(
+                            &t!("config-error-summary"),
+                            &format!("{}\n{}", t!("config-error-body"), message),
+                        ) {
+                            error!("{}", e);
+                        }
+                        status.config_error = Some(message);
+                    }
+                }
 
-                tray_icon_tx.send(Event::Updates(list_of_updates)).unwrap();
+                bus.publish_status(&status);
             }
-            Event::Updates(_) => {}
             Event::Updating => {
-                thread::sleep(Duration::from_secs(5));
-                tx.send(Event::Checking).unwrap();
+                upgrade_detected = true;
+                if status.transition(Transition::UpgradeStarted) {
+                    outputs.publish_state(&config, &status);
+                    bus.publish_state(&status);
+                }
+
+                if !install_running && !following_transaction.swap(true, Ordering::SeqCst) {
+                    pacman_log::follow_transaction(
+                        status.updates.len(),
+                        tx.clone(),
+                        following_transaction.clone(),
+                    );
+                }
+
+                // The watcher reports every write of a long transaction, so only
+                // one recheck is scheduled until it has run. With the pacman
+                // hooks installed the post-transaction hook schedules it instead.
+                if !transaction_in_progress && !recheck_pending.swap(true, Ordering::SeqCst) {
+                    schedule_post_update_recheck(
+                        &config,
+                        &pacman_db_path,
+                        tx.clone(),
+                        recheck_pending.clone(),
+                    );
+                }
+            }
+            Event::TransactionStarted => {
+                info!(event = "transaction_started", "Pacman transaction started");
+                transaction_in_progress = true;
+                tx.send(Event::Updating).unwrap();
+            }
+            Event::TransactionFinished => {
+                info!(
+                    event = "transaction_finished",
+                    "Pacman transaction finished"
+                );
+                transaction_in_progress = false;
+                if !recheck_pending.swap(true, Ordering::SeqCst) {
+                    schedule_post_update_recheck(
+                        &config,
+                        &pacman_db_path,
+                        tx.clone(),
+                        recheck_pending.clone(),
+                    );
+                }
+            }
+            Event::ThreadFailed(failure) => {
+                status
+                    .thread_failures
+                    .retain(|known| known.thread != failure.thread);
+                status.thread_failures.push(failure);
+
+                bus.publish_status(&status);
             }
             Event::Shutdown => {
+                info!("Shutting down");
                 break;
             }
         }
     }
 
+    supervisor::shutdown();
+    bus.publish(StateUpdate::Shutdown);
+    supervisor::join(threads);
+    state_file::remove();
+
     Ok(())
 }
 
-fn check_updates() -> Result<Vec<String>> {
-    let mut child = match Command::new("checkupdates").stdout(Stdio::piped()).spawn() {
+/// Checks again once the pacman transaction that triggered the watcher has
+/// settled and finished, and once more a while later to catch multi-step
+/// upgrade sessions.
+fn schedule_post_update_recheck(
+    config: &Config,
+    pacman_db_path: &Path,
+    tx: Sender<Event>,
+    recheck_pending: Arc<AtomicBool>,
+) {
+    let settle_delay = Duration::from_secs(config.post_update_settle_secs);
+    let recheck_delay = Duration::from_secs(config.post_update_recheck_secs);
+    let pacman_db_path = pacman_db_path.to_path_buf();
+
+    thread::spawn(move || {
+        let _span = info_span!("recheck").entered();
+        if supervisor::sleep(settle_delay) {
+            return;
+        }
+
+        let started_waiting_at = Instant::now();
+        while !transaction_finished(&pacman_db_path) {
+            if started_waiting_at.elapsed() >= TRANSACTION_WAIT_TIMEOUT {
+                warn!("Timed out waiting for the pacman transaction to finish");
+                break;
+            }
+            thread::sleep(TRANSACTION_POLL_INTERVAL);
+        }
+
+        recheck_pending.store(false, Ordering::SeqCst);
+        if tx.send(Event::Checking).is_err() {
+            return;
+        }
+
+        if recheck_delay.is_zero() {
+            return;
+        }
+
+        if supervisor::sleep(recheck_delay) {
+            return;
+        }
+        info!("Re-checking after upgrade");
+        let _ = tx.send(Event::Checking);
+    });
+}
+
+/// Packages installed since the sync databases were refreshed, if that
+/// happened after the last full upgrade: the classic partial upgrade.
+fn partial_upgrade_packages(
+    pacman_db_path: &Path,
+    last_upgrade: Option<DateTime<Utc>>,
+    pacman_log: &PacmanLog,
+) -> Vec<String> {
+    let Some(synced_at) = pacman_conf::last_sync(pacman_db_path).map(DateTime::<Utc>::from) else {
+        return Vec::new();
+    };
+
+    if last_upgrade.is_some_and(|last_upgrade| last_upgrade >= synced_at) {
+        return Vec::new();
+    }
+
+    pacman_log.installed_since(synced_at)
+}
+
+/// A transaction is finished once pacman released its database lock and the
+/// log shows the transaction ended.
+fn transaction_finished(pacman_db_path: &Path) -> bool {
+    if pacman_db_path.join("db.lck").exists() {
+        return false;
+    }
+
+    match pacman_log::last_transaction_finished() {
+        Ok(finished) => finished,
+        Err(e) => {
+            error!("Failed to read pacman log: {}", e);
+            true
+        }
+    }
+}
+
+/// Checks for updates once for `--oneshot`, prints them and returns the exit
+/// code. Doesn't need the running instance or its lock.
+fn check_once() -> Result<i32> {
+    let config = Config::load()?;
+    let mut filtered = filters::apply(check_updates(false)?, &config);
+    if let Ok(history) = History::open() {
+        filters::hold_back_recent(&mut filtered, &history, &config);
+    }
+    let updates = filtered.updates;
+    for update in &updates {
+        println!("{}", update);
+    }
+
+    let code = match status::severity(status::PACMAN_SOURCE, updates.len() as u32, None, &config) {
+        Severity::None => 0,
+        Severity::Normal => UPDATES_EXIT_CODE,
+        Severity::Warning => WARNING_EXIT_CODE,
+        Severity::Critical => CRITICAL_EXIT_CODE,
+    };
+    Ok(code)
+}
+
+/// Lists the pending updates. With `download` they are also downloaded into
+/// the pacman cache.
+fn check_updates(download: bool) -> Result<Vec<String>> {
+    let mut command = Command::new("checkupdates");
+    if download {
+        command.arg("-d");
+    }
+
+    let mut child = match command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
         Ok(child) => child,
         Err(e) => bail!("Failed to check for updates: {}", e),
     };
@@ -282,11 +1085,73 @@ fn check_updates() -> Result<Vec<String>> {
         }
     }
 
-    child.wait()?;
+    let mut errors = String::new();
+    if let Some(mut stderr) = child.stderr.take() {
+        stderr.read_to_string(&mut errors)?;
+    }
+
+    // 2 means there are no updates.
+    let exit_status = child.wait()?;
+    if !exit_status.success() && exit_status.code() != Some(2) {
+        bail!(
+            "checkupdates failed with {}: {}",
+            exit_status,
+            errors.trim()
+        );
+    }
 
     Ok(updates)
 }
 
+/// Whether a network interface other than loopback is up. A check that
+/// fails without one is put down to being offline.
+fn network_online() -> bool {
+    let Ok(interfaces) = fs::read_dir("/sys/class/net") else {
+        return true;
+    };
+
+    interfaces.flatten().any(|interface| {
+        interface.file_name() != "lo"
+            && fs::read_to_string(interface.path().join("operstate"))
+                .is_ok_and(|operstate| operstate.trim() == "up")
+    })
+}
+
+/// Returns the entries of `before` whose package no longer has a pending update.
+fn upgraded_packages(before: &[String], after: &[String]) -> Vec<String> {
+    let still_pending: Vec<&str> = after
+        .iter()
+        .filter_map(|update| update.split_whitespace().next())
+        .collect();
+
+    before
+        .iter()
+        .filter(|update| match update.split_whitespace().next() {
+            Some(name) => !still_pending.contains(&name),
+            None => false,
+        })
+        .cloned()
+        .collect()
+}
+
+/// Returns the entries of `after` whose package had no pending update in
+/// `before`. A newer version of a package that was already pending isn't new.
+fn added_updates(before: &[String], after: &[String]) -> Vec<String> {
+    let already_pending: Vec<&str> = before
+        .iter()
+        .filter_map(|update| update.split_whitespace().next())
+        .collect();
+
+    after
+        .iter()
+        .filter(|update| match update.split_whitespace().next() {
+            Some(name) => !already_pending.contains(&name),
+            None => false,
+        })
+        .cloned()
+        .collect()
+}
+
 fn verify_checkupdates_is_installed() -> Result<()> {
     match Command::new("which").arg("checkupdates").output() {
         Ok(output) => {
@@ -300,201 +1165,39 @@ fn verify_checkupdates_is_installed() -> Result<()> {
     }
 }
 
-fn setup_logging() {
-    let runtime_dir = match std::env::var("XDG_RUNTIME_DIR") {
-        Ok(dir) => dir,
-        Err(_) => {
-            println!("Failed to get XDG_RUNTIME_DIR when setting up logging");
-            return;
-        }
-    };
-
-    let log_path = format!("{}/arch-updates-rs.log", runtime_dir);
-    let log_file = match File::create(log_path) {
-        Ok(file) => file,
-        Err(_) => {
-            println!("Failed to create log file when setting up logging");
-            return;
-        }
-    };
-
-    if let Err(e) = CombinedLogger::init(vec![
-        TermLogger::new(
-            LevelFilter::Info,
-            LogConfig::default(),
-            TerminalMode::Mixed,
-            ColorChoice::Auto,
-        ),
-        WriteLogger::new(LevelFilter::Info, LogConfig::default(), log_file),
-    ]) {
-        println!("Failed to initialize logging: {}", e);
-    };
+fn hostname() -> String {
+    std::fs::read_to_string("/etc/hostname")
+        .map(|hostname| hostname.trim().to_string())
+        .unwrap_or_default()
 }
 
-fn convert_bytes_to_icon(bytes: &[u8]) -> Result<Icon> {
-    let image_buff = match image::load_from_memory(bytes) {
-        Ok(image_dyn) => image_dyn.into_rgba8(),
-        Err(e) => return Err(e).context("Failed to load icon"),
-    };
+/// Returns the directory for the lock, log and diagnostics files.
+///
+/// Falls back to a private per-user directory when `XDG_RUNTIME_DIR` is not
+/// set, which is common in containers and some session setups.
+fn runtime_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
 
-    let (width, height) = image_buff.dimensions();
-    let icon_rgba = image_buff.into_raw();
+    let uid = std::fs::metadata("/proc/self")
+        .context("Failed to get current user id")?
+        .uid();
 
-    let icon = match Icon::from_rgba(icon_rgba, width, height) {
-        Ok(icon) => icon,
-        Err(e) => return Err(e).context("Failed to create icon"),
+    let dir = match dirs::cache_dir() {
+        Some(dir) => dir.join("arch-updates-rs"),
+        None => std::env::temp_dir().join(format!("arch-updates-rs-{}", uid)),
     };
 
-    Ok(icon)
-}
-
-fn setup_tray_icon(config: Config, app_tx: Sender<Event>) -> Sender<Event> {
-    let (tx, rx) = channel::<Event>();
-
-    std::thread::spawn(move || {
-        use tray_icon::{
-            TrayIconBuilder,
-            menu::{Menu, MenuItem, Submenu},
-        };
-
-        gtk::init().unwrap();
-
-        let icon = match convert_bytes_to_icon(NO_UPDATES_ICON_BYTES) {
-            Ok(icon) => icon,
-            Err(e) => {
-                error!("Failed to convert bytes to icon: {}", e);
-                return;
-            }
-        };
-
-        let menu = Menu::new();
-
-        let list_of_updates_submenu = Submenu::new("0 pending updates", true);
+    std::fs::create_dir_all(&dir).context("Failed to create runtime directory")?;
 
-        if let Err(e) = menu.append_items(&[&list_of_updates_submenu]) {
-            error!("Failed to append menu item: {}", e);
-            return;
-        }
-
-        let tray_icon = match TrayIconBuilder::new().with_menu(Box::new(menu)).build() {
-            Ok(tray_icon) => tray_icon,
-            Err(e) => {
-                error!("Failed to build tray icon: {}", e);
-                return;
-            }
-        };
-
-        if let Err(e) = tray_icon.set_icon(Some(icon)) {
-            error!("Failed to set icon: {}", e);
-            return;
-        };
-
-        glib::timeout_add_local(Duration::from_millis(100), move || {
-            while let Ok(event) = rx.try_recv() {
-                match event {
-                    Event::Checking => {
-                        let checking_icon = match convert_bytes_to_icon(CHECKING_ICON_BYTES) {
-                            Ok(icon) => icon,
-                            Err(e) => {
-                                error!("Failed to convert bytes to icon: {}", e);
-                                return glib::ControlFlow::Break;
-                            }
-                        };
-                        if let Err(e) = tray_icon.set_icon(Some(checking_icon)) {
-                            error!("Failed to set icon: {}", e);
-                            return glib::ControlFlow::Break;
-                        };
-                    }
-                    Event::Updates(list_of_updates) => {
-                        let updates_icon;
-                        let num_of_updates = list_of_updates.len() as u32;
-                        if num_of_updates == 0 {
-                            updates_icon = match convert_bytes_to_icon(NO_UPDATES_ICON_BYTES) {
-                                Ok(icon) => icon,
-                                Err(e) => {
-                                    error!("Failed to convert bytes to icon: {}", e);
-                                    return glib::ControlFlow::Break;
-                                }
-                            };
-                        } else if num_of_updates < config.warning_threshold {
-                            updates_icon = match convert_bytes_to_icon(UPDATES_ICON_BYTES) {
-                                Ok(icon) => icon,
-                                Err(e) => {
-                                    error!("Failed to convert bytes to icon: {}", e);
-                                    return glib::ControlFlow::Break;
-                                }
-                            };
-                        } else if num_of_updates < config.critical_threshold {
-                            updates_icon =
-                                match convert_bytes_to_icon(UPDATES_WARNING_LEVEL_ICON_BYTES) {
-                                    Ok(icon) => icon,
-                                    Err(e) => {
-                                        error!("Failed to convert bytes to icon: {}", e);
-                                        return glib::ControlFlow::Break;
-                                    }
-                                };
-                        } else {
-                            updates_icon =
-                                match convert_bytes_to_icon(UPDATES_CRITICAL_LEVEL_ICON_BYTES) {
-                                    Ok(icon) => icon,
-                                    Err(e) => {
-                                        error!("Failed to convert bytes to icon: {}", e);
-                                        return glib::ControlFlow::Break;
-                                    }
-                                };
-                        }
-
-                        if let Err(e) = tray_icon.set_icon(Some(updates_icon)) {
-                            error!("Failed to set icon: {}", e);
-                            return glib::ControlFlow::Break;
-                        };
-
-                        for item in list_of_updates_submenu.items() {
-                            if let Some(_item) = item.as_menuitem() {
-                                if let Err(e) = list_of_updates_submenu.remove(_item) {
-                                    error!("Failed to remove menu item: {}", e);
-                                    return glib::ControlFlow::Break;
-                                };
-                            }
-                        }
-
-                        list_of_updates_submenu
-                            .set_text(format!("{} pending updates", num_of_updates));
-
-                        for update in list_of_updates.iter() {
-                            let update_item = MenuItem::new(update, true, None);
-                            if let Err(e) = list_of_updates_submenu.append_items(&[&update_item]) {
-                                error!("Failed to append menu items: {}", e);
-                                return glib::ControlFlow::Break;
-                            }
-                        }
-
-                        info!("Updated tray icon");
-                    }
-                    Event::Updating => {
-                        let updating_icon = match convert_bytes_to_icon(UPDATING_ICON_BYTES) {
-                            Ok(icon) => icon,
-                            Err(e) => {
-                                error!("Failed to convert bytes to icon: {}", e);
-                                return glib::ControlFlow::Break;
-                            }
-                        };
-                        if let Err(e) = tray_icon.set_icon(Some(updating_icon)) {
-                            error!("Failed to set icon: {}", e);
-                            return glib::ControlFlow::Break;
-                        };
-                        app_tx.send(Event::Updating).unwrap();
-                    }
-                    Event::Shutdown => {
-                        return glib::ControlFlow::Break;
-                    }
-                }
-            }
-            glib::ControlFlow::Continue
-        });
+    let metadata = std::fs::metadata(&dir).context("Failed to read runtime directory")?;
+    if metadata.uid() != uid {
+        bail!("Runtime directory {:?} is owned by another user", dir);
+    }
 
-        gtk::main();
-    });
+    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))
+        .context("Failed to set runtime directory permissions")?;
 
-    tx
+    Ok(dir)
 }
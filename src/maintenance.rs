@@ -0,0 +1,121 @@
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+use crate::i18n::t;
+
+/// System maintenance offered in the tray's "Maintenance" submenu. Each runs
+/// in a terminal, so sudo can ask for the password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    CleanCache,
+    RemoveOrphans,
+}
+
+impl Action {
+    pub const ALL: [Action; 2] = [Action::CleanCache, Action::RemoveOrphans];
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.id() == id)
+    }
+
+    /// Menu id, and key in the `[confirm]` config section.
+    pub fn id(self) -> &'static str {
+        match self {
+            Action::CleanCache => "clean_cache",
+            Action::RemoveOrphans => "remove_orphans",
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            Action::CleanCache => t!("clean-cache"),
+            Action::RemoveOrphans => t!("remove-orphans"),
+        }
+    }
+
+    pub fn command(self) -> &'static str {
+        match self {
+            Action::CleanCache => "sudo paccache -r",
+            Action::RemoveOrphans => "pacman -Qdtq | sudo pacman -Rns -",
+        }
+    }
+
+    /// Describes what running the action would change, without changing it.
+    pub fn preview(self) -> Result<String> {
+        match self {
+            Action::CleanCache => clean_cache_preview(),
+            Action::RemoveOrphans => orphans_preview(),
+        }
+    }
+}
+
+/// The summary line of a paccache dry run, e.g. "finished dry run: 12
+/// candidates (disk space saved: 345.67 MiB)".
+fn clean_cache_preview() -> Result<String> {
+    let output = Command::new("paccache")
+        .arg("-d")
+        .output()
+        .context("Failed to run paccache")?;
+
+    if !output.status.success() {
+        bail!(
+            "paccache exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let summary = stdout
+        .lines()
+        .rev()
+        .map(|line| line.trim().trim_start_matches("==>").trim())
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| t!("nothing-to-clean"));
+
+    Ok(summary)
+}
+
+/// The orphaned packages with their installed size, one per line.
+fn orphans_preview() -> Result<String> {
+    let output = Command::new("pacman")
+        .arg("-Qdtq")
+        .output()
+        .context("Failed to run pacman")?;
+
+    // pacman exits with 1 when no package matches.
+    let orphans: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.to_string())
+        .collect();
+    if orphans.is_empty() {
+        return Ok(t!("no-orphans"));
+    }
+
+    let info = Command::new("pacman")
+        .arg("-Qi")
+        .args(&orphans)
+        .output()
+        .context("Failed to run pacman")?;
+
+    let mut lines = Vec::new();
+    let mut name = None;
+    for line in String::from_utf8_lossy(&info.stdout).lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        match key.trim() {
+            "Name" => name = Some(value.trim().to_string()),
+            "Installed Size" => {
+                if let Some(name) = name.take() {
+                    lines.push(format!("{} ({})", name, value.trim()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(lines.join("\n"))
+}
@@ -0,0 +1,70 @@
+use std::{
+    sync::{
+        OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+use serde::Serialize;
+
+/// Counters about the daemon itself, to check that it is healthy and cheap.
+pub struct Counters {
+    pub events_processed: AtomicU64,
+    pub checks_run: AtomicU64,
+    /// Failed checks of pacman, remote hosts and additional sources.
+    pub check_failures: AtomicU64,
+    /// File system events swallowed by the watcher's debouncer.
+    pub watcher_events_coalesced: AtomicU64,
+}
+
+pub static COUNTERS: Counters = Counters {
+    events_processed: AtomicU64::new(0),
+    checks_run: AtomicU64::new(0),
+    check_failures: AtomicU64::new(0),
+    watcher_events_coalesced: AtomicU64::new(0),
+};
+
+static STARTED_AT: OnceLock<Instant> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Metrics {
+    pub uptime_secs: u64,
+    pub events_processed: u64,
+    pub checks_run: u64,
+    pub check_failures: u64,
+    pub watcher_events_coalesced: u64,
+    pub memory_rss_bytes: Option<u64>,
+}
+
+/// Records the start time for the uptime. Called once at startup.
+pub fn start() {
+    STARTED_AT.get_or_init(Instant::now);
+}
+
+pub fn increment(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn snapshot() -> Metrics {
+    Metrics {
+        uptime_secs: STARTED_AT
+            .get()
+            .map(|started_at| started_at.elapsed().as_secs())
+            .unwrap_or_default(),
+        events_processed: COUNTERS.events_processed.load(Ordering::Relaxed),
+        checks_run: COUNTERS.checks_run.load(Ordering::Relaxed),
+        check_failures: COUNTERS.check_failures.load(Ordering::Relaxed),
+        watcher_events_coalesced: COUNTERS.watcher_events_coalesced.load(Ordering::Relaxed),
+        memory_rss_bytes: memory_rss_bytes(),
+    }
+}
+
+/// Reads the resident set size from the `VmRSS:  12345 kB` line of
+/// `/proc/self/status`.
+fn memory_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
@@ -0,0 +1,278 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use rusqlite::Connection;
+use toml::Table;
+use tracing::info;
+
+use crate::config::ConfigFormat;
+
+/// Upgrades of the config file, in order. The migration at index `i` turns a
+/// version `i + 1` config into a version `i + 2` config. Append a function
+/// here whenever a key is renamed or moved, instead of breaking old files.
+const CONFIG_MIGRATIONS: &[fn(&mut Table)] = &[fix_interval_spelling];
+
+/// The version written to new config files. Files without `config_version`
+/// predate the migrations and are version 1.
+pub const CURRENT_CONFIG_VERSION: i64 = 1 + CONFIG_MIGRATIONS.len() as i64;
+
+/// Schema changes of the history database, in order. The migration at index
+/// `i` turns a version `i` database into a version `i + 1` database. The
+/// version is stored in SQLite's `user_version`.
+const HISTORY_MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS checks (
+        id INTEGER PRIMARY KEY,
+        timestamp INTEGER NOT NULL,
+        count INTEGER NOT NULL,
+        packages TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS upgrades (
+        id INTEGER PRIMARY KEY,
+        timestamp INTEGER NOT NULL,
+        count INTEGER NOT NULL,
+        packages TEXT NOT NULL
+    );",
+    "CREATE TABLE meta (
+        key TEXT PRIMARY KEY,
+        value INTEGER NOT NULL
+    );",
+    "CREATE TABLE first_seen (
+        package TEXT NOT NULL,
+        version TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        PRIMARY KEY (package, version)
+    );",
+];
+
+/// Version 2 fixed the spelling of `interval_in_seconds`.
+fn fix_interval_spelling(table: &mut Table) {
+    if let Some(interval) = table.remove("inverval_in_seconds") {
+        table.entry("interval_in_seconds").or_insert(interval);
+    }
+}
+
+/// Brings an outdated config file up to the current version. The original is
+/// kept next to it as a backup. Returns the migrated contents, or `None` if
+/// the file is already current.
+pub fn migrate_config(config_path: &Path, contents: &str) -> Result<Option<String>> {
+    let format = ConfigFormat::from_path(config_path);
+    let mut table = format
+        .parse_table(contents)
+        .with_context(|| format!("Failed to parse {:?}", config_path))?;
+
+    let Some(version) = migrate_config_table(&mut table)
+        .with_context(|| format!("Failed to migrate {:?}", config_path))?
+    else {
+        return Ok(None);
+    };
+
+    let backup_path = backup_path(config_path, version);
+    std::fs::copy(config_path, &backup_path)
+        .with_context(|| format!("Failed to back up config to {:?}", backup_path))?;
+
+    let migrated = format
+        .to_string(&table)
+        .context("Failed to serialize migrated config")?;
+    std::fs::write(config_path, &migrated).context("Failed to write migrated config")?;
+
+    info!(
+        "Migrated config from version {} to {}. The old file is at {:?}",
+        version, CURRENT_CONFIG_VERSION, backup_path
+    );
+
+    Ok(Some(migrated))
+}
+
+/// Brings a parsed config up to the current version in memory. Returns the
+/// version it had, or `None` if it is already current.
+pub fn migrate_config_table(table: &mut Table) -> Result<Option<i64>> {
+    let version = match table.get("config_version") {
+        Some(version) => version
+            .as_integer()
+            .context("config_version must be an integer")?,
+        None => 1,
+    };
+
+    if version > CURRENT_CONFIG_VERSION {
+        bail!(
+            "Config version {} is newer than this build understands ({})",
+            version,
+            CURRENT_CONFIG_VERSION
+        );
+    }
+
+    if version == CURRENT_CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    for migration in &CONFIG_MIGRATIONS[(version - 1) as usize..] {
+        migration(table);
+    }
+    table.insert("config_version".to_string(), CURRENT_CONFIG_VERSION.into());
+
+    Ok(Some(version))
+}
+
+/// Brings the history database up to the current schema. An existing
+/// database is copied before it is changed.
+pub fn migrate_history(conn: &Connection, db_path: &Path) -> Result<()> {
+    let version: i64 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .context("Failed to read history database version")?;
+    let latest = HISTORY_MIGRATIONS.len() as i64;
+
+    if version > latest {
+        bail!(
+            "History database is version {}, but this build only understands up to {}",
+            version,
+            latest
+        );
+    }
+
+    if version == latest {
+        return Ok(());
+    }
+
+    // Databases from before the migrations are version 0 but hold data.
+    let has_tables: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table')",
+            [],
+            |row| row.get(0),
+        )
+        .context("Failed to inspect history database")?;
+    if has_tables {
+        // VACUUM INTO refuses to overwrite the backup of an earlier attempt.
+        let backup_path = backup_path(db_path, version);
+        if backup_path.exists() {
+            std::fs::remove_file(&backup_path)
+                .with_context(|| format!("Failed to remove old backup {:?}", backup_path))?;
+        }
+        conn.execute(
+            "VACUUM INTO ?1",
+            [backup_path.to_string_lossy().into_owned()],
+        )
+        .with_context(|| format!("Failed to back up history database to {:?}", backup_path))?;
+    }
+
+    for (index, migration) in HISTORY_MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let to_version = index + 1;
+        conn.execute_batch(&format!(
+            "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+            migration, to_version
+        ))
+        .with_context(|| {
+            format!(
+                "Failed to migrate history database to version {}",
+                to_version
+            )
+        })?;
+    }
+
+    info!(
+        "Migrated history database from version {} to {}",
+        version, latest
+    );
+
+    Ok(())
+}
+
+/// `<file>.v<version>.bak` next to the file.
+fn backup_path(path: &Path, version: i64) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".v{}.bak", version));
+    PathBuf::from(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_version(conn: &Connection) -> i64 {
+        conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn migrates_empty_history_to_latest() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(user_version(&conn), 0);
+
+        migrate_history(&conn, Path::new(":memory:")).unwrap();
+
+        assert_eq!(user_version(&conn), HISTORY_MIGRATIONS.len() as i64);
+        for table in ["checks", "upgrades", "meta", "first_seen"] {
+            let exists: bool = conn
+                .query_row(
+                    "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = ?1)",
+                    [table],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert!(exists, "{} is missing", table);
+        }
+    }
+
+    #[test]
+    fn current_history_is_left_alone() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate_history(&conn, Path::new(":memory:")).unwrap();
+        conn.execute("INSERT INTO meta (key, value) VALUES ('kept', 1)", [])
+            .unwrap();
+
+        migrate_history(&conn, Path::new(":memory:")).unwrap();
+
+        let kept: i64 = conn
+            .query_row("SELECT value FROM meta WHERE key = 'kept'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(kept, 1);
+    }
+
+    #[test]
+    fn newer_history_is_rejected() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&format!(
+            "PRAGMA user_version = {};",
+            HISTORY_MIGRATIONS.len() + 1
+        ))
+        .unwrap();
+
+        assert!(migrate_history(&conn, Path::new(":memory:")).is_err());
+    }
+
+    #[test]
+    fn config_tables() {
+        let cases: &[(&str, Option<i64>, &str)] = &[
+            (
+                "inverval_in_seconds = 60",
+                Some(1),
+                "config_version = 2\ninterval_in_seconds = 60\n",
+            ),
+            (
+                "inverval_in_seconds = 60\ninterval_in_seconds = 30",
+                Some(1),
+                "config_version = 2\ninterval_in_seconds = 30\n",
+            ),
+            (
+                "config_version = 2\ninterval_in_seconds = 60",
+                None,
+                "config_version = 2\ninterval_in_seconds = 60\n",
+            ),
+        ];
+        for (input, version, expected) in cases {
+            let mut table: Table = input.parse().unwrap();
+            assert_eq!(
+                migrate_config_table(&mut table).unwrap(),
+                *version,
+                "{}",
+                input
+            );
+            assert_eq!(&toml::to_string(&table).unwrap(), expected, "{}", input);
+        }
+
+        let mut newer: Table = "config_version = 99".parse().unwrap();
+        assert!(migrate_config_table(&mut newer).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use rumqttc::{Client, LastWill, MqttOptions, Packet, QoS};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{error, info};
+
+use crate::{hostname, status::Status};
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+    pub host: Option<String>,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// State is published to `<topic>/<hostname>/state`.
+    pub topic: String,
+    /// Home Assistant's MQTT discovery prefix.
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: None,
+            port: 1883,
+            username: None,
+            password: None,
+            topic: "arch-updates-rs".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+}
+
+/// Publishes the update state to an MQTT broker, announcing it to Home
+/// Assistant as a sensor.
+pub struct Mqtt {
+    client: Client,
+    state_topic: String,
+    /// Set when dropped, e.g. after the config changed, so the connection
+    /// thread ends.
+    stopped: Arc<AtomicBool>,
+}
+
+impl Mqtt {
+    pub fn connect(config: &MqttConfig) -> Option<Self> {
+        let Some(host) = &config.host else {
+            return None;
+        };
+
+        let hostname = hostname();
+        let base_topic = format!("{}/{}", config.topic, hostname);
+        let state_topic = format!("{}/state", base_topic);
+        let availability_topic = format!("{}/availability", base_topic);
+
+        let mut options =
+            MqttOptions::new(format!("arch-updates-rs-{}", hostname), host, config.port);
+        options.set_keep_alive(KEEP_ALIVE);
+        options.set_last_will(LastWill::new(
+            &availability_topic,
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+
+        let unique_id = format!("arch_updates_rs_{}", hostname.replace(['-', '.'], "_"));
+        let discovery_topic = format!("{}/sensor/{}/config", config.discovery_prefix, unique_id);
+        let discovery = json!({
+            "name": "Pending updates",
+            "unique_id": unique_id,
+            "object_id": unique_id,
+            "state_topic": state_topic,
+            "value_template": "{{ value_json.count }}",
+            "json_attributes_topic": state_topic,
+            "availability_topic": availability_topic,
+            "unit_of_measurement": "updates",
+            "state_class": "measurement",
+            "icon": "mdi:package-up",
+            "device": {
+                "identifiers": [unique_id],
+                "name": hostname,
+                "manufacturer": "Arch Linux",
+                "model": "arch-updates-rs",
+                "sw_version": env!("CARGO_PKG_VERSION"),
+            },
+        })
+        .to_string();
+
+        let (client, mut connection) = Client::new(options, 10);
+
+        let announcer = client.clone();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let connection_stopped = stopped.clone();
+        thread::spawn(move || {
+            for notification in connection.iter() {
+                if connection_stopped.load(Ordering::SeqCst) {
+                    return;
+                }
+                match notification {
+                    Ok(rumqttc::Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("Connected to MQTT broker");
+                        // Discovery and availability are re-sent on every
+                        // connect so Home Assistant picks them up after a
+                        // broker restart.
+                        if let Err(e) = announcer
+                            .try_publish(
+                                &discovery_topic,
+                                QoS::AtLeastOnce,
+                                true,
+                                discovery.clone(),
+                            )
+                            .and_then(|_| {
+                                announcer.try_publish(
+                                    &availability_topic,
+                                    QoS::AtLeastOnce,
+                                    true,
+                                    "online",
+                                )
+                            })
+                        {
+                            error!("Failed to announce to MQTT broker: {}", e);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("MQTT connection error: {}", e);
+                        thread::sleep(RECONNECT_DELAY);
+                    }
+                }
+            }
+        });
+
+        Some(Self {
+            client,
+            state_topic,
+            stopped,
+        })
+    }
+
+    pub fn publish(&self, status: &Status) -> Result<()> {
+        let payload = json!({
+            "count": status.updates.len(),
+            "severity": status.severity,
+            "state": status.state.as_str(),
+            "last_checked": status.last_checked,
+            "last_upgrade": status.last_upgrade,
+        })
+        .to_string();
+
+        self.client
+            .try_publish(&self.state_topic, QoS::AtLeastOnce, true, payload)
+            .context("Failed to publish state to MQTT broker")?;
+
+        Ok(())
+    }
+}
+
+impl Drop for Mqtt {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        let _ = self.client.try_disconnect();
+    }
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+const NEWS_FEED_URL: &str = "https://archlinux.org/feeds/news/";
+const NEWS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Holding back the updates until the Arch news are read, like informant.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct NewsConfig {
+    /// Fetches the news with every check that finds updates. News published
+    /// since the last upgrade keep the icon in its news state until they are
+    /// marked read.
+    pub enabled: bool,
+}
+
+/// An item of the Arch Linux news feed.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewsItem {
+    pub title: String,
+    pub link: String,
+    pub published: DateTime<Utc>,
+}
+
+/// The items of the news feed, newest first.
+pub fn fetch() -> Result<Vec<NewsItem>> {
+    let agent = ureq::AgentBuilder::new().timeout(NEWS_TIMEOUT).build();
+    let feed = agent
+        .get(NEWS_FEED_URL)
+        .call()
+        .context("Failed to fetch the Arch news")?
+        .into_string()
+        .context("Failed to read the Arch news")?;
+
+    Ok(parse_feed(&feed))
+}
+
+/// The items published after `since`. Without a last upgrade to compare
+/// against, every item counts.
+pub fn published_after(items: &[NewsItem], since: Option<DateTime<Utc>>) -> Vec<NewsItem> {
+    items
+        .iter()
+        .filter(|item| since.is_none_or(|since| item.published > since))
+        .cloned()
+        .collect()
+}
+
+/// Reads the items of an RSS feed. The feed is simple enough that the tags
+/// can be picked out without an XML parser; items without a parsable date
+/// are skipped.
+fn parse_feed(feed: &str) -> Vec<NewsItem> {
+    feed.split("<item>")
+        .skip(1)
+        .filter_map(|item| {
+            let published = DateTime::parse_from_rfc2822(&tag_text(item, "pubDate")?).ok()?;
+            Some(NewsItem {
+                title: tag_text(item, "title")?,
+                link: tag_text(item, "link").unwrap_or_default(),
+                published: published.with_timezone(&Utc),
+            })
+        })
+        .collect()
+}
+
+fn tag_text(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    let text = xml[start..end].trim();
+    let text = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|text| text.strip_suffix("]]>"))
+        .unwrap_or(text);
+
+    Some(decode_entities(text))
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,350 @@
+use std::{
+    process::Command,
+    sync::{Mutex, mpsc::Sender},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use zbus::blocking::{Connection, Proxy};
+
+use crate::{
+    Event,
+    config::Config,
+    i18n::t,
+    privileges,
+    status::{self, Severity},
+};
+
+const APP_NAME: &str = "arch-updates-rs";
+
+const UPDATE_NOW_ACTION: &str = "update-now";
+const SNOOZE_ACTION: &str = "snooze";
+
+/// Packages listed in the body of an update notification before the rest is
+/// summarized.
+const MAX_LISTED_UPDATES: usize = 10;
+
+/// How often do-not-disturb is looked at while an update notification waits
+/// for it to end.
+const DO_NOT_DISTURB_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The update notification held back while do-not-disturb is on. Later
+/// checks replace it, so a single notification is shown once it ends.
+static DEFERRED: Mutex<Option<Deferred>> = Mutex::new(None);
+
+struct Deferred {
+    config: Config,
+    tx: Sender<Event>,
+    severity: Severity,
+    updates: Vec<String>,
+    new_updates: Vec<String>,
+}
+
+/// Desktop notifications about pending updates.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationConfig {
+    pub enabled: bool,
+    /// Only notify when a check finds packages that weren't pending before,
+    /// instead of after every check with pending updates.
+    pub only_new: bool,
+    /// Only raise the icon's severity in a check that found new packages.
+    pub escalate_only_on_new: bool,
+    /// How long the "Snooze" action suppresses update notifications.
+    pub snooze_secs: u64,
+    pub urgency: UrgencyConfig,
+}
+
+/// Urgency levels of the notification specification.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Urgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+impl From<Urgency> for notify_rust::Urgency {
+    fn from(urgency: Urgency) -> Self {
+        match urgency {
+            Urgency::Low => notify_rust::Urgency::Low,
+            Urgency::Normal => notify_rust::Urgency::Normal,
+            Urgency::Critical => notify_rust::Urgency::Critical,
+        }
+    }
+}
+
+/// The urgency of update notifications for each severity the icon shows.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct UrgencyConfig {
+    /// Below the warning threshold.
+    pub normal: Urgency,
+    pub warning: Urgency,
+    pub critical: Urgency,
+}
+
+impl Default for UrgencyConfig {
+    fn default() -> Self {
+        Self {
+            normal: Urgency::Low,
+            warning: Urgency::Normal,
+            critical: Urgency::Critical,
+        }
+    }
+}
+
+impl UrgencyConfig {
+    fn for_severity(&self, severity: Severity) -> Urgency {
+        match severity {
+            Severity::None | Severity::Normal => self.normal,
+            Severity::Warning => self.warning,
+            Severity::Critical => self.critical,
+        }
+    }
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            only_new: true,
+            escalate_only_on_new: false,
+            snooze_secs: 4 * 60 * 60,
+            urgency: UrgencyConfig::default(),
+        }
+    }
+}
+
+/// Shows a desktop notification through the freedesktop notification daemon.
+pub fn send(summary: &str, body: &str) -> Result<()> {
+    Notification::new()
+        .appname(APP_NAME)
+        .summary(summary)
+        .body(body)
+        .show()
+        .context("Failed to show notification")?;
+
+    Ok(())
+}
+
+/// Notifies about the pending updates of a check. `new_updates` are the
+/// entries that weren't pending in the previous check. The notification
+/// offers to update right away or to snooze further notifications, the
+/// latter is reported as `Event::SnoozeNotifications`. The urgency follows
+/// `severity`, so it uses the same thresholds as the icon. While the
+/// notification daemon is in do-not-disturb mode, the notification waits
+/// until it ends.
+pub fn notify_updates(
+    config: &Config,
+    tx: Sender<Event>,
+    severity: Severity,
+    updates: &[String],
+    new_updates: &[String],
+) {
+    if !config.notifications.enabled {
+        return;
+    }
+
+    let mut deferred = DEFERRED.lock().unwrap();
+    let waiting = deferred.is_some();
+
+    // What became new while the notification waited is still new, unless it
+    // was upgraded since.
+    let mut new_updates = new_updates.to_vec();
+    if let Some(previous) = deferred.take() {
+        let is_pending = |update: &String| {
+            let name = package_name(update);
+            updates.iter().any(|pending| package_name(pending) == name)
+        };
+        let earlier: Vec<String> = previous
+            .new_updates
+            .into_iter()
+            .filter(|update| {
+                is_pending(update)
+                    && !new_updates
+                        .iter()
+                        .any(|new| package_name(new) == package_name(update))
+            })
+            .collect();
+        new_updates.splice(0..0, earlier);
+    }
+
+    if do_not_disturb() {
+        info!("Holding the update notification back until do-not-disturb ends");
+        *deferred = Some(Deferred {
+            config: config.clone(),
+            tx,
+            severity,
+            updates: updates.to_vec(),
+            new_updates,
+        });
+        if !waiting {
+            show_after_do_not_disturb();
+        }
+        return;
+    }
+    drop(deferred);
+
+    show_updates(config, tx, severity, updates, &new_updates);
+}
+
+/// Shows the deferred notification once do-not-disturb ends.
+fn show_after_do_not_disturb() {
+    thread::spawn(|| {
+        loop {
+            thread::sleep(DO_NOT_DISTURB_POLL_INTERVAL);
+            if do_not_disturb() {
+                continue;
+            }
+
+            let deferred = DEFERRED.lock().unwrap().take();
+            if let Some(deferred) = deferred {
+                show_updates(
+                    &deferred.config,
+                    deferred.tx,
+                    deferred.severity,
+                    &deferred.updates,
+                    &deferred.new_updates,
+                );
+            }
+            return;
+        }
+    });
+}
+
+fn show_updates(
+    config: &Config,
+    tx: Sender<Event>,
+    severity: Severity,
+    updates: &[String],
+    new_updates: &[String],
+) {
+    let (summary, listed) = if config.notifications.only_new {
+        if new_updates.is_empty() {
+            return;
+        }
+        (t!("new-updates", count = new_updates.len()), new_updates)
+    } else {
+        if updates.is_empty() {
+            return;
+        }
+        (t!("pending-updates", count = updates.len()), updates)
+    };
+
+    let mut body = listed
+        .iter()
+        .take(MAX_LISTED_UPDATES)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if listed.len() > MAX_LISTED_UPDATES {
+        body.push_str(&format!(
+            "\n{}",
+            t!("more-updates", count = listed.len() - MAX_LISTED_UPDATES)
+        ));
+    }
+    if status::keyring_pending(updates) {
+        body = format!(
+            "{}\n{}",
+            t!("keyring-first", package = status::KEYRING_PACKAGE),
+            body
+        );
+    }
+
+    let handle = match Notification::new()
+        .appname(APP_NAME)
+        .summary(&summary)
+        .body(&body)
+        .urgency(config.notifications.urgency.for_severity(severity).into())
+        .action(
+            UPDATE_NOW_ACTION,
+            &privileges::action_label(&t!("update-now")),
+        )
+        .action(SNOOZE_ACTION, &t!("snooze"))
+        .show()
+    {
+        Ok(handle) => handle,
+        Err(e) => {
+            error!("Failed to show notification: {}", e);
+            return;
+        }
+    };
+
+    // The notification daemon reports the chosen action over D-Bus, which
+    // blocks until the notification is gone.
+    let update_command = config.update_command_for(updates);
+    let config = config.clone();
+    thread::spawn(move || {
+        handle.wait_for_action(|action| match action {
+            UPDATE_NOW_ACTION => {
+                if let Err(e) = privileges::run(&config, &update_command) {
+                    error!("Failed to launch update terminal: {}", e);
+                }
+            }
+            SNOOZE_ACTION => {
+                info!("Snoozing update notifications");
+                let _ = tx.send(Event::SnoozeNotifications);
+            }
+            _ => {}
+        });
+    });
+}
+
+fn package_name(update: &str) -> &str {
+    update.split_whitespace().next().unwrap_or_default()
+}
+
+/// Whether the notification daemon is in do-not-disturb mode. Asks the
+/// `Inhibited` property of the notification specification first, then the
+/// daemons that only tell their own clients: dunst, swaync and mako.
+fn do_not_disturb() -> bool {
+    if let Ok(inhibited) = inhibited() {
+        return inhibited;
+    }
+
+    let answers = [
+        ("dunstctl", &["is-paused"][..]),
+        ("swaync-client", &["--get-dnd"][..]),
+    ];
+    for (program, args) in answers {
+        if let Some(output) = run(program, args) {
+            return output.trim() == "true";
+        }
+    }
+
+    run("makoctl", &["mode"]).is_some_and(|modes| {
+        modes
+            .split_whitespace()
+            .any(|mode| mode == "do-not-disturb")
+    })
+}
+
+fn inhibited() -> Result<bool> {
+    let connection = Connection::session().context("Failed to connect to the session bus")?;
+    let proxy = Proxy::new(
+        &connection,
+        "org.freedesktop.Notifications",
+        "/org/freedesktop/Notifications",
+        "org.freedesktop.Notifications",
+    )
+    .context("Failed to create notifications proxy")?;
+
+    proxy
+        .get_property("Inhibited")
+        .context("Failed to read whether notifications are inhibited")
+}
+
+/// The output of a daemon's client, `None` if it isn't installed or its
+/// daemon isn't running.
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
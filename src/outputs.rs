@@ -0,0 +1,294 @@
+use std::{
+    sync::mpsc::Sender,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    calendar,
+    config::{Config, differs},
+    eww,
+    hyprland::{self, Message},
+    mqtt::Mqtt,
+    state_file,
+    status::{Status, StatusResponse},
+    template, webhook,
+};
+
+/// Minimum time between two updates of each output. `0` updates on every
+/// change.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThrottleConfig {
+    /// Refresh of the tray's tooltip and "Last checked" labels.
+    pub tooltip_secs: u64,
+    pub mqtt_secs: u64,
+    pub webhook_secs: u64,
+    pub calendar_secs: u64,
+    /// Rewrites of `state.json` in the runtime directory.
+    pub state_file_secs: u64,
+    pub eww_secs: u64,
+    pub hyprland_secs: u64,
+    pub template_secs: u64,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            tooltip_secs: 30,
+            mqtt_secs: 0,
+            webhook_secs: 0,
+            calendar_secs: 0,
+            state_file_secs: 0,
+            eww_secs: 0,
+            hyprland_secs: 0,
+            template_secs: 0,
+        }
+    }
+}
+
+/// Limits how often an output runs. A change that arrives too early isn't
+/// dropped but becomes due once the interval has passed.
+struct Throttle {
+    interval: Duration,
+    last_run: Option<Instant>,
+    pending: bool,
+}
+
+impl Throttle {
+    fn new(secs: u64) -> Self {
+        Self {
+            interval: Duration::from_secs(secs),
+            last_run: None,
+            pending: false,
+        }
+    }
+
+    /// Returns whether the output may run now, otherwise remembers the change.
+    fn ready(&mut self) -> bool {
+        if self
+            .last_run
+            .is_some_and(|last_run| last_run.elapsed() < self.interval)
+        {
+            self.pending = true;
+            return false;
+        }
+
+        self.last_run = Some(Instant::now());
+        self.pending = false;
+        true
+    }
+
+    fn is_due(&self) -> bool {
+        self.time_until_due() == Some(Duration::ZERO)
+    }
+
+    /// How long until a remembered change may run, if there is one.
+    fn time_until_due(&self) -> Option<Duration> {
+        if !self.pending {
+            return None;
+        }
+
+        Some(
+            self.last_run
+                .map(|last_run| self.interval.saturating_sub(last_run.elapsed()))
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// The push outputs that are updated from the main loop.
+pub struct Outputs {
+    mqtt: Option<Mqtt>,
+    mqtt_throttle: Throttle,
+    webhook_throttle: Throttle,
+    /// The status the webhook compared against last, so throttled changes
+    /// are still reported against what the receiver has seen.
+    webhook_status: Status,
+    calendar_throttle: Throttle,
+    state_file_throttle: Throttle,
+    eww_throttle: Throttle,
+    hyprland_throttle: Throttle,
+    template_throttle: Throttle,
+    /// The last notification Hyprland showed, so only changes are shown.
+    hyprland_message: Option<Message>,
+    /// Writes the template's lines to its FIFO or file.
+    template_writer: Option<Sender<String>>,
+}
+
+impl Outputs {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            mqtt: Mqtt::connect(&config.mqtt),
+            mqtt_throttle: Throttle::new(config.throttle.mqtt_secs),
+            webhook_throttle: Throttle::new(config.throttle.webhook_secs),
+            webhook_status: Status::default(),
+            calendar_throttle: Throttle::new(config.throttle.calendar_secs),
+            state_file_throttle: Throttle::new(config.throttle.state_file_secs),
+            eww_throttle: Throttle::new(config.throttle.eww_secs),
+            hyprland_throttle: Throttle::new(config.throttle.hyprland_secs),
+            template_throttle: Throttle::new(config.throttle.template_secs),
+            hyprland_message: None,
+            template_writer: config.template.path.clone().map(template::spawn_writer),
+        }
+    }
+
+    /// Applies a reloaded config. Outputs whose settings changed are set up
+    /// again, the others keep their connections and throttles.
+    pub fn reconfigure(&mut self, old: &Config, new: &Config) {
+        if differs(&old.mqtt, &new.mqtt) {
+            self.mqtt = Mqtt::connect(&new.mqtt);
+        }
+        if differs(&old.throttle, &new.throttle) {
+            self.mqtt_throttle = Throttle::new(new.throttle.mqtt_secs);
+            self.webhook_throttle = Throttle::new(new.throttle.webhook_secs);
+            self.calendar_throttle = Throttle::new(new.throttle.calendar_secs);
+            self.state_file_throttle = Throttle::new(new.throttle.state_file_secs);
+            self.eww_throttle = Throttle::new(new.throttle.eww_secs);
+            self.hyprland_throttle = Throttle::new(new.throttle.hyprland_secs);
+            self.template_throttle = Throttle::new(new.throttle.template_secs);
+        }
+        if old.template.path != new.template.path {
+            self.template_writer = new.template.path.clone().map(template::spawn_writer);
+        }
+    }
+
+    /// Publishes a change of the state, like a check that started.
+    pub fn publish_state(&mut self, config: &Config, status: &Status) {
+        self.publish_mqtt(status);
+        self.publish_hyprland(config, status);
+        self.publish_template(config, status);
+        self.publish_eww(config, status);
+        self.publish_state_file(status);
+    }
+
+    /// Publishes the result of a check.
+    pub fn publish(&mut self, config: &Config, status: &Status) {
+        self.publish_mqtt(status);
+        self.publish_webhook(config, status);
+        self.publish_calendar(config, status);
+        self.publish_hyprland(config, status);
+        self.publish_template(config, status);
+        self.publish_eww(config, status);
+        self.publish_state_file(status);
+    }
+
+    /// Runs the outputs whose throttled changes have become due.
+    pub fn flush(&mut self, config: &Config, status: &Status) {
+        if self.mqtt_throttle.is_due() {
+            self.publish_mqtt(status);
+        }
+        if self.webhook_throttle.is_due() {
+            self.publish_webhook(config, status);
+        }
+        if self.calendar_throttle.is_due() {
+            self.publish_calendar(config, status);
+        }
+        if self.state_file_throttle.is_due() {
+            self.publish_state_file(status);
+        }
+        if self.eww_throttle.is_due() {
+            self.publish_eww(config, status);
+        }
+        if self.hyprland_throttle.is_due() {
+            self.publish_hyprland(config, status);
+        }
+        if self.template_throttle.is_due() {
+            self.publish_template(config, status);
+        }
+    }
+
+    /// How long the main loop may wait before `flush` has something to do.
+    pub fn next_due(&self) -> Option<Duration> {
+        [
+            &self.mqtt_throttle,
+            &self.webhook_throttle,
+            &self.calendar_throttle,
+            &self.state_file_throttle,
+            &self.eww_throttle,
+            &self.hyprland_throttle,
+            &self.template_throttle,
+        ]
+        .into_iter()
+        .filter_map(Throttle::time_until_due)
+        .min()
+    }
+
+    fn publish_mqtt(&mut self, status: &Status) {
+        if let Some(mqtt) = &self.mqtt
+            && self.mqtt_throttle.ready()
+            && let Err(e) = mqtt.publish(status)
+        {
+            error!("{}", e);
+        }
+    }
+
+    fn publish_webhook(&mut self, config: &Config, status: &Status) {
+        if config.webhook.url.is_some() && self.webhook_throttle.ready() {
+            webhook::notify_change(&config.webhook, &self.webhook_status, status);
+            self.webhook_status = status.clone();
+        }
+    }
+
+    fn publish_calendar(&mut self, config: &Config, status: &Status) {
+        if config.calendar.enabled
+            && self.calendar_throttle.ready()
+            && let Err(e) = calendar::write(config, status)
+        {
+            error!("Failed to write maintenance calendar: {}", e);
+        }
+    }
+
+    fn publish_hyprland(&mut self, config: &Config, status: &Status) {
+        if !config.hyprland.enabled {
+            return;
+        }
+
+        let Some(message) = hyprland::message(status) else {
+            return;
+        };
+        if self.hyprland_message.as_ref() == Some(&message) || !self.hyprland_throttle.ready() {
+            return;
+        }
+
+        if let Err(e) = hyprland::notify(&config.hyprland, &message) {
+            error!("{:#}", e);
+        }
+        self.hyprland_message = Some(message);
+    }
+
+    fn publish_template(&mut self, config: &Config, status: &Status) {
+        let Some(writer) = &self.template_writer else {
+            return;
+        };
+        if !self.template_throttle.ready() {
+            return;
+        }
+
+        match serde_json::to_value(StatusResponse::new(status)) {
+            Ok(status) => {
+                let _ = writer.send(template::render(&status, &config.template));
+            }
+            Err(e) => error!("Failed to serialize status: {}", e),
+        }
+    }
+
+    fn publish_eww(&mut self, config: &Config, status: &Status) {
+        if config.eww.enabled
+            && self.eww_throttle.ready()
+            && let Err(e) = eww::publish(&config.eww, status)
+        {
+            error!("{:#}", e);
+        }
+    }
+
+    fn publish_state_file(&mut self, status: &Status) {
+        if self.state_file_throttle.ready()
+            && let Err(e) = state_file::write(status)
+        {
+            error!("Failed to write state file: {:#}", e);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result, bail};
+use tracing::error;
+
+const PACMAN_CONF_PATH: &str = "/etc/pacman.conf";
+
+/// pacman's compiled-in default when pacman.conf doesn't set `DBPath`.
+const DEFAULT_DB_PATH: &str = "/var/lib/pacman/";
+
+/// How deep `Include`s may nest, which stops include loops.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// The settings of the `[options]` section of pacman.conf this program uses.
+#[derive(Debug, Default)]
+pub struct PacmanConf {
+    pub db_path: Option<PathBuf>,
+    /// Patterns of `IgnorePkg`, which may contain `*` and `?`.
+    pub ignore_pkg: Vec<String>,
+    /// Patterns of `IgnoreGroup`.
+    pub ignore_group: Vec<String>,
+    /// The packages of the groups in `ignore_group`, from the sync databases.
+    pub ignored_group_members: HashSet<String>,
+}
+
+impl PacmanConf {
+    pub fn read() -> Result<Self> {
+        let mut conf = Self::default();
+        let mut in_options = false;
+        conf.read_file(Path::new(PACMAN_CONF_PATH), &mut in_options, 0)?;
+
+        // The rest of the settings are still good without the groups.
+        if !conf.ignore_group.is_empty() {
+            match group_members(&conf.ignore_group) {
+                Ok(members) => conf.ignored_group_members = members,
+                Err(e) => error!("{:#}", e),
+            }
+        }
+
+        Ok(conf)
+    }
+
+    fn read_file(&mut self, path: &Path, in_options: &mut bool, depth: usize) -> Result<()> {
+        self.read_with(path, in_options, depth, &|path| {
+            fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))
+        })
+    }
+
+    /// Reads the options of `path` and the files it includes, getting their
+    /// contents from `load`. Like pacman, an included file continues the
+    /// section of the `Include` line.
+    fn read_with(
+        &mut self,
+        path: &Path,
+        in_options: &mut bool,
+        depth: usize,
+        load: &dyn Fn(&Path) -> Result<String>,
+    ) -> Result<()> {
+        if depth > MAX_INCLUDE_DEPTH {
+            bail!("Includes of {:?} nest too deep", path);
+        }
+
+        for line in load(path)?.lines() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(section) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                *in_options = section == "options";
+                continue;
+            }
+
+            // Repositories include their mirrorlists, which don't matter here.
+            if !*in_options {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+
+            match key.trim() {
+                "DBPath" => self.db_path = Some(PathBuf::from(value.trim())),
+                // May be given several times, each with several packages.
+                "IgnorePkg" => self
+                    .ignore_pkg
+                    .extend(value.split_whitespace().map(str::to_string)),
+                "IgnoreGroup" => self
+                    .ignore_group
+                    .extend(value.split_whitespace().map(str::to_string)),
+                "Include" => {
+                    for include in expand(value.trim()) {
+                        self.read_with(&include, in_options, depth + 1, load)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether pacman skips upgrades of `package`.
+    pub fn is_ignored(&self, package: &str) -> bool {
+        self.ignored_group_members.contains(package)
+            || self
+                .ignore_pkg
+                .iter()
+                .any(|pattern| glob_match(pattern.as_bytes(), package.as_bytes()))
+    }
+}
+
+/// The files an `Include` names. Like pacman, `*` and `?` in the file name
+/// match several files, in alphabetical order.
+fn expand(include: &str) -> Vec<PathBuf> {
+    let path = Path::new(include);
+    let (Some(dir), Some(pattern)) = (path.parent(), path.file_name()) else {
+        return vec![path.to_path_buf()];
+    };
+    let pattern = pattern.to_string_lossy();
+    if !pattern.contains(['*', '?']) {
+        return vec![path.to_path_buf()];
+    }
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| {
+            glob_match(
+                pattern.as_bytes(),
+                entry.file_name().to_string_lossy().as_bytes(),
+            )
+        })
+        .map(|entry| entry.path())
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// The packages of the sync database groups matching `patterns`.
+fn group_members(patterns: &[String]) -> Result<HashSet<String>> {
+    let output = Command::new("pacman")
+        .arg("-Sg")
+        .output()
+        .context("Failed to list package groups")?;
+    let groups: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|group| {
+            patterns
+                .iter()
+                .any(|pattern| glob_match(pattern.as_bytes(), group.as_bytes()))
+        })
+        .map(str::to_string)
+        .collect();
+    if groups.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let output = Command::new("pacman")
+        .arg("-Sgq")
+        .args(&groups)
+        .output()
+        .context("Failed to list the packages of ignored groups")?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Matches shell-style patterns with `*` and `?`, like pacman does for
+/// `IgnorePkg`, `IgnoreGroup` and `Include`.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match (pattern.first(), text.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_match(&pattern[1..], text) || (!text.is_empty() && glob_match(pattern, &text[1..]))
+        }
+        (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &text[1..]),
+        (Some(p), Some(t)) if p == t => glob_match(&pattern[1..], &text[1..]),
+        _ => false,
+    }
+}
+
+/// When the sync databases under `pacman_db_path` were last refreshed, e.g.
+/// by `pacman -Sy`.
+pub fn last_sync(pacman_db_path: &Path) -> Option<SystemTime> {
+    fs::read_dir(pacman_db_path.join("sync"))
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            entry
+                .path()
+                .extension()
+                .is_some_and(|extension| extension == "db")
+        })
+        .filter_map(|entry| {
+            entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .max()
+}
+
+/// The pacman database directory from pacman.conf, or pacman's default.
+pub fn db_path() -> PathBuf {
+    PacmanConf::read()
+        .ok()
+        .and_then(|conf| conf.db_path)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DB_PATH))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Reads `/etc/pacman.conf` from `files` instead of the disk.
+    fn read(files: &[(&str, &str)]) -> Result<PacmanConf> {
+        let files: HashMap<&str, &str> = files.iter().copied().collect();
+        let mut conf = PacmanConf::default();
+        conf.read_with(
+            Path::new(PACMAN_CONF_PATH),
+            &mut false,
+            0,
+            &|path| match files.get(path.to_str().unwrap()) {
+                Some(contents) => Ok(contents.to_string()),
+                None => bail!("{:?} doesn't exist", path),
+            },
+        )?;
+        Ok(conf)
+    }
+
+    #[test]
+    fn follows_includes_in_the_options() {
+        let conf = read(&[
+            (
+                "/etc/pacman.conf",
+                "\
+[options]
+DBPath = /var/lib/pacman-test/
+IgnorePkg = linux
+Include = /etc/pacman.d/ignore.conf
+IgnoreGroup = gnome
+
+[core]
+Include = /etc/pacman.d/mirrorlist
+",
+            ),
+            (
+                "/etc/pacman.d/ignore.conf",
+                "# Continues [options]\nIgnorePkg = nvidia* firefox\n",
+            ),
+            (
+                "/etc/pacman.d/mirrorlist",
+                "Server = https://example.org/$repo/os/$arch\nIgnorePkg = not-an-option\n",
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(conf.db_path, Some(PathBuf::from("/var/lib/pacman-test/")));
+        assert_eq!(conf.ignore_pkg, ["linux", "nvidia*", "firefox"]);
+        assert_eq!(conf.ignore_group, ["gnome"]);
+    }
+
+    #[test]
+    fn an_include_can_switch_sections() {
+        let conf = read(&[
+            (
+                "/etc/pacman.conf",
+                "[options]\nInclude = /etc/pacman.d/repos.conf\nIgnorePkg = linux\n",
+            ),
+            (
+                "/etc/pacman.d/repos.conf",
+                "IgnorePkg = vim\n[custom]\nServer = file:///srv/repo\n",
+            ),
+        ])
+        .unwrap();
+
+        // The section of the included file carries on after it.
+        assert_eq!(conf.ignore_pkg, ["vim"]);
+    }
+
+    #[test]
+    fn stops_include_loops() {
+        let error = read(&[(
+            "/etc/pacman.conf",
+            "[options]\nInclude = /etc/pacman.conf\n",
+        )])
+        .unwrap_err();
+        assert!(error.to_string().contains("nest too deep"), "{:#}", error);
+
+        assert!(read(&[("/etc/pacman.conf", "[options]\nInclude = /missing.conf\n")]).is_err());
+    }
+
+    #[test]
+    fn matches_globs() {
+        let cases = [
+            ("linux", "linux", true),
+            ("linux", "linux-lts", false),
+            ("linux*", "linux-lts", true),
+            ("linux*", "linux", true),
+            ("*-git", "neovim-git", true),
+            ("*-git", "neovim-git-debug", false),
+            ("python-?", "python-a", true),
+            ("python-?", "python-", false),
+            ("*", "", true),
+            ("?", "", false),
+            ("a*b*c", "aXXbYYc", true),
+            ("a*b*c", "aXXcYYb", false),
+            ("*.conf", "10-ignore.conf", true),
+        ];
+
+        for (pattern, text, expected) in cases {
+            assert_eq!(
+                glob_match(pattern.as_bytes(), text.as_bytes()),
+                expected,
+                "{} against {}",
+                pattern,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_packages_and_group_members() {
+        let conf = PacmanConf {
+            ignore_pkg: vec!["linux".to_string(), "nvidia*".to_string()],
+            ignore_group: vec!["gnome".to_string()],
+            ignored_group_members: HashSet::from(["gnome-shell".to_string()]),
+            ..PacmanConf::default()
+        };
+        let cases = [
+            ("linux", true),
+            ("linux-lts", false),
+            ("nvidia-utils", true),
+            ("gnome-shell", true),
+            ("gnome-terminal", false),
+            ("firefox", false),
+        ];
+
+        for (package, expected) in cases {
+            assert_eq!(conf.is_ignored(package), expected, "{}", package);
+        }
+    }
+}
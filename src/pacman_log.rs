@@ -0,0 +1,499 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    os::unix::fs::MetadataExt,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use tracing::error;
+
+use crate::{Event, i18n};
+
+const PACMAN_LOG_PATH: &str = "/var/log/pacman.log";
+
+/// How much of the end of the log to read when looking for the latest
+/// transaction. One transaction's worth of lines comfortably fits.
+const LOG_TAIL_BYTES: u64 = 256 * 1024;
+
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Gives up following a transaction that never logs its end, e.g. because
+/// pacman was killed.
+const FOLLOW_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// What pacman logs for each package of a transaction, and the message to
+/// show.
+const PACKAGE_ACTIONS: &[(&str, &str)] = &[
+    ("installed", "progress-installing"),
+    ("upgraded", "progress-installing"),
+    ("downgraded", "progress-installing"),
+    ("reinstalled", "progress-installing"),
+    ("removed", "progress-removing"),
+];
+
+/// What pacman prints for each package of a transaction, and the message to
+/// show.
+const PACKAGE_OPERATIONS: &[(&str, &str)] = &[
+    ("installing", "progress-installing"),
+    ("upgrading", "progress-installing"),
+    ("downgrading", "progress-installing"),
+    ("reinstalling", "progress-installing"),
+    ("removing", "progress-removing"),
+];
+
+/// What the pacman log says about past transactions. Between checks only
+/// the lines pacman appended since are read, the earlier ones are summed up
+/// here.
+pub struct PacmanLog {
+    path: PathBuf,
+    /// Identifies the file, so a rotated log is read from the start.
+    inode: u64,
+    position: u64,
+    partial_line: String,
+    full_upgrade_started: bool,
+    last_full_upgrade: Option<DateTime<FixedOffset>>,
+    /// When each package was last installed, upgraded or downgraded.
+    installed: HashMap<String, DateTime<FixedOffset>>,
+}
+
+impl PacmanLog {
+    pub fn new() -> Self {
+        Self::at(PathBuf::from(PACMAN_LOG_PATH))
+    }
+
+    fn at(path: PathBuf) -> Self {
+        Self {
+            path,
+            inode: 0,
+            position: 0,
+            partial_line: String::new(),
+            full_upgrade_started: false,
+            last_full_upgrade: None,
+            installed: HashMap::new(),
+        }
+    }
+
+    /// Reads what pacman logged since the last call. The first call reads
+    /// the whole log.
+    pub fn update(&mut self) -> Result<()> {
+        let mut file = File::open(&self.path).context("Failed to open pacman log")?;
+        let metadata = file.metadata().context("Failed to read pacman log")?;
+        if metadata.ino() != self.inode || metadata.len() < self.position {
+            *self = Self::at(std::mem::take(&mut self.path));
+            self.inode = metadata.ino();
+        }
+
+        file.seek(SeekFrom::Start(self.position))
+            .context("Failed to seek in pacman log")?;
+        let mut new = Vec::new();
+        file.read_to_end(&mut new)
+            .context("Failed to read pacman log")?;
+        self.position += new.len() as u64;
+        self.read(&String::from_utf8_lossy(&new));
+
+        Ok(())
+    }
+
+    /// Takes in `text` appended to the log. A line pacman is still writing is
+    /// kept until the rest of it arrives.
+    fn read(&mut self, text: &str) {
+        self.partial_line.push_str(text);
+        while let Some(end) = self.partial_line.find('\n') {
+            let line: String = self.partial_line.drain(..=end).collect();
+            self.read_line(&line);
+        }
+    }
+
+    fn read_line(&mut self, line: &str) {
+        if line.contains("[PACMAN] Running ") {
+            self.full_upgrade_started = false;
+        } else if line.contains("[PACMAN] starting full system upgrade") {
+            self.full_upgrade_started = true;
+        } else if self.full_upgrade_started && line.contains("[ALPM] transaction completed") {
+            if let Some(timestamp) = parse_timestamp(line) {
+                self.last_full_upgrade = Some(timestamp);
+            }
+            self.full_upgrade_started = false;
+        }
+
+        let Some((_, message)) = line.split_once("[ALPM] ") else {
+            return;
+        };
+        let mut words = message.split_whitespace();
+        let (Some(action), Some(package)) = (words.next(), words.next()) else {
+            return;
+        };
+        if action == "removed" || !PACKAGE_ACTIONS.iter().any(|(name, _)| *name == action) {
+            return;
+        }
+        if let Some(timestamp) = parse_timestamp(line) {
+            self.installed.insert(package.to_string(), timestamp);
+        }
+    }
+
+    /// When the last full system upgrade (`pacman -Syu`) transaction
+    /// completed.
+    pub fn last_full_upgrade(&self) -> Option<DateTime<FixedOffset>> {
+        self.last_full_upgrade
+    }
+
+    /// The packages that were installed, upgraded or downgraded after
+    /// `since`, in the order they were installed last.
+    pub fn installed_since(&self, since: DateTime<Utc>) -> Vec<String> {
+        let mut installed: Vec<_> = self
+            .installed
+            .iter()
+            .filter(|(_, timestamp)| **timestamp > since)
+            .collect();
+        installed.sort_by(|a, b| a.1.cmp(b.1).then_with(|| a.0.cmp(b.0)));
+        installed
+            .into_iter()
+            .map(|(package, _)| package.clone())
+            .collect()
+    }
+}
+
+/// Returns whether the most recent transaction in the pacman log has finished,
+/// either successfully or not.
+pub fn last_transaction_finished() -> Result<bool> {
+    let mut file = File::open(PACMAN_LOG_PATH).context("Failed to open pacman log")?;
+    let len = file.metadata().context("Failed to read pacman log")?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(LOG_TAIL_BYTES)))
+        .context("Failed to seek in pacman log")?;
+
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)
+        .context("Failed to read pacman log")?;
+
+    Ok(transaction_finished(&String::from_utf8_lossy(&tail)))
+}
+
+/// Whether the last transaction in `log` has ended. A log without any
+/// transaction counts as finished.
+fn transaction_finished(log: &str) -> bool {
+    for line in log.lines().rev() {
+        if line.contains("[ALPM] transaction started") {
+            return false;
+        }
+        if line.contains("[ALPM] transaction completed")
+            || line.contains("[ALPM] transaction failed")
+            || line.contains("[ALPM] transaction interrupted")
+        {
+            return true;
+        }
+    }
+
+    true
+}
+
+/// Reports the progress of the running transaction as `Event::Progress`,
+/// e.g. "Installing 23/87: firefox", by following the pacman log. `total` is
+/// the number of pending updates, since the log doesn't say how many
+/// packages the transaction has. Clears `following` once the transaction
+/// ended.
+pub fn follow_transaction(total: usize, tx: Sender<Event>, following: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        if let Err(e) = follow(total, &tx) {
+            error!("Failed to follow the pacman log: {:#}", e);
+        }
+        following.store(false, Ordering::SeqCst);
+    });
+}
+
+fn follow(total: usize, tx: &Sender<Event>) -> Result<()> {
+    let mut file = File::open(PACMAN_LOG_PATH).context("Failed to open pacman log")?;
+    let mut position = file.metadata().context("Failed to read pacman log")?.len();
+
+    // The transaction started before the database was written, so catch up
+    // on the packages it already logged.
+    file.seek(SeekFrom::Start(position.saturating_sub(LOG_TAIL_BYTES)))
+        .context("Failed to seek in pacman log")?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)
+        .context("Failed to read pacman log")?;
+    let tail = String::from_utf8_lossy(&tail);
+    let Some(start) = tail.rfind("[ALPM] transaction started") else {
+        return Ok(());
+    };
+
+    let mut done = 0;
+    for line in tail[start..].lines() {
+        if follow_line(line, &mut done, total, tx) {
+            return Ok(());
+        }
+    }
+
+    let started_following_at = Instant::now();
+    let mut partial_line = String::new();
+    while started_following_at.elapsed() < FOLLOW_TIMEOUT {
+        thread::sleep(FOLLOW_POLL_INTERVAL);
+
+        file.seek(SeekFrom::Start(position))
+            .context("Failed to seek in pacman log")?;
+        let mut new = Vec::new();
+        file.read_to_end(&mut new)
+            .context("Failed to read pacman log")?;
+        position += new.len() as u64;
+
+        partial_line.push_str(&String::from_utf8_lossy(&new));
+        while let Some(end) = partial_line.find('\n') {
+            let line: String = partial_line.drain(..=end).collect();
+            if follow_line(&line, &mut done, total, tx) {
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Counts and reports a package line. Returns whether the transaction ended.
+fn follow_line(line: &str, done: &mut usize, total: usize, tx: &Sender<Event>) -> bool {
+    let Some((_, message)) = line.split_once("[ALPM] ") else {
+        return false;
+    };
+
+    if message.starts_with("transaction completed")
+        || message.starts_with("transaction failed")
+        || message.starts_with("transaction interrupted")
+    {
+        return true;
+    }
+
+    let mut words = message.split_whitespace();
+    let (Some(action), Some(package)) = (words.next(), words.next()) else {
+        return false;
+    };
+    if let Some((_, message)) = PACKAGE_ACTIONS.iter().find(|(name, _)| *name == action) {
+        *done += 1;
+        let progress = progress(message, *done, total.max(*done), package);
+        return tx.send(Event::Progress(progress)).is_err();
+    }
+
+    false
+}
+
+/// Turns a progress line of pacman's output, like "(23/87) upgrading
+/// firefox", into the same form as `follow_transaction`. Other lines are
+/// returned as they are.
+pub fn progress_from_output(line: &str) -> String {
+    let parsed = line.strip_prefix('(').and_then(|line| {
+        let (counts, operation) = line.split_once(") ")?;
+        let (done, total) = counts.split_once('/')?;
+        let (operation, package) = operation.split_once(' ')?;
+        let (_, message) = PACKAGE_OPERATIONS
+            .iter()
+            .find(|(name, _)| *name == operation)?;
+        Some(progress(
+            message,
+            done.trim().parse().ok()?,
+            total.trim().parse().ok()?,
+            package.trim_end_matches('.'),
+        ))
+    });
+
+    parsed.unwrap_or_else(|| line.to_string())
+}
+
+/// E.g. "Installing 23/87: firefox".
+fn progress(message: &str, done: usize, total: usize, package: &str) -> String {
+    i18n::translate(
+        message,
+        &[
+            ("done", done.to_string()),
+            ("total", total.to_string()),
+            ("package", package.to_string()),
+        ],
+    )
+}
+
+/// Parses the `[2024-05-01T10:00:00+0200]` prefix of a pacman log line.
+fn parse_timestamp(line: &str) -> Option<DateTime<FixedOffset>> {
+    let end = line.find(']')?;
+    let timestamp = line.get(1..end)?;
+    DateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S%z").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSACTION: &str = "\
+[2024-05-01T10:00:00+0200] [PACMAN] Running 'pacman -Syu'
+[2024-05-01T10:00:00+0200] [PACMAN] synchronizing package lists
+[2024-05-01T10:00:05+0200] [PACMAN] starting full system upgrade
+[2024-05-01T10:00:20+0200] [ALPM] transaction started
+[2024-05-01T10:00:21+0200] [ALPM] upgraded firefox (125.0.3-1 -> 126.0-1)
+[2024-05-01T10:00:22+0200] [ALPM] installed nss (3.100-1)
+[2024-05-01T10:00:23+0200] [ALPM-SCRIPTLET] ==> Updating module dependencies
+";
+
+    fn timestamp(timestamp: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(timestamp)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn finds_the_last_full_upgrade() {
+        let completed = "[2024-05-01T10:00:24+0200] [ALPM] transaction completed\n";
+        let cases: &[(&[&str], Option<&str>)] = &[
+            (&[], None),
+            // Still running.
+            (&[TRANSACTION], None),
+            (&[TRANSACTION, completed], Some("2024-05-01T10:00:24+02:00")),
+            // Only `-Syu` counts, not installing a single package.
+            (
+                &[
+                    "[2024-05-01T09:00:00+0200] [PACMAN] Running 'pacman -S vlc'\n",
+                    "[2024-05-01T09:00:01+0200] [ALPM] transaction started\n",
+                    "[2024-05-01T09:00:02+0200] [ALPM] installed vlc (3.0.20-1)\n",
+                    "[2024-05-01T09:00:03+0200] [ALPM] transaction completed\n",
+                ],
+                None,
+            ),
+            // An upgrade that failed doesn't count either.
+            (
+                &[
+                    TRANSACTION,
+                    "[2024-05-01T10:00:24+0200] [ALPM] transaction failed\n",
+                    "[2024-05-01T11:00:00+0200] [PACMAN] Running 'pacman -S vlc'\n",
+                    "[2024-05-01T11:00:01+0200] [ALPM] transaction started\n",
+                    "[2024-05-01T11:00:03+0200] [ALPM] transaction completed\n",
+                ],
+                None,
+            ),
+            // The latest of several.
+            (
+                &[
+                    TRANSACTION,
+                    completed,
+                    "[2024-06-01T10:00:00+0200] [PACMAN] Running 'pacman -Syu'\n",
+                    "[2024-06-01T10:00:05+0200] [PACMAN] starting full system upgrade\n",
+                    "[2024-06-01T10:00:20+0200] [ALPM] transaction started\n",
+                    "[2024-06-01T10:00:24+0200] [ALPM] transaction completed\n",
+                ],
+                Some("2024-06-01T10:00:24+02:00"),
+            ),
+        ];
+
+        for (lines, expected) in cases {
+            let mut log = PacmanLog::at(PathBuf::new());
+            for line in *lines {
+                log.read(line);
+            }
+            assert_eq!(
+                log.last_full_upgrade(),
+                expected.map(|expected| DateTime::parse_from_rfc3339(expected).unwrap()),
+                "{:?}",
+                lines
+            );
+        }
+    }
+
+    #[test]
+    fn lists_packages_installed_since() {
+        let mut log = PacmanLog::at(PathBuf::new());
+        log.read(TRANSACTION);
+        log.read(
+            "[2024-05-01T10:00:24+0200] [ALPM] transaction completed
+[2024-05-02T09:00:00+0200] [PACMAN] Running 'pacman -S vlc'
+[2024-05-02T09:00:01+0200] [ALPM] transaction started
+[2024-05-02T09:00:02+0200] [ALPM] installed vlc (3.0.20-1)
+[2024-05-02T09:00:03+0200] [ALPM] removed python-six (1.16.0-1)
+[2024-05-02T09:00:04+0200] [ALPM] upgraded firefox (126.0-1 -> 126.0.1-1)
+[2024-05-02T09:00:05+0200] [ALPM] transaction completed
+[2024-05-02T09:00:06+0200] [ALPM] downgraded li",
+        );
+
+        let cases: &[(&str, &[&str])] = &[
+            ("2024-05-01T07:00:00Z", &["nss", "vlc", "firefox"]),
+            ("2024-05-01T08:00:21Z", &["nss", "vlc", "firefox"]),
+            ("2024-05-01T08:00:22Z", &["vlc", "firefox"]),
+            ("2024-05-02T07:00:02Z", &["firefox"]),
+            ("2024-05-03T00:00:00Z", &[]),
+        ];
+        for (since, expected) in cases {
+            assert_eq!(
+                log.installed_since(timestamp(since)),
+                *expected,
+                "since {}",
+                since
+            );
+        }
+
+        // The rest of the line pacman was writing.
+        log.read("bfoo (2-1 -> 1-1)\n");
+        assert_eq!(
+            log.installed_since(timestamp("2024-05-02T07:00:05Z")),
+            ["libfoo"]
+        );
+    }
+
+    #[test]
+    fn finds_the_end_of_the_last_transaction() {
+        let cases = [
+            ("", true),
+            (TRANSACTION, false),
+            (
+                "[2024-05-01T10:00:24+0200] [ALPM] transaction completed\n",
+                true,
+            ),
+            (
+                "[2024-05-01T10:00:24+0200] [ALPM] transaction failed\n",
+                true,
+            ),
+            (
+                "[2024-05-01T10:00:24+0200] [ALPM] transaction interrupted\n",
+                true,
+            ),
+            (
+                "[2024-05-01T10:00:24+0200] [ALPM] transaction completed\n\
+                 [2024-05-01T11:00:00+0200] [ALPM] transaction started\n",
+                false,
+            ),
+        ];
+
+        for (lines, expected) in cases {
+            let log = if lines.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", TRANSACTION, lines)
+            };
+            assert_eq!(transaction_finished(&log), expected, "{:?}", lines);
+        }
+    }
+
+    #[test]
+    fn rewrites_pacman_output_as_progress() {
+        let cases = [
+            ("(23/87) upgrading firefox", "Installing 23/87: firefox"),
+            ("( 1/12) installing nss", "Installing 1/12: nss"),
+            ("(3/3) removing python-six", "Removing 3/3: python-six"),
+            ("(2/5) downgrading linux...", "Installing 2/5: linux"),
+            (
+                "(1/1) checking keys in keyring",
+                "(1/1) checking keys in keyring",
+            ),
+            (
+                ":: Proceed with installation? [Y/n]",
+                ":: Proceed with installation? [Y/n]",
+            ),
+            ("(x/5) upgrading firefox", "(x/5) upgrading firefox"),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(progress_from_output(line), expected, "{}", line);
+        }
+    }
+}
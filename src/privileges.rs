@@ -0,0 +1,104 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+
+use anyhow::{Context, Result, bail};
+use tracing::info;
+
+use crate::{config::Config, i18n::t, notification, terminal};
+
+/// Groups that may become root with sudo or through polkit's admin rules.
+const ADMIN_GROUPS: &[&str] = &["wheel", "sudo", "admin"];
+
+/// Clipboard tools tried in order, with the arguments that make them read
+/// the text from stdin.
+const CLIPBOARD_TOOLS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+];
+
+/// Whether the user can run commands as root, through passwordless sudo, or
+/// with a password as member of an admin group. Detected once, since sudo
+/// may log every attempt.
+pub fn can_escalate() -> bool {
+    static CAN_ESCALATE: OnceLock<bool> = OnceLock::new();
+
+    *CAN_ESCALATE.get_or_init(|| {
+        let passwordless = Command::new("sudo")
+            .args(["-n", "true"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+
+        let groups = Command::new("id")
+            .arg("-Gn")
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+            .unwrap_or_default();
+        let admin = groups
+            .split_whitespace()
+            .any(|group| ADMIN_GROUPS.contains(&group));
+
+        let can_escalate = passwordless || admin;
+        if !can_escalate {
+            info!("Cannot become root, system actions copy their command instead");
+        }
+        can_escalate
+    })
+}
+
+/// The menu label of an action that needs root.
+pub fn action_label(label: &str) -> String {
+    if can_escalate() {
+        label.to_string()
+    } else {
+        t!("copy-command", label = label)
+    }
+}
+
+/// Runs a command that needs root in a terminal. Without a way to become
+/// root that would just fail, so the command is copied to the clipboard
+/// instead, to run it from an account that can.
+pub fn run(config: &Config, command: &str) -> Result<()> {
+    if can_escalate() {
+        return terminal::launch(config.terminal.as_deref(), &config.window, command);
+    }
+
+    copy_to_clipboard(command)?;
+    notification::send(
+        &t!("command-copied"),
+        &format!("{}\n{}", t!("command-copied-body"), command),
+    )
+}
+
+fn copy_to_clipboard(text: &str) -> Result<()> {
+    for (tool, args) in CLIPBOARD_TOOLS {
+        let Ok(mut child) = Command::new(tool)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .with_context(|| format!("Failed to write to {}", tool))?;
+        }
+
+        if child.wait().is_ok_and(|status| status.success()) {
+            return Ok(());
+        }
+    }
+
+    let tools: Vec<&str> = CLIPBOARD_TOOLS.iter().map(|(tool, _)| *tool).collect();
+    bail!("None of {} could copy to the clipboard", tools.join(", "))
+}
@@ -0,0 +1,71 @@
+use std::thread;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::sources::run_if_installed;
+
+pub const ID: &str = "python_tools";
+pub const NAME: &str = "Python tools";
+
+#[derive(Deserialize)]
+struct OutdatedPackage {
+    name: String,
+    version: String,
+    latest_version: String,
+}
+
+/// Returns outdated pipx applications and `pip install --user` packages,
+/// formatted like checkupdates lines (`name old -> new`).
+pub fn check() -> Result<Vec<String>> {
+    let applications = run_if_installed("pipx", &["list", "--short"])?.unwrap_or_default();
+
+    // Every pip run asks PyPI and takes seconds, so they all run at once.
+    thread::scope(|scope| {
+        let applications: Vec<_> = applications
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(|application| scope.spawn(move || outdated_application(application)))
+            .collect();
+        let user_packages = scope
+            .spawn(|| run_if_installed("pip", &["list", "--outdated", "--user", "--format=json"]));
+
+        let mut updates = Vec::new();
+        for application in applications {
+            updates.extend(application.join().unwrap()?);
+        }
+        if let Some(outdated) = user_packages.join().unwrap()? {
+            updates.extend(parse_outdated(&outdated)?.into_iter().map(format_package));
+        }
+        Ok(updates)
+    })
+}
+
+/// pipx keeps every application in its own virtualenv, so each one is asked
+/// separately and only its main package is reported.
+fn outdated_application(application: &str) -> Result<Vec<String>> {
+    let Some(outdated) = run_if_installed(
+        "pipx",
+        &["runpip", application, "list", "--outdated", "--format=json"],
+    )?
+    else {
+        return Ok(vec![]);
+    };
+
+    Ok(parse_outdated(&outdated)?
+        .into_iter()
+        .filter(|package| package.name.eq_ignore_ascii_case(application))
+        .map(format_package)
+        .collect())
+}
+
+fn parse_outdated(output: &str) -> Result<Vec<OutdatedPackage>> {
+    serde_json::from_str(output).context("Failed to parse pip output")
+}
+
+fn format_package(package: OutdatedPackage) -> String {
+    format!(
+        "{} {} -> {}",
+        package.name, package.version, package.latest_version
+    )
+}
@@ -0,0 +1,70 @@
+use std::{process::Command, sync::mpsc::Sender, thread};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::Event;
+
+/// Lists the packages linked against libraries that are gone after an
+/// upgrade, like foreign packages after a soname bump. Part of
+/// rebuild-detector.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RebuildConfig {
+    pub enabled: bool,
+}
+
+/// Runs `checkrebuild` and reports the packages that need a rebuild as
+/// `Event::RebuildChecked`.
+pub fn start(tx: Sender<Event>) {
+    thread::spawn(move || {
+        info!("Looking for packages that need a rebuild");
+        let packages = match check() {
+            Ok(packages) => {
+                info!(
+                    event = "rebuild_check",
+                    packages = packages.len(),
+                    "{} packages need a rebuild",
+                    packages.len()
+                );
+                Some(packages)
+            }
+            Err(e) => {
+                error!("Failed to run checkrebuild: {:#}", e);
+                None
+            }
+        };
+        let _ = tx.send(Event::RebuildChecked(packages));
+    });
+}
+
+/// checkrebuild prints one `<repository>\t<package>` line per package.
+/// Packages that aren't from a repository are listed as `foreign`, the
+/// others get their repository appended.
+fn check() -> Result<Vec<String>> {
+    let output = Command::new("checkrebuild")
+        .output()
+        .context("Failed to run checkrebuild, is rebuild-detector installed?")?;
+
+    if !output.status.success() {
+        bail!(
+            "checkrebuild exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (repository, package) = line.trim().split_once(char::is_whitespace)?;
+            let package = package.trim();
+            Some(if repository == "foreign" {
+                package.to_string()
+            } else {
+                format!("{} ({})", package, repository)
+            })
+        })
+        .collect())
+}
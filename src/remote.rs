@@ -0,0 +1,102 @@
+use std::{process::Stdio, thread};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    config::Config,
+    metrics, ssh,
+    status::{PACMAN_SOURCE, Severity, severity},
+};
+
+/// An additional machine checked with `ssh <destination> checkupdates`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteHost {
+    /// Anything `ssh` accepts as destination, e.g. `admin@nas` or a Host alias.
+    pub destination: String,
+    /// Label in the menu. Defaults to the destination.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default = "ssh::default_connect_timeout_secs")]
+    pub connect_timeout_secs: u32,
+}
+
+impl RemoteHost {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.destination)
+    }
+}
+
+/// The result of checking one remote host.
+#[derive(Debug, Clone, Serialize)]
+pub struct HostStatus {
+    pub name: String,
+    pub updates: Vec<String>,
+    pub severity: Severity,
+    pub error: Option<String>,
+}
+
+/// Checks all configured remote hosts in parallel.
+pub fn check_all(config: &Config) -> Vec<HostStatus> {
+    let interval_secs = config.interval_in_seconds as u64;
+
+    let handles: Vec<_> = config
+        .remote_hosts
+        .iter()
+        .cloned()
+        .map(|host| thread::spawn(move || (check(&host, interval_secs), host)))
+        .collect();
+
+    handles
+        .into_iter()
+        .filter_map(|handle| handle.join().ok())
+        .map(|(result, host)| match result {
+            Ok(updates) => {
+                info!("{} updates available on {}", updates.len(), host.name());
+                HostStatus {
+                    name: host.name().to_string(),
+                    severity: severity(PACMAN_SOURCE, updates.len() as u32, None, config),
+                    updates,
+                    error: None,
+                }
+            }
+            Err(e) => {
+                error!("Failed to check {} for updates: {}", host.name(), e);
+                metrics::increment(&metrics::COUNTERS.check_failures);
+                HostStatus {
+                    name: host.name().to_string(),
+                    updates: vec![],
+                    severity: Severity::None,
+                    error: Some(e.to_string()),
+                }
+            }
+        })
+        .collect()
+}
+
+fn check(host: &RemoteHost, interval_secs: u64) -> Result<Vec<String>> {
+    ssh::wait_jitter();
+
+    let output = ssh::command(&host.destination, host.connect_timeout_secs, interval_secs)?
+        .arg("checkupdates")
+        .stdin(Stdio::null())
+        .output()?;
+
+    // checkupdates exits with 2 when there are no updates, ssh with 255 when
+    // the connection failed.
+    match output.status.code() {
+        Some(0) | Some(2) => {}
+        _ => bail!(
+            "ssh exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.to_string())
+        .collect())
+}
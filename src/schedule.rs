@@ -0,0 +1,428 @@
+use std::{
+    str::FromStr,
+    sync::mpsc::{RecvTimeoutError, Sender, channel},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeDelta, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, info_span};
+
+use crate::{Event, config::Config, supervisor};
+
+/// How far ahead to look for the next time a cron expression matches. Covers
+/// expressions like "every February 29th that is a Monday".
+const MAX_LOOKAHEAD_DAYS: i64 = 366 * 28;
+
+/// A cron expression with the five fields minute, hour, day of month, month
+/// and day of week. The fields accept `*`, numbers, ranges such as `9-17`,
+/// lists such as `1,15` and steps such as `*/2`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// As in cron, a day matches either day field if both are restricted.
+    /// Like Vixie cron, a field starting with `*`, including `*/2`, counts
+    /// as unrestricted.
+    days_of_month_restricted: bool,
+    days_of_week_restricted: bool,
+}
+
+impl FromStr for CronSchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let fields: Vec<_> = expression.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            bail!(
+                "Expected 5 fields in cron expression {:?}, found {}",
+                expression,
+                fields.len()
+            );
+        };
+
+        // Sunday is both 0 and 7.
+        let mut days_of_week_bits =
+            parse_field(days_of_week, 0, 7).context("Invalid day of week")?;
+        if days_of_week_bits & 1 << 7 != 0 {
+            days_of_week_bits = (days_of_week_bits | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            expression: expression.to_string(),
+            minutes: parse_field(minutes, 0, 59).context("Invalid minute")?,
+            hours: parse_field(hours, 0, 23).context("Invalid hour")?,
+            days_of_month: parse_field(days_of_month, 1, 31).context("Invalid day of month")?,
+            months: parse_field(months, 1, 12).context("Invalid month")?,
+            days_of_week: days_of_week_bits,
+            days_of_month_restricted: !days_of_month.starts_with('*'),
+            days_of_week_restricted: !days_of_week.starts_with('*'),
+        })
+    }
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = anyhow::Error;
+
+    fn try_from(expression: String) -> Result<Self> {
+        expression.parse()
+    }
+}
+
+impl From<CronSchedule> for String {
+    fn from(schedule: CronSchedule) -> Self {
+        schedule.expression
+    }
+}
+
+impl CronSchedule {
+    /// The first full minute after `time` that matches the expression.
+    pub fn next_after(&self, time: DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut time =
+            time.naive_local().with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let limit = time + TimeDelta::days(MAX_LOOKAHEAD_DAYS);
+
+        while time < limit {
+            if !self.matches_date(time.date()) {
+                time = time.date().succ_opt()?.and_time(NaiveTime::MIN);
+                continue;
+            }
+
+            // Times skipped by a DST change don't exist and are left out.
+            if has_bit(self.hours, time.hour())
+                && has_bit(self.minutes, time.minute())
+                && let Some(next) = Local.from_local_datetime(&time).earliest()
+            {
+                return Some(next);
+            }
+
+            time += TimeDelta::minutes(1);
+        }
+
+        None
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        let day_of_month = has_bit(self.days_of_month, date.day());
+        let day_of_week = has_bit(self.days_of_week, date.weekday().num_days_from_sunday());
+        let day = if self.days_of_month_restricted && self.days_of_week_restricted {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        };
+
+        day && has_bit(self.months, date.month())
+    }
+}
+
+/// Parses one field of a cron expression into a bit set of the values it
+/// matches.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut bits = 0;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>()?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            bail!("Step of {:?} must not be 0", part);
+        }
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (start.parse()?, end.parse()?)
+        } else {
+            // `5/15` starts at 5 and repeats until the end of the range.
+            let start = range.parse()?;
+            (start, if step > 1 { max } else { start })
+        };
+
+        if start < min || end > max || start > end {
+            bail!("{:?} is outside of {}-{}", part, min, max);
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+fn has_bit(bits: u64, value: u32) -> bool {
+    bits & 1 << value != 0
+}
+
+/// Tells the timer thread about checks and config changes, so that they
+/// take effect right away rather than once the current wait is over.
+#[derive(Clone)]
+pub struct Scheduler {
+    tx: Sender<TimerCommand>,
+}
+
+enum TimerCommand {
+    /// The config was reloaded.
+    Reconfigure(Box<Config>),
+    /// A check ran, so the next one is due a full interval from now.
+    Restart,
+}
+
+impl Scheduler {
+    /// Reschedules the next check if `interval_in_seconds`,
+    /// `interval_jitter_percent` or `schedule` changed.
+    pub fn reconfigure(&self, config: &Config) {
+        let _ = self
+            .tx
+            .send(TimerCommand::Reconfigure(Box::new(config.clone())));
+    }
+
+    /// Starts the wait for the next check over, after a check that ran for
+    /// another reason, like an upgrade or `arch-updates-rs check`.
+    pub fn restart(&self) {
+        let _ = self.tx.send(TimerCommand::Restart);
+    }
+}
+
+/// Sends `Event::Checking` every `interval_in_seconds`, or whenever the cron
+/// expression in `schedule` matches.
+pub fn start(mut config: Config, tx: Sender<Event>) -> (Scheduler, Option<JoinHandle<()>>) {
+    let (timer_tx, timer_rx) = channel();
+    let thread = supervisor::supervise("timer", tx.clone(), move || {
+        let _span = info_span!("timer").entered();
+        let mut delay = next_delay(&config);
+        info!("Next check in {} seconds", delay.as_secs());
+        let mut due_at = Instant::now() + delay;
+        loop {
+            match supervisor::recv_timeout(
+                &timer_rx,
+                due_at.saturating_duration_since(Instant::now()),
+            ) {
+                Ok(TimerCommand::Reconfigure(new_config)) => {
+                    let changed = new_config.interval_in_seconds != config.interval_in_seconds
+                        || new_config.interval_jitter_percent != config.interval_jitter_percent
+                        || new_config.schedule != config.schedule;
+                    config = *new_config;
+                    if !changed {
+                        continue;
+                    }
+                    delay = next_delay(&config);
+                    info!("Rescheduled, next check in {} seconds", delay.as_secs());
+                }
+                Ok(TimerCommand::Restart) => {
+                    delay = next_delay(&config);
+                    debug!("Next check in {} seconds", delay.as_secs());
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if tx.send(Event::Checking).is_err() {
+                        return;
+                    }
+                    delay = next_delay(&config);
+                    info!("Next check in {} seconds", delay.as_secs());
+                }
+                // The app is shutting down.
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            due_at = Instant::now() + delay;
+        }
+    });
+
+    (Scheduler { tx: timer_tx }, thread)
+}
+
+fn next_delay(config: &Config) -> Duration {
+    if let Some(schedule) = &config.schedule {
+        let now = Local::now();
+        match schedule.next_after(now) {
+            Some(next) => return (next - now).to_std().unwrap_or_default(),
+            None => error!(
+                "Schedule {:?} never matches, using the interval instead",
+                String::from(schedule.clone())
+            ),
+        }
+    }
+
+    jittered_interval(config, &mut fastrand::Rng::new())
+}
+
+/// The interval, randomly shortened or lengthened by up to
+/// `interval_jitter_percent`, so machines sharing the same config don't all
+/// hit the mirrors at the same moment. The offset is uniformly distributed,
+/// so on average the checks still run once per interval.
+fn jittered_interval(config: &Config, rng: &mut fastrand::Rng) -> Duration {
+    let interval = config.interval_in_seconds as f64;
+    let max_jitter = interval * config.interval_jitter_percent.min(100) as f64 / 100.0;
+
+    // A value between -1 and 1.
+    let random = rng.f64() * 2.0 - 1.0;
+
+    Duration::from_secs_f64(interval + max_jitter * random)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(values: &[u32]) -> u64 {
+        values.iter().fold(0, |bits, value| bits | 1 << value)
+    }
+
+    fn local(time: &str) -> DateTime<Local> {
+        Local
+            .from_local_datetime(&time.parse().unwrap())
+            .earliest()
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_fields() {
+        let cases: &[(&str, u32, u32, u64)] = &[
+            ("*", 0, 5, bits(&[0, 1, 2, 3, 4, 5])),
+            ("3", 0, 59, bits(&[3])),
+            ("9-12", 0, 23, bits(&[9, 10, 11, 12])),
+            ("*/15", 0, 59, bits(&[0, 15, 30, 45])),
+            ("10-20/5", 0, 59, bits(&[10, 15, 20])),
+            ("5/20", 0, 59, bits(&[5, 25, 45])),
+            ("1,15", 1, 31, bits(&[1, 15])),
+            ("1-3,10,20-30/5", 1, 31, bits(&[1, 2, 3, 10, 20, 25, 30])),
+        ];
+        for (field, min, max, expected) in cases {
+            assert_eq!(
+                parse_field(field, *min, *max).unwrap(),
+                *expected,
+                "{}",
+                field
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        let cases: &[(&str, u32, u32)] = &[
+            ("60", 0, 59),
+            ("0", 1, 31),
+            ("5-3", 0, 59),
+            ("*/0", 0, 59),
+            ("a", 0, 59),
+            ("1-", 0, 59),
+            ("", 0, 59),
+        ];
+        for (field, min, max) in cases {
+            assert!(parse_field(field, *min, *max).is_err(), "{}", field);
+        }
+    }
+
+    #[test]
+    fn treats_seven_as_sunday() {
+        let schedule: CronSchedule = "0 0 * * 7".parse().unwrap();
+        assert_eq!(schedule.days_of_week, bits(&[0]));
+        assert!("0 0 * *".parse::<CronSchedule>().is_err());
+    }
+
+    #[test]
+    fn finds_the_next_match() {
+        // 2024-01-01 is a Monday.
+        let cases = [
+            ("*/15 * * * *", "2024-01-01T10:07:30", "2024-01-01T10:15:00"),
+            ("*/15 * * * *", "2024-01-01T10:45:00", "2024-01-01T11:00:00"),
+            ("0 9-17 * * *", "2024-01-01T17:00:00", "2024-01-02T09:00:00"),
+            ("30 6 * * 1-5", "2024-01-05T07:00:00", "2024-01-08T06:30:00"),
+            ("0 0 1 * *", "2024-01-15T00:00:00", "2024-02-01T00:00:00"),
+            ("0 12 29 2 *", "2024-03-01T00:00:00", "2028-02-29T12:00:00"),
+        ];
+        for (expression, after, expected) in cases {
+            let schedule: CronSchedule = expression.parse().unwrap();
+            assert_eq!(
+                schedule.next_after(local(after)),
+                Some(local(expected)),
+                "{} after {}",
+                expression,
+                after
+            );
+        }
+
+        let never: CronSchedule = "0 0 31 2 *".parse().unwrap();
+        assert_eq!(never.next_after(local("2024-01-01T00:00:00")), None);
+    }
+
+    #[test]
+    fn jitters_within_the_configured_share() {
+        let cases = [
+            (3600, 0, 3600.0, 3600.0),
+            (3600, 10, 3240.0, 3960.0),
+            (60, 250, 0.0, 120.0),
+        ];
+        let mut rng = fastrand::Rng::with_seed(7);
+
+        for (interval_in_seconds, interval_jitter_percent, min, max) in cases {
+            let config = Config {
+                interval_in_seconds,
+                interval_jitter_percent,
+                ..Config::default()
+            };
+            let delays: Vec<f64> = (0..1000)
+                .map(|_| jittered_interval(&config, &mut rng).as_secs_f64())
+                .collect();
+
+            assert!(
+                delays.iter().all(|delay| (min..=max).contains(delay)),
+                "{}s with {}%",
+                interval_in_seconds,
+                interval_jitter_percent
+            );
+            // Spread around the interval rather than towards one end.
+            let mean = delays.iter().sum::<f64>() / delays.len() as f64;
+            let tolerance = (max - min) * 0.05;
+            assert!(
+                (mean - interval_in_seconds as f64).abs() <= tolerance,
+                "mean {} of {}s with {}%",
+                mean,
+                interval_in_seconds,
+                interval_jitter_percent
+            );
+        }
+    }
+
+    #[test]
+    fn matches_either_day_field_only_if_both_are_restricted() {
+        // 2024-01-01 is a Monday, 2024-01-13 a Saturday.
+        let cases = [
+            // Either the 13th or a Monday.
+            ("0 0 13 * 1", "2024-01-01", true),
+            ("0 0 13 * 1", "2024-01-13", true),
+            ("0 0 13 * 1", "2024-01-02", false),
+            // Only the 13th.
+            ("0 0 13 * *", "2024-01-01", false),
+            ("0 0 13 * *", "2024-01-13", true),
+            // Only Mondays.
+            ("0 0 * * 1", "2024-01-01", true),
+            ("0 0 * * 1", "2024-01-13", false),
+            // A step starting with `*` is unrestricted, so only the odd
+            // days of the month that are Mondays.
+            ("0 0 */2 * 1", "2024-01-01", true),
+            ("0 0 */2 * 1", "2024-01-08", false),
+            ("0 0 */2 * 1", "2024-01-13", false),
+            ("0 0 13 * */3", "2024-01-13", true),
+            ("0 0 13 * */3", "2024-02-13", false),
+            ("0 0 13 * */3", "2024-01-03", false),
+        ];
+        for (expression, date, expected) in cases {
+            let schedule: CronSchedule = expression.parse().unwrap();
+            assert_eq!(
+                schedule.matches_date(date.parse().unwrap()),
+                expected,
+                "{} on {}",
+                expression,
+                date
+            );
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::sources::run_if_installed;
+
+pub const ID: &str = "snap";
+pub const NAME: &str = "Snaps";
+
+/// Returns snaps with a pending refresh, formatted like checkupdates lines
+/// (`name old -> new`).
+pub fn check() -> Result<Vec<String>> {
+    let Some(refreshes) = run_if_installed("snap", &["refresh", "--list"])? else {
+        return Ok(vec![]);
+    };
+    // Without pending refreshes only "All snaps up to date." on stderr.
+    if refreshes.lines().nth(1).is_none() {
+        return Ok(vec![]);
+    }
+
+    // `snap refresh --list` only shows the new version.
+    let installed: HashMap<String, String> = run_if_installed("snap", &["list"])?
+        .unwrap_or_default()
+        .lines()
+        .skip(1)
+        .filter_map(name_and_version)
+        .collect();
+
+    Ok(refreshes
+        .lines()
+        .skip(1)
+        .filter_map(name_and_version)
+        .map(|(name, new)| match installed.get(&name) {
+            Some(old) => format!("{} {} -> {}", name, old, new),
+            None => format!("{} -> {}", name, new),
+        })
+        .collect())
+}
+
+/// Reads the first two columns of the `Name  Version  Rev ...` tables.
+fn name_and_version(line: &str) -> Option<(String, String)> {
+    let mut columns = line.split_whitespace();
+    Some((columns.next()?.to_string(), columns.next()?.to_string()))
+}
@@ -0,0 +1,806 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, RecvTimeoutError, Sender, channel},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use image::RgbaImage;
+use serde::Serialize;
+use tracing::{error, info};
+use zbus::{
+    blocking::{Connection, Proxy, connection::Builder},
+    fdo, interface,
+    zvariant::{OwnedObjectPath, OwnedValue, StructureBuilder, Type, Value},
+};
+
+use crate::{
+    Event,
+    appearance::{ColorScheme, IconTheme},
+    bus::StateUpdate,
+    changelog,
+    config::Config,
+    i18n::t,
+    maintenance::Action,
+    privileges, sources, supervisor,
+    tray::{
+        ACKNOWLEDGE_ALL_MENU_ID, DEBUG_SNAPSHOT_MENU_ID, DOWNLOAD_UPDATES_MENU_ID,
+        INSTALL_UPDATES_MENU_ID, IconConfig, IconKind, MARK_NEWS_READ_MENU_ID, MenuLabel,
+        MenuSection, PAUSE_MENU_ID, SHOW_UPDATES_MENU_ID, TraySink, TrayState, UPDATE_NOW_MENU_ID,
+        local_prefix, render_icon, retry_until_shown, run_menu_entry, save_download_updates,
+        snapshot_header, write_diagnostics_snapshot,
+    },
+};
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const ITEM_INTERFACE: &str = "org.kde.StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+const MENU_INTERFACE: &str = "com.canonical.dbusmenu";
+
+/// How often menu clicks and color scheme changes are looked at while
+/// waiting for state updates.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+const WATCHER_NAME: &str = "org.kde.StatusNotifierWatcher";
+
+/// How often to check whether the StatusNotifierWatcher was replaced.
+const WATCHER_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// An image in the StatusNotifierItem format: width, height and ARGB32
+/// pixels in network byte order.
+type Pixmap = (i32, i32, Vec<u8>);
+
+/// A menu entry as dbusmenu shows it.
+#[derive(Debug, Clone)]
+struct Entry {
+    /// The menu ID of the `tray` module a click runs.
+    id: Option<String>,
+    label: String,
+    enabled: bool,
+    /// Whether a checkbox entry is checked.
+    checked: Option<bool>,
+    submenu: Option<Vec<Entry>>,
+}
+
+impl Entry {
+    fn item(id: &'static str, label: String) -> Self {
+        Self {
+            id: Some(id.to_string()),
+            label,
+            enabled: true,
+            checked: None,
+            submenu: None,
+        }
+    }
+
+    fn text(label: String, enabled: bool) -> Self {
+        Self {
+            id: None,
+            label,
+            enabled,
+            checked: None,
+            submenu: None,
+        }
+    }
+
+    fn submenu(label: String, enabled: bool) -> Self {
+        Self {
+            submenu: Some(Vec::new()),
+            ..Self::text(label, enabled)
+        }
+    }
+
+    fn properties(&self) -> HashMap<String, OwnedValue> {
+        let mut properties = HashMap::new();
+        // Underscores mark access keys otherwise.
+        properties.insert(
+            "label".to_string(),
+            OwnedValue::from(zbus::zvariant::Str::from(self.label.replace('_', "__"))),
+        );
+        if !self.enabled {
+            properties.insert("enabled".to_string(), OwnedValue::from(false));
+        }
+        if let Some(checked) = self.checked {
+            properties.insert(
+                "toggle-type".to_string(),
+                OwnedValue::from(zbus::zvariant::Str::from_static("checkmark")),
+            );
+            properties.insert(
+                "toggle-state".to_string(),
+                OwnedValue::from(i32::from(checked)),
+            );
+        }
+        if self.submenu.is_some() {
+            properties.insert(
+                "children-display".to_string(),
+                OwnedValue::from(zbus::zvariant::Str::from_static("submenu")),
+            );
+        }
+        properties
+    }
+
+    fn children(&self) -> &[Entry] {
+        self.submenu.as_deref().unwrap_or_default()
+    }
+}
+
+/// The menu, in the same order as the GTK one.
+#[derive(Debug, Clone)]
+struct Menu {
+    local: Entry,
+    hosts: Vec<Entry>,
+    sources: Vec<Entry>,
+    ignored: Option<Entry>,
+    rebuild: Option<Entry>,
+    advisories: Vec<Entry>,
+    /// Only with GTK 4, a GTK 3 window can't be shown from here.
+    show_updates: Option<Entry>,
+    update_now: Entry,
+    install_updates: Option<Entry>,
+    mark_news_read: Option<Entry>,
+    acknowledge_all: Entry,
+    download_updates: Entry,
+    pause: Entry,
+    last_checked: Entry,
+    last_upgraded: Entry,
+    oldest_pending: Entry,
+    statistics: Entry,
+    maintenance: Entry,
+    debug: Option<Entry>,
+}
+
+impl Menu {
+    fn new(config: &Config) -> Self {
+        let mut maintenance = Entry::submenu(t!("maintenance"), true);
+        maintenance.submenu = Some(
+            Action::ALL
+                .into_iter()
+                .map(|action| Entry::item(action.id(), privileges::action_label(&action.label())))
+                .collect(),
+        );
+
+        Self {
+            local: Entry::submenu(
+                t!(
+                    "local-pending-updates",
+                    prefix = local_prefix(config),
+                    count = 0
+                ),
+                true,
+            ),
+            hosts: config
+                .remote_hosts
+                .iter()
+                .map(|host| Entry::submenu(t!("section-not-checked", name = host.name()), true))
+                .collect(),
+            sources: sources::enabled(&config.sources)
+                .into_iter()
+                .map(|name| Entry::submenu(t!("section-not-checked", name = name), true))
+                .collect(),
+            ignored: config
+                .show_ignored_updates
+                .then(|| Entry::submenu(t!("ignored-updates", count = 0), true)),
+            rebuild: config
+                .rebuild
+                .enabled
+                .then(|| Entry::submenu(t!("needs-rebuild-not-checked"), false)),
+            advisories: Vec::new(),
+            show_updates: cfg!(feature = "gtk4")
+                .then(|| Entry::item(SHOW_UPDATES_MENU_ID, t!("show-updates"))),
+            update_now: Entry::item(
+                UPDATE_NOW_MENU_ID,
+                privileges::action_label(&t!("update-now")),
+            ),
+            install_updates: config
+                .install
+                .enabled
+                .then(|| Entry::item(INSTALL_UPDATES_MENU_ID, t!("install-updates"))),
+            mark_news_read: config
+                .news
+                .enabled
+                .then(|| Entry::item(MARK_NEWS_READ_MENU_ID, t!("mark-news-read"))),
+            acknowledge_all: Entry::item(ACKNOWLEDGE_ALL_MENU_ID, t!("acknowledge-all")),
+            download_updates: Entry {
+                checked: Some(config.download_updates),
+                ..Entry::item(DOWNLOAD_UPDATES_MENU_ID, t!("download-in-background"))
+            },
+            pause: Entry::item(PAUSE_MENU_ID, t!("pause-checks")),
+            last_checked: Entry::text(t!("last-checked-never"), false),
+            last_upgraded: Entry::text(t!("last-upgraded-unknown"), false),
+            oldest_pending: Entry::text(t!("oldest-pending-none"), false),
+            statistics: Entry::submenu(t!("statistics"), true),
+            maintenance,
+            debug: config.debug_menu.then(|| {
+                let mut debug = Entry::submenu(t!("debug"), true);
+                debug.submenu = Some(vec![Entry::item(
+                    DEBUG_SNAPSHOT_MENU_ID,
+                    t!("save-menu-snapshot"),
+                )]);
+                debug
+            }),
+        }
+    }
+
+    fn entries(&self) -> Vec<Entry> {
+        let mut entries = vec![self.local.clone()];
+        entries.extend(self.hosts.iter().cloned());
+        entries.extend(self.sources.iter().cloned());
+        entries.extend(self.ignored.clone());
+        entries.extend(self.rebuild.clone());
+        entries.extend(self.advisories.iter().cloned());
+        entries.extend(self.show_updates.clone());
+        entries.push(self.update_now.clone());
+        entries.extend(self.install_updates.clone());
+        entries.extend(self.mark_news_read.clone());
+        entries.extend([
+            self.acknowledge_all.clone(),
+            self.download_updates.clone(),
+            self.pause.clone(),
+            self.last_checked.clone(),
+            self.last_upgraded.clone(),
+            self.oldest_pending.clone(),
+            self.statistics.clone(),
+            self.maintenance.clone(),
+        ]);
+        entries.extend(self.debug.clone());
+        entries
+    }
+
+    fn section_mut(&mut self, section: MenuSection) -> Option<&mut Entry> {
+        match section {
+            MenuSection::Local => Some(&mut self.local),
+            MenuSection::Host(index) => self.hosts.get_mut(index),
+            MenuSection::Source(index) => self.sources.get_mut(index),
+            MenuSection::Ignored => self.ignored.as_mut(),
+            MenuSection::Rebuild => self.rebuild.as_mut(),
+            MenuSection::Statistics => Some(&mut self.statistics),
+        }
+    }
+}
+
+/// Numbers the entries depth first, starting at 1. The root is 0.
+fn numbered(entries: &[Entry]) -> Vec<&Entry> {
+    let mut numbered = Vec::new();
+    for entry in entries {
+        numbered.push(entry);
+        numbered.extend(self::numbered(entry.children()));
+    }
+    numbered
+}
+
+/// A dbusmenu layout node: ID, properties and the child nodes.
+#[derive(Debug, Serialize, Type)]
+struct Layout(i32, HashMap<String, OwnedValue>, Vec<OwnedValue>);
+
+impl Layout {
+    /// The layout of the entry with `id`, whose descendants are numbered
+    /// from `id + 1` on.
+    fn new(id: i32, properties: HashMap<String, OwnedValue>, children: &[Entry]) -> Result<Self> {
+        let mut next_id = id + 1;
+        let mut child_layouts = Vec::new();
+        for child in children {
+            let child_id = next_id;
+            next_id += 1 + self::numbered(child.children()).len() as i32;
+            let layout = Layout::new(child_id, child.properties(), child.children())?;
+            let structure = StructureBuilder::new()
+                .add_field(layout.0)
+                .add_field(layout.1)
+                .add_field(layout.2)
+                .build()?;
+            child_layouts.push(OwnedValue::try_from(Value::from(structure))?);
+        }
+
+        Ok(Layout(id, properties, child_layouts))
+    }
+}
+
+/// What the D-Bus interfaces show, updated by the tray loop.
+struct Model {
+    icon: Vec<Pixmap>,
+    tooltip: String,
+    menu: Menu,
+    /// Changes with the menu, so hosts know to fetch it again.
+    revision: u32,
+}
+
+/// The `org.kde.StatusNotifierItem` interface.
+struct Item {
+    model: Arc<Mutex<Model>>,
+}
+
+#[interface(name = "org.kde.StatusNotifierItem")]
+impl Item {
+    #[zbus(property)]
+    fn category(&self) -> String {
+        "SystemServices".to_string()
+    }
+
+    #[zbus(property)]
+    fn id(&self) -> String {
+        env!("CARGO_PKG_NAME").to_string()
+    }
+
+    #[zbus(property)]
+    fn title(&self) -> String {
+        env!("CARGO_PKG_NAME").to_string()
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> String {
+        "Active".to_string()
+    }
+
+    /// Empty, so that hosts use `IconPixmap`.
+    #[zbus(property)]
+    fn icon_name(&self) -> String {
+        String::new()
+    }
+
+    #[zbus(property)]
+    fn icon_pixmap(&self) -> Vec<Pixmap> {
+        self.model.lock().unwrap().icon.clone()
+    }
+
+    /// Icon name, icon, title and description.
+    #[zbus(property)]
+    fn tool_tip(&self) -> (String, Vec<Pixmap>, String, String) {
+        let tooltip = self.model.lock().unwrap().tooltip.clone();
+        let (title, description) = tooltip.split_once('\n').unwrap_or((&tooltip, ""));
+        (
+            String::new(),
+            Vec::new(),
+            title.to_string(),
+            description.to_string(),
+        )
+    }
+
+    /// Clicks open the menu, like with the GTK backend.
+    #[zbus(property)]
+    fn item_is_menu(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn menu(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(MENU_PATH).unwrap()
+    }
+
+    fn activate(&self, _x: i32, _y: i32) {}
+
+    fn secondary_activate(&self, _x: i32, _y: i32) {}
+
+    fn context_menu(&self, _x: i32, _y: i32) {}
+
+    fn scroll(&self, _delta: i32, _orientation: String) {}
+}
+
+/// The `com.canonical.dbusmenu` interface the host renders the menu from.
+struct DbusMenu {
+    model: Arc<Mutex<Model>>,
+    /// The menu IDs of clicked entries, for the tray loop.
+    clicks: Sender<String>,
+}
+
+#[interface(name = "com.canonical.dbusmenu")]
+impl DbusMenu {
+    fn get_layout(
+        &self,
+        parent_id: i32,
+        _recursion_depth: i32,
+        _property_names: Vec<String>,
+    ) -> fdo::Result<(u32, Layout)> {
+        let model = self.model.lock().unwrap();
+        let entries = model.menu.entries();
+        let layout = if parent_id == 0 {
+            let mut properties = HashMap::new();
+            properties.insert(
+                "children-display".to_string(),
+                OwnedValue::from(zbus::zvariant::Str::from_static("submenu")),
+            );
+            Layout::new(0, properties, &entries)
+        } else {
+            let Some(parent) = numbered(&entries).get(parent_id as usize - 1).copied() else {
+                return Err(fdo::Error::InvalidArgs(format!(
+                    "No menu entry {}",
+                    parent_id
+                )));
+            };
+            Layout::new(parent_id, parent.properties(), parent.children())
+        };
+
+        layout
+            .map(|layout| (model.revision, layout))
+            .map_err(|e| fdo::Error::Failed(format!("{:#}", e)))
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        _property_names: Vec<String>,
+    ) -> Vec<(i32, HashMap<String, OwnedValue>)> {
+        let entries = self.model.lock().unwrap().menu.entries();
+        let numbered = numbered(&entries);
+        ids.into_iter()
+            .filter_map(|id| {
+                let entry = numbered.get((id as usize).checked_sub(1)?)?;
+                Some((id, entry.properties()))
+            })
+            .collect()
+    }
+
+    fn get_property(&self, id: i32, name: String) -> fdo::Result<OwnedValue> {
+        let entries = self.model.lock().unwrap().menu.entries();
+        numbered(&entries)
+            .get((id as usize).wrapping_sub(1))
+            .and_then(|entry| entry.properties().remove(&name))
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("No property {} of {}", name, id)))
+    }
+
+    fn event(&self, id: i32, event_id: String, _data: OwnedValue, _timestamp: u32) {
+        if event_id != "clicked" {
+            return;
+        }
+
+        let entries = self.model.lock().unwrap().menu.entries();
+        if let Some(menu_id) = numbered(&entries)
+            .get((id as usize).wrapping_sub(1))
+            .and_then(|entry| entry.id.clone())
+        {
+            let _ = self.clicks.send(menu_id);
+        }
+    }
+
+    fn event_group(&self, events: Vec<(i32, String, OwnedValue, u32)>) -> Vec<i32> {
+        for (id, event_id, data, timestamp) in events {
+            self.event(id, event_id, data, timestamp);
+        }
+        Vec::new()
+    }
+
+    /// The menu is always up to date, so it never needs an update first.
+    fn about_to_show(&self, _id: i32) -> bool {
+        false
+    }
+
+    fn about_to_show_group(&self, _ids: Vec<i32>) -> (Vec<i32>, Vec<i32>) {
+        (Vec::new(), Vec::new())
+    }
+
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[zbus(property)]
+    fn text_direction(&self) -> String {
+        "ltr".to_string()
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> String {
+        "normal".to_string()
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The tray icon as a StatusNotifierItem, registered with the panel's
+/// StatusNotifierWatcher.
+struct SniTray {
+    connection: Connection,
+    /// The bus name the item is served at.
+    name: String,
+    /// The unique name of the StatusNotifierWatcher the item is registered
+    /// with.
+    watcher_owner: Option<String>,
+    last_watcher_check: Instant,
+    model: Arc<Mutex<Model>>,
+    icon_config: IconConfig,
+    color_scheme: ColorScheme,
+    current_icon: IconKind,
+}
+
+impl SniTray {
+    /// Serves the item and its menu on the session bus. It only shows up
+    /// once `register`ed.
+    fn connect(config: &Config, clicks: Sender<String>) -> Result<Self> {
+        let model = Arc::new(Mutex::new(Model {
+            icon: Vec::new(),
+            tooltip: String::new(),
+            menu: Menu::new(config),
+            revision: 1,
+        }));
+
+        let name = format!("org.kde.StatusNotifierItem-{}-1", std::process::id());
+        let connection = Builder::session()
+            .and_then(|builder| builder.name(name.as_str()))
+            .and_then(|builder| {
+                builder.serve_at(
+                    ITEM_PATH,
+                    Item {
+                        model: model.clone(),
+                    },
+                )
+            })
+            .and_then(|builder| {
+                builder.serve_at(
+                    MENU_PATH,
+                    DbusMenu {
+                        model: model.clone(),
+                        clicks,
+                    },
+                )
+            })
+            .and_then(|builder| builder.build())
+            .context("Failed to connect to the session bus")?;
+
+        let color_scheme = match config.icon.theme {
+            IconTheme::Light => ColorScheme::Light,
+            IconTheme::Dark | IconTheme::Auto => ColorScheme::Dark,
+        };
+
+        Ok(Self {
+            connection,
+            name,
+            watcher_owner: None,
+            last_watcher_check: Instant::now(),
+            model,
+            icon_config: config.icon.clone(),
+            color_scheme,
+            current_icon: IconKind::NoUpdates,
+        })
+    }
+
+    /// Registers the item with the StatusNotifierWatcher, which the panel or
+    /// bar provides.
+    fn register(&mut self) -> Result<()> {
+        let watcher = Proxy::new(
+            &self.connection,
+            WATCHER_NAME,
+            "/StatusNotifierWatcher",
+            WATCHER_NAME,
+        )
+        .context("Failed to create StatusNotifierWatcher proxy")?;
+        watcher
+            .call::<_, _, ()>("RegisterStatusNotifierItem", &(self.name.as_str(),))
+            .context("Failed to register with the StatusNotifierWatcher. Is a panel or bar with a tray running?")?;
+
+        self.watcher_owner = self.current_watcher_owner();
+        Ok(())
+    }
+
+    /// The unique name of the StatusNotifierWatcher, `None` while there is
+    /// none.
+    fn current_watcher_owner(&self) -> Option<String> {
+        let dbus = Proxy::new(
+            &self.connection,
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+        )
+        .ok()?;
+        dbus.call("GetNameOwner", &(WATCHER_NAME,)).ok()
+    }
+
+    /// Registers again when another StatusNotifierWatcher took over, e.g.
+    /// because the bar that provides it was restarted. The new one doesn't
+    /// know the item, so the icon would stay hidden.
+    fn follow_watcher(&mut self) -> Result<()> {
+        if self.last_watcher_check.elapsed() < WATCHER_CHECK_INTERVAL {
+            return Ok(());
+        }
+        self.last_watcher_check = Instant::now();
+
+        let owner = self.current_watcher_owner();
+        if owner.is_none() || owner == self.watcher_owner {
+            return Ok(());
+        }
+        info!("The StatusNotifierWatcher changed, registering the tray icon again");
+        self.register()
+    }
+
+    /// Renders the current icon again for the new color scheme.
+    fn set_color_scheme(&mut self, color_scheme: ColorScheme) -> Result<()> {
+        self.color_scheme = color_scheme;
+        self.set_icon(self.current_icon)
+    }
+
+    fn emit(&self, path: &str, interface: &str, signal: &str) -> Result<()> {
+        self.connection
+            .emit_signal(None::<&str>, path, interface, signal, &())
+            .with_context(|| format!("Failed to emit {}", signal))
+    }
+
+    /// Changes the menu with `change` and tells the host.
+    fn change_menu(&mut self, change: impl FnOnce(&mut Menu)) -> Result<()> {
+        let revision = {
+            let mut model = self.model.lock().unwrap();
+            change(&mut model.menu);
+            model.revision += 1;
+            model.revision
+        };
+        self.connection
+            .emit_signal(
+                None::<&str>,
+                MENU_PATH,
+                MENU_INTERFACE,
+                "LayoutUpdated",
+                &(revision, 0i32),
+            )
+            .context("Failed to emit LayoutUpdated")
+    }
+
+    fn snapshot(&self) -> String {
+        let mut snapshot = String::new();
+        for entry in self.model.lock().unwrap().menu.entries() {
+            snapshot.push_str(&format!("  {}\n", entry.label));
+            for child in entry.children() {
+                snapshot.push_str(&format!("    {}\n", child.label));
+            }
+        }
+        snapshot
+    }
+}
+
+impl TraySink for SniTray {
+    fn set_icon(&mut self, icon: IconKind) -> Result<()> {
+        let image = render_icon(icon, &self.icon_config, self.color_scheme)?;
+        self.model.lock().unwrap().icon = vec![pixmap(&image)];
+        self.current_icon = icon;
+        self.emit(ITEM_PATH, ITEM_INTERFACE, "NewIcon")
+    }
+
+    fn set_tooltip(&mut self, tooltip: &str) -> Result<()> {
+        self.model.lock().unwrap().tooltip = tooltip.to_string();
+        self.emit(ITEM_PATH, ITEM_INTERFACE, "NewToolTip")
+    }
+
+    fn set_submenu(
+        &mut self,
+        section: MenuSection,
+        title: &str,
+        lines: &[String],
+        enabled: bool,
+    ) -> Result<()> {
+        self.change_menu(|menu| {
+            // Hosts, sources and checkrebuild only get a submenu at startup.
+            if let Some(entry) = menu.section_mut(section) {
+                entry.label = title.to_string();
+                entry.submenu = Some(
+                    lines
+                        .iter()
+                        .map(|line| Entry {
+                            // Clicking one of this machine's updates shows its
+                            // changelog.
+                            id: changelog::menu_id(line).filter(|_| section == MenuSection::Local),
+                            ..Entry::text(line.clone(), enabled)
+                        })
+                        .collect(),
+                );
+            }
+        })
+    }
+
+    fn set_label(&mut self, label: MenuLabel, text: &str) -> Result<()> {
+        self.change_menu(|menu| {
+            let entry = match label {
+                MenuLabel::UpdateNow => {
+                    menu.update_now.label = privileges::action_label(text);
+                    return;
+                }
+                MenuLabel::MarkNewsRead => match &mut menu.mark_news_read {
+                    Some(entry) => entry,
+                    None => return,
+                },
+                MenuLabel::LastChecked => &mut menu.last_checked,
+                MenuLabel::LastUpgraded => &mut menu.last_upgraded,
+                MenuLabel::OldestPending => &mut menu.oldest_pending,
+                MenuLabel::Pause => &mut menu.pause,
+            };
+            entry.label = text.to_string();
+        })
+    }
+
+    fn set_advisories(&mut self, messages: &[String]) -> Result<()> {
+        self.change_menu(|menu| {
+            menu.advisories = messages
+                .iter()
+                .map(|message| Entry::text(message.clone(), true))
+                .collect();
+        })
+    }
+}
+
+/// Converts RGBA pixels to the ARGB32 pixmap hosts expect.
+fn pixmap(image: &RgbaImage) -> Pixmap {
+    let data = image
+        .pixels()
+        .flat_map(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            [a, r, g, b]
+        })
+        .collect();
+    (image.width() as i32, image.height() as i32, data)
+}
+
+/// Shows the tray icon as a StatusNotifierItem until the app shuts down, or
+/// until the tray fails and has to be restarted. Needs no GTK.
+pub fn run(
+    mut config: Config,
+    app_tx: Sender<Event>,
+    updates: Arc<Mutex<Receiver<StateUpdate>>>,
+    color_schemes: Arc<Mutex<Receiver<ColorScheme>>>,
+) {
+    let (clicks_tx, clicks) = channel();
+    let Some(mut tray) = retry_until_shown(|| {
+        let mut tray = SniTray::connect(&config, clicks_tx.clone())?;
+        tray.register()?;
+        Ok(tray)
+    }) else {
+        return;
+    };
+    info!("Showing the tray icon as a StatusNotifierItem");
+
+    if let Err(e) = tray.set_icon(IconKind::NoUpdates) {
+        error!("{:#}", e);
+        return;
+    }
+
+    let mut state = TrayState::new(&config);
+    loop {
+        while let Ok(id) = clicks.try_recv() {
+            if id == DOWNLOAD_UPDATES_MENU_ID {
+                let enabled = !config.download_updates;
+                let result = tray.change_menu(|menu| menu.download_updates.checked = Some(enabled));
+                if save_download_updates(&mut config, enabled)
+                    && let Err(e) = result
+                {
+                    error!("{:#}", e);
+                }
+            } else if id == DEBUG_SNAPSHOT_MENU_ID {
+                let snapshot = snapshot_header(&state.status, &config) + &tray.snapshot();
+                match write_diagnostics_snapshot(&snapshot) {
+                    Ok(path) => info!("Saved menu snapshot to {:?}", path),
+                    Err(e) => error!("Failed to save menu snapshot: {}", e),
+                }
+            } else {
+                run_menu_entry(&id, &mut config, &state.status, &app_tx);
+            }
+        }
+
+        while let Ok(color_scheme) = color_schemes.lock().unwrap().try_recv() {
+            if let Err(e) = tray.set_color_scheme(color_scheme) {
+                error!("Failed to update tray: {:#}", e);
+                return;
+            }
+        }
+
+        let update = supervisor::recv_timeout(&updates.lock().unwrap(), POLL_INTERVAL);
+        match update {
+            Ok(update) => match state.apply(&mut tray, update, &mut config) {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(e) => {
+                    // Ends this run of the tray thread, so that it is
+                    // restarted.
+                    error!("Failed to update tray: {:#}", e);
+                    return;
+                }
+            },
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        if let Err(e) = tray.follow_watcher() {
+            error!("Failed to update tray: {:#}", e);
+            return;
+        }
+        state.refresh_elapsed(&mut tray, &config);
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread,
+};
+
+use chrono::{Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+/// The sound of the freedesktop sound theme played without a `file`.
+const DEFAULT_SOUND_ID: &str = "message-new-instant";
+
+/// A sound when a check finds new updates or the critical threshold is
+/// crossed.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SoundConfig {
+    pub enabled: bool,
+    /// A sound file to play instead of the sound theme's.
+    pub file: Option<PathBuf>,
+    /// No sound is played from `quiet_start` until `quiet_end`, which may be
+    /// on the next day.
+    pub quiet_start: Option<NaiveTime>,
+    pub quiet_end: Option<NaiveTime>,
+}
+
+impl SoundConfig {
+    fn is_quiet(&self, now: NaiveTime) -> bool {
+        match (self.quiet_start, self.quiet_end) {
+            (Some(start), Some(end)) if start <= end => start <= now && now < end,
+            // Quiet over midnight.
+            (Some(start), Some(end)) => start <= now || now < end,
+            _ => false,
+        }
+    }
+}
+
+/// Plays the sound with libcanberra's `canberra-gtk-play`, or the `file` with
+/// `pw-play` or `paplay` when that isn't installed, unless it's quiet hours.
+pub fn play(config: &SoundConfig) {
+    if !config.enabled || config.is_quiet(Local::now().time()) {
+        return;
+    }
+
+    let mut canberra = Command::new("canberra-gtk-play");
+    match &config.file {
+        Some(file) => canberra.arg("--file").arg(file),
+        None => canberra.args(["--id", DEFAULT_SOUND_ID]),
+    };
+
+    let mut commands = vec![canberra];
+    if let Some(file) = &config.file {
+        for player in ["pw-play", "paplay"] {
+            let mut command = Command::new(player);
+            command.arg(file);
+            commands.push(command);
+        }
+    }
+
+    for mut command in commands {
+        let program = command.get_program().to_string_lossy().into_owned();
+        match command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(mut child) => {
+                info!("Playing the update sound with {}", program);
+                thread::spawn(move || child.wait());
+                return;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                error!("Failed to run {}: {}", program, e);
+                return;
+            }
+        }
+    }
+
+    error!("Failed to play the update sound: canberra-gtk-play is not installed");
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    process::{Command, Stdio},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{aur, developer_tools, metrics, python_tools, snap};
+
+/// Optional update sources besides pacman, shown in their own menu sections.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SourcesConfig {
+    /// Rust toolchains (`rustup check`) and binaries installed with
+    /// `cargo install` (`cargo install-update -l`).
+    pub developer_tools: bool,
+    /// Python tools installed with pipx or `pip install --user`.
+    pub python_tools: bool,
+    /// Querying PyPI for every tool is slow, so it runs less often than the
+    /// regular checks.
+    pub python_tools_interval_in_seconds: u64,
+    /// Snap packages with a pending `snap refresh`.
+    pub snap: bool,
+    /// Foreign packages with a newer version in the AUR, or flagged
+    /// out-of-date or orphaned there.
+    pub aur: bool,
+}
+
+impl Default for SourcesConfig {
+    fn default() -> Self {
+        Self {
+            developer_tools: false,
+            python_tools: false,
+            python_tools_interval_in_seconds: 60 * 60 * 24,
+            snap: false,
+            aur: false,
+        }
+    }
+}
+
+/// The result of checking one additional source.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceStatus {
+    /// Key of the source in the config, e.g. `python_tools`.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub updates: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Names of the enabled sources, in the order `Sources::check_all` returns them.
+pub fn enabled(config: &SourcesConfig) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    if config.developer_tools {
+        names.push(developer_tools::NAME);
+    }
+    if config.python_tools {
+        names.push(python_tools::NAME);
+    }
+    if config.snap {
+        names.push(snap::NAME);
+    }
+    if config.aur {
+        names.push(aur::NAME);
+    }
+    names
+}
+
+/// Checks the enabled sources, reusing the last result of sources whose own
+/// interval hasn't passed yet. Shared between the main loop and the checker
+/// thread, the cache is only locked to look results up and store them.
+#[derive(Default)]
+pub struct Sources {
+    cache: Mutex<HashMap<&'static str, (Instant, SourceStatus)>>,
+}
+
+impl Sources {
+    pub fn check_all(&self, config: &SourcesConfig) -> Vec<SourceStatus> {
+        let mut statuses = Vec::new();
+        if config.developer_tools {
+            statuses.push(source_status(
+                developer_tools::ID,
+                developer_tools::NAME,
+                developer_tools::check(),
+            ));
+        }
+        if config.python_tools {
+            statuses.push(self.check_cached(
+                python_tools::ID,
+                python_tools::NAME,
+                Duration::from_secs(config.python_tools_interval_in_seconds),
+                python_tools::check,
+            ));
+        }
+        if config.snap {
+            statuses.push(source_status(snap::ID, snap::NAME, snap::check()));
+        }
+        if config.aur {
+            statuses.push(source_status(aur::ID, aur::NAME, aur::check()));
+        }
+        statuses
+    }
+
+    /// Drops cached results that can't be reused anymore: of sources that
+    /// were disabled, or whose interval has passed.
+    pub fn expire_cache(&self, config: &SourcesConfig) {
+        let enabled = enabled(config);
+        let interval = Duration::from_secs(config.python_tools_interval_in_seconds);
+        self.cache.lock().unwrap().retain(|name, (checked_at, _)| {
+            enabled.contains(name) && checked_at.elapsed() < interval
+        });
+    }
+
+    fn check_cached(
+        &self,
+        id: &'static str,
+        name: &'static str,
+        interval: Duration,
+        check: fn() -> Result<Vec<String>>,
+    ) -> SourceStatus {
+        if let Some((checked_at, status)) = self.cache.lock().unwrap().get(name)
+            && checked_at.elapsed() < interval
+        {
+            return status.clone();
+        }
+
+        let status = source_status(id, name, check());
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(name, (Instant::now(), status.clone()));
+        status
+    }
+}
+
+fn source_status(
+    id: &'static str,
+    name: &'static str,
+    result: Result<Vec<String>>,
+) -> SourceStatus {
+    match result {
+        Ok(updates) => {
+            info!("{} {} updates available", updates.len(), name);
+            SourceStatus {
+                id,
+                name,
+                updates,
+                error: None,
+            }
+        }
+        Err(e) => {
+            error!("Failed to check {} for updates: {}", name, e);
+            metrics::increment(&metrics::COUNTERS.check_failures);
+            SourceStatus {
+                id,
+                name,
+                updates: vec![],
+                error: Some(e.to_string()),
+            }
+        }
+    }
+}
+
+/// Runs a command and returns its stdout, or `None` if the program or, for
+/// cargo, the plugin is not installed.
+pub fn run_if_installed(program: &str, args: &[&str]) -> Result<Option<String>> {
+    let output = match Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => bail!("Failed to run {}: {}", program, e),
+    };
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // `cargo install-update` is a plugin, cargo reports it as an unknown command.
+        if stderr.contains("no such command") {
+            return Ok(None);
+        }
+        bail!("{} {} failed: {}", program, args.join(" "), stderr.trim());
+    }
+
+    Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+}
@@ -0,0 +1,50 @@
+use std::{process::Command, thread, time::Duration};
+
+use anyhow::Result;
+
+use crate::runtime_dir;
+
+/// Upper bound of the random delay before each host is contacted, so several
+/// machines checking the same hosts don't all connect at once.
+const MAX_JITTER: Duration = Duration::from_secs(3);
+
+/// How long the shared SSH connection outlives a check beyond the interval.
+const CONTROL_PERSIST_MARGIN_SECS: u64 = 60;
+
+pub fn default_connect_timeout_secs() -> u32 {
+    10
+}
+
+/// Sleeps for a random moment of up to `MAX_JITTER`, uniformly distributed
+/// so the connections are spread evenly over that time.
+pub fn wait_jitter() {
+    let jitter = fastrand::u64(..MAX_JITTER.as_millis() as u64);
+    thread::sleep(Duration::from_millis(jitter));
+}
+
+/// An `ssh <destination>` command that keeps one master connection per host
+/// open between checks every `interval_secs`, instead of doing a full
+/// handshake every time. BatchMode makes ssh fail instead of prompting, so
+/// only agent or unencrypted key authentication works.
+pub fn command(
+    destination: &str,
+    connect_timeout_secs: u32,
+    interval_secs: u64,
+) -> Result<Command> {
+    let control_path = runtime_dir()?.join("ssh-%C");
+    let mut command = Command::new("ssh");
+    command
+        .args(["-o", "BatchMode=yes"])
+        .args(["-o", &format!("ConnectTimeout={}", connect_timeout_secs)])
+        .args(["-o", "ControlMaster=auto"])
+        .args(["-o", &format!("ControlPath={}", control_path.display())])
+        .args([
+            "-o",
+            &format!(
+                "ControlPersist={}",
+                interval_secs + CONTROL_PERSIST_MARGIN_SECS
+            ),
+        ])
+        .arg(destination);
+    Ok(command)
+}
@@ -0,0 +1,191 @@
+use anyhow::{Result, bail};
+use serde::Serialize;
+
+/// What the app is doing, or why it isn't checking. The icon, the tooltip,
+/// the menu and the outputs are all derived from it. It only changes through
+/// `next`, which knows which transitions are valid.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum AppState {
+    /// Up to date.
+    #[default]
+    Idle,
+    Checking,
+    UpdatesAvailable {
+        /// Already in the status as `count`.
+        #[serde(skip)]
+        count: usize,
+    },
+    /// A pacman transaction is running.
+    Upgrading,
+    /// The last check failed.
+    Error {
+        error: String,
+    },
+    /// The user paused checks.
+    Paused,
+    /// The last check failed because there is no network.
+    Offline,
+}
+
+/// Something that happened to the app, which may change its state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transition {
+    CheckStarted,
+    CheckFinished {
+        updates: usize,
+    },
+    CheckFailed {
+        error: String,
+    },
+    /// A check failed while no network interface was up.
+    WentOffline,
+    UpgradeStarted,
+    /// An upgrade ended without a check to follow, e.g. because it failed.
+    UpgradeEnded {
+        updates: usize,
+    },
+    Pause,
+    Resume {
+        updates: usize,
+    },
+}
+
+impl AppState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AppState::Idle => "idle",
+            AppState::Checking => "checking",
+            AppState::UpdatesAvailable { .. } => "updates_available",
+            AppState::Upgrading => "upgrading",
+            AppState::Error { .. } => "error",
+            AppState::Paused => "paused",
+            AppState::Offline => "offline",
+        }
+    }
+
+    /// The state to rest in with `updates` pending.
+    fn settled(updates: usize) -> Self {
+        if updates == 0 {
+            AppState::Idle
+        } else {
+            AppState::UpdatesAvailable { count: updates }
+        }
+    }
+
+    /// The state after `transition`, or why it can't happen in this state.
+    pub fn next(&self, transition: Transition) -> Result<AppState> {
+        let next = match (self, transition) {
+            (AppState::Paused, Transition::CheckStarted | Transition::UpgradeStarted) => {
+                bail!("checks are paused")
+            }
+            (AppState::Checking, Transition::CheckStarted) => bail!("a check is already running"),
+            (_, Transition::CheckStarted) => AppState::Checking,
+            (AppState::Checking, Transition::CheckFinished { updates }) => {
+                AppState::settled(updates)
+            }
+            (AppState::Checking, Transition::CheckFailed { error }) => AppState::Error { error },
+            (AppState::Checking, Transition::WentOffline) => AppState::Offline,
+            (_, Transition::UpgradeStarted) => AppState::Upgrading,
+            (AppState::Upgrading, Transition::UpgradeEnded { updates }) => {
+                AppState::settled(updates)
+            }
+            (AppState::Checking | AppState::Upgrading, Transition::Pause) => {
+                bail!("it is {}", self.as_str())
+            }
+            (AppState::Paused, Transition::Pause) => bail!("checks are already paused"),
+            (_, Transition::Pause) => AppState::Paused,
+            (AppState::Paused, Transition::Resume { updates }) => AppState::settled(updates),
+            (_, transition) => bail!("{:?} can't happen while {}", transition, self.as_str()),
+        };
+
+        Ok(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transitions() {
+        use AppState::*;
+
+        let error = || Error {
+            error: "mirror unreachable".to_string(),
+        };
+        let check_failed = || Transition::CheckFailed {
+            error: "mirror unreachable".to_string(),
+        };
+        let cases = [
+            (Idle, Transition::CheckStarted, Some(Checking)),
+            (
+                UpdatesAvailable { count: 3 },
+                Transition::CheckStarted,
+                Some(Checking),
+            ),
+            (error(), Transition::CheckStarted, Some(Checking)),
+            (Offline, Transition::CheckStarted, Some(Checking)),
+            (Upgrading, Transition::CheckStarted, Some(Checking)),
+            (Checking, Transition::CheckStarted, None),
+            (Paused, Transition::CheckStarted, None),
+            (
+                Checking,
+                Transition::CheckFinished { updates: 0 },
+                Some(Idle),
+            ),
+            (
+                Checking,
+                Transition::CheckFinished { updates: 2 },
+                Some(UpdatesAvailable { count: 2 }),
+            ),
+            (Idle, Transition::CheckFinished { updates: 2 }, None),
+            (Checking, check_failed(), Some(error())),
+            (Idle, check_failed(), None),
+            (Checking, Transition::WentOffline, Some(Offline)),
+            (Idle, Transition::WentOffline, None),
+            (Idle, Transition::UpgradeStarted, Some(Upgrading)),
+            (Checking, Transition::UpgradeStarted, Some(Upgrading)),
+            (Paused, Transition::UpgradeStarted, None),
+            (
+                Upgrading,
+                Transition::UpgradeEnded { updates: 0 },
+                Some(Idle),
+            ),
+            (
+                Upgrading,
+                Transition::UpgradeEnded { updates: 1 },
+                Some(UpdatesAvailable { count: 1 }),
+            ),
+            (Idle, Transition::UpgradeEnded { updates: 0 }, None),
+            (Idle, Transition::Pause, Some(Paused)),
+            (
+                UpdatesAvailable { count: 3 },
+                Transition::Pause,
+                Some(Paused),
+            ),
+            (error(), Transition::Pause, Some(Paused)),
+            (Offline, Transition::Pause, Some(Paused)),
+            (Checking, Transition::Pause, None),
+            (Upgrading, Transition::Pause, None),
+            (Paused, Transition::Pause, None),
+            (Paused, Transition::Resume { updates: 0 }, Some(Idle)),
+            (
+                Paused,
+                Transition::Resume { updates: 4 },
+                Some(UpdatesAvailable { count: 4 }),
+            ),
+            (Idle, Transition::Resume { updates: 0 }, None),
+        ];
+
+        for (state, transition, expected) in cases {
+            assert_eq!(
+                state.next(transition.clone()).ok(),
+                expected,
+                "{:?} after {:?}",
+                transition,
+                state
+            );
+        }
+    }
+}
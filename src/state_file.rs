@@ -0,0 +1,54 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+
+use crate::{runtime_dir, status::Status};
+
+const FILE_NAME: &str = "state.json";
+
+/// Where the state is kept for scripts: `arch-updates-rs/state.json` in the
+/// runtime directory.
+pub fn path() -> Result<PathBuf> {
+    Ok(runtime_dir()?.join("arch-updates-rs").join(FILE_NAME))
+}
+
+/// The part of the status that prompts and bars need: counts, state, the
+/// pending updates and timestamps.
+pub fn payload(status: &Status) -> Value {
+    json!({
+        "count": status.updates.len(),
+        "total_count": status.total_count(),
+        "severity": status.severity,
+        "state": status.state.as_str(),
+        "updates": status.updates,
+        "last_checked": status.last_checked,
+        "last_upgrade": status.last_upgrade,
+    })
+}
+
+/// Replaces `path` with `contents` in one step, so that readers never see
+/// half a file.
+pub fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let partial_path = path.with_extension("partial");
+    fs::write(&partial_path, contents)
+        .with_context(|| format!("Failed to write {:?}", partial_path))?;
+    fs::rename(&partial_path, path).with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// Writes the state of `status` to the state file.
+pub fn write(status: &Status) -> Result<()> {
+    let path = path()?;
+    fs::create_dir_all(path.parent().unwrap()).context("Failed to create state directory")?;
+    write_atomic(&path, &format!("{}\n", payload(status)))
+}
+
+/// Removes the state file on exit, so that scripts don't read a stale state.
+pub fn remove() {
+    if let Ok(path) = path() {
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,443 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    advisories::Advisory,
+    config::Config,
+    history::Statistics,
+    kernel::KernelMismatch,
+    metrics::{self, Metrics},
+    news::NewsItem,
+    remote::HostStatus,
+    sources::SourceStatus,
+    state::{AppState, Transition},
+    supervisor::ThreadFailure,
+    tray::IconCount,
+};
+
+const MIB: u64 = 1024 * 1024;
+
+/// Key of the official repositories in the per-source `thresholds`.
+pub const PACMAN_SOURCE: &str = "pacman";
+
+/// Holds the keys packages are signed with. An outdated keyring is the most
+/// common reason an upgrade fails, so it should be updated first.
+pub const KEYRING_PACKAGE: &str = "archlinux-keyring";
+
+/// How urgently the pending updates should be brought to the user's attention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    None,
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::None => "none",
+            Severity::Normal => "normal",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+/// The canonical result of the last check, owned by the main loop. Every
+/// frontend renders from this instead of keeping its own copy of the truth.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Status {
+    #[serde(flatten)]
+    pub state: AppState,
+    pub updates: Vec<String>,
+    /// Updates pacman won't install because of `IgnorePkg` or `IgnoreGroup`.
+    /// They aren't in `updates` and don't count.
+    pub ignored_updates: Vec<String>,
+    /// Updates pending for less than `min_update_age_hours`. They aren't in
+    /// `updates` and don't count yet.
+    pub recent_updates: Vec<String>,
+    /// How many bytes downloading `updates` takes. Only looked up with
+    /// `warning_download_mib` or `critical_download_mib` set.
+    pub download_size: Option<u64>,
+    /// When the longest pending of `updates` was first seen, from the
+    /// history.
+    pub oldest_pending: Option<DateTime<Utc>>,
+    pub severity: Severity,
+    pub last_checked: Option<DateTime<Utc>>,
+    pub last_upgrade: Option<DateTime<Utc>>,
+    pub statistics: Option<Statistics>,
+    /// Results of the configured remote hosts. `updates` only holds this
+    /// machine's updates.
+    pub hosts: Vec<HostStatus>,
+    /// Results of the enabled non-pacman sources. They only count towards
+    /// the severity with `icon.count = "combined"`.
+    pub sources: Vec<SourceStatus>,
+    /// Why the config file couldn't be reloaded. The daemon keeps running
+    /// with the previous config until the file is fixed.
+    pub config_error: Option<String>,
+    /// The user acknowledged the pending updates, so a stale last upgrade
+    /// doesn't escalate the severity until new packages show up.
+    pub acknowledged: bool,
+    /// All pending updates are in the pacman cache, so upgrading won't have
+    /// to download anything.
+    pub downloaded: bool,
+    /// What the running upgrade is doing, while one is running.
+    pub progress: Option<String>,
+    /// Packages installed after the sync databases were refreshed, without
+    /// a full upgrade since. They may need libraries newer than the ones
+    /// installed, so the system needs a full `pacman -Syu`.
+    pub partial_upgrade: Vec<String>,
+    /// Problems around the updates, like an outdated mirrorlist.
+    pub advisories: Vec<Advisory>,
+    /// Packages linked against libraries the last upgrade removed, from
+    /// checkrebuild.
+    pub needs_rebuild: Vec<String>,
+    /// Arch news published since the last upgrade that weren't marked read.
+    pub unread_news: Vec<NewsItem>,
+    /// Set when the running kernel was upgraded without a reboot since.
+    pub kernel_mismatch: Option<KernelMismatch>,
+    /// Worker threads that stopped and were restarted, the last failure of
+    /// each.
+    pub thread_failures: Vec<ThreadFailure>,
+}
+
+impl Status {
+    pub fn new(
+        updates: Vec<String>,
+        last_upgrade: Option<DateTime<Utc>>,
+        statistics: Option<Statistics>,
+        hosts: Vec<HostStatus>,
+        sources: Vec<SourceStatus>,
+        config: &Config,
+    ) -> Self {
+        let mut status = Self {
+            state: AppState::Idle,
+            updates,
+            ignored_updates: Vec::new(),
+            recent_updates: Vec::new(),
+            download_size: None,
+            oldest_pending: None,
+            severity: Severity::None,
+            last_checked: Some(Utc::now()),
+            last_upgrade,
+            statistics,
+            hosts,
+            sources,
+            config_error: None,
+            acknowledged: false,
+            downloaded: false,
+            progress: None,
+            partial_upgrade: Vec::new(),
+            advisories: Vec::new(),
+            needs_rebuild: Vec::new(),
+            unread_news: Vec::new(),
+            kernel_mismatch: None,
+            thread_failures: Vec::new(),
+        };
+        status.update_severity(config);
+        status
+    }
+
+    /// Moves to the state after `transition`. Transitions the current state
+    /// doesn't allow are logged and ignored. Returns whether it happened.
+    pub fn transition(&mut self, transition: Transition) -> bool {
+        match self.state.next(transition.clone()) {
+            Ok(state) => {
+                self.state = state;
+                true
+            }
+            Err(e) => {
+                info!("Ignoring {:?}: {}", transition, e);
+                false
+            }
+        }
+    }
+
+    /// Computes the severity again, e.g. after the status was acknowledged.
+    pub fn update_severity(&mut self, config: &Config) {
+        let last_upgrade = if self.acknowledged {
+            None
+        } else {
+            self.last_upgrade
+        };
+
+        // Thresholds apply per machine and source, the most urgent one wins.
+        let mut severity = self.hosts.iter().map(|host| host.severity).fold(
+            severity(
+                PACMAN_SOURCE,
+                self.updates.len() as u32,
+                last_upgrade,
+                config,
+            ),
+            Severity::max,
+        );
+        if config.icon.count == IconCount::Combined {
+            severity = self
+                .sources
+                .iter()
+                .map(|source| self::severity(source.id, source.updates.len() as u32, None, config))
+                .fold(severity, Severity::max);
+        }
+
+        // A few big packages can be more work than many small ones.
+        if let Some(download_size) = self.download_size
+            && !self.updates.is_empty()
+        {
+            let reaches = |mib: u64| mib > 0 && download_size >= mib * MIB;
+            if reaches(config.critical_download_mib) {
+                severity = Severity::Critical;
+            } else if reaches(config.warning_download_mib) {
+                severity = severity.max(Severity::Warning);
+            }
+        }
+
+        // Counts alone don't show updates that were left pending for weeks.
+        let pending_is_stale = config.pending_warning_days > 0
+            && !self.acknowledged
+            && self.oldest_pending.is_some_and(|oldest_pending| {
+                elapsed_since(oldest_pending).as_secs()
+                    >= config.pending_warning_days as u64 * 60 * 60 * 24
+            });
+        if pending_is_stale && !self.updates.is_empty() {
+            severity = severity.max(Severity::Warning);
+        }
+
+        // A partial upgrade can break programs until the pending updates
+        // are installed.
+        if !self.partial_upgrade.is_empty() && !self.updates.is_empty() {
+            severity = severity.max(Severity::Warning);
+        }
+        if !self.updates.is_empty() {
+            severity = self
+                .advisories
+                .iter()
+                .map(|advisory| advisory.severity)
+                .fold(severity, Severity::max);
+        }
+
+        self.severity = severity;
+    }
+
+    /// Pending updates on this machine and all remote hosts.
+    pub fn total_count(&self) -> usize {
+        self.updates.len()
+            + self
+                .hosts
+                .iter()
+                .map(|host| host.updates.len())
+                .sum::<usize>()
+    }
+}
+
+/// The status as served to other processes, with the counts precomputed.
+#[derive(Serialize)]
+pub struct StatusResponse<'a> {
+    pub count: usize,
+    pub total_count: usize,
+    /// Counters about the daemon itself.
+    pub internal: Metrics,
+    #[serde(flatten)]
+    pub status: &'a Status,
+}
+
+impl<'a> StatusResponse<'a> {
+    pub fn new(status: &'a Status) -> Self {
+        Self {
+            count: status.updates.len(),
+            total_count: status.total_count(),
+            internal: metrics::snapshot(),
+            status,
+        }
+    }
+}
+
+/// The severity of `num_of_updates` pending updates of `source`.
+pub fn severity(
+    source: &str,
+    num_of_updates: u32,
+    last_upgrade: Option<DateTime<Utc>>,
+    config: &Config,
+) -> Severity {
+    let (warning_threshold, critical_threshold) = config.thresholds_for(source);
+
+    // Pending updates on a system that hasn't been upgraded in a while
+    // deserve at least the warning level.
+    let upgrade_is_stale = config.upgrade_warning_days > 0
+        && last_upgrade.is_some_and(|last_upgrade| {
+            elapsed_since(last_upgrade).as_secs()
+                >= config.upgrade_warning_days as u64 * 60 * 60 * 24
+        });
+
+    if num_of_updates == 0 {
+        Severity::None
+    } else if num_of_updates < warning_threshold && !upgrade_is_stale {
+        Severity::Normal
+    } else if num_of_updates < critical_threshold {
+        Severity::Warning
+    } else {
+        Severity::Critical
+    }
+}
+
+/// Whether a new `archlinux-keyring` is among `updates`.
+pub fn keyring_pending(updates: &[String]) -> bool {
+    updates
+        .iter()
+        .any(|update| update.split_whitespace().next() == Some(KEYRING_PACKAGE))
+}
+
+pub fn elapsed_since(timestamp: DateTime<Utc>) -> Duration {
+    Utc::now()
+        .signed_duration_since(timestamp)
+        .to_std()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Thresholds;
+
+    fn updates(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|index| format!("package-{} 1.0-1 -> 1.1-1", index))
+            .collect()
+    }
+
+    #[test]
+    fn severity_follows_the_thresholds() {
+        let mut config = Config::default();
+        config.thresholds.insert(
+            "snap".to_string(),
+            Thresholds {
+                warning: Some(2),
+                critical: None,
+            },
+        );
+        let stale = Some(Utc::now() - chrono::Duration::days(30));
+        let cases = [
+            (PACMAN_SOURCE, 0, None, Severity::None),
+            (PACMAN_SOURCE, 1, None, Severity::Normal),
+            (PACMAN_SOURCE, 24, None, Severity::Normal),
+            (PACMAN_SOURCE, 25, None, Severity::Warning),
+            (PACMAN_SOURCE, 99, None, Severity::Warning),
+            (PACMAN_SOURCE, 100, None, Severity::Critical),
+            (PACMAN_SOURCE, 1, stale, Severity::Warning),
+            (PACMAN_SOURCE, 0, stale, Severity::None),
+            (PACMAN_SOURCE, 100, stale, Severity::Critical),
+            ("snap", 1, None, Severity::Normal),
+            ("snap", 2, None, Severity::Warning),
+            ("snap", 100, None, Severity::Critical),
+        ];
+
+        for (source, count, last_upgrade, expected) in cases {
+            assert_eq!(
+                severity(source, count, last_upgrade, &config),
+                expected,
+                "{} updates of {}, last upgrade {:?}",
+                count,
+                source,
+                last_upgrade
+            );
+        }
+    }
+
+    #[test]
+    fn raises_severity_for_partial_upgrades_and_big_downloads() {
+        let config = Config {
+            warning_download_mib: 100,
+            critical_download_mib: 1000,
+            ..Config::default()
+        };
+
+        let mut status = Status::new(updates(2), None, None, vec![], vec![], &config);
+        assert_eq!(status.severity, Severity::Normal);
+
+        status.partial_upgrade = vec!["firefox".to_string()];
+        status.update_severity(&config);
+        assert_eq!(status.severity, Severity::Warning);
+
+        status.partial_upgrade.clear();
+        status.download_size = Some(1000 * MIB);
+        status.update_severity(&config);
+        assert_eq!(status.severity, Severity::Critical);
+
+        // Nothing is urgent without pending updates.
+        status.updates.clear();
+        status.update_severity(&config);
+        assert_eq!(status.severity, Severity::None);
+    }
+
+    #[test]
+    fn acknowledging_forgets_the_stale_upgrade() {
+        let config = Config::default();
+        let last_upgrade = Some(Utc::now() - chrono::Duration::days(30));
+        let mut status = Status::new(updates(1), last_upgrade, None, vec![], vec![], &config);
+        assert_eq!(status.severity, Severity::Warning);
+
+        status.acknowledged = true;
+        status.update_severity(&config);
+        assert_eq!(status.severity, Severity::Normal);
+    }
+
+    #[test]
+    fn warns_about_updates_pending_for_too_long() {
+        let config = Config {
+            pending_warning_days: 7,
+            ..Config::default()
+        };
+        let cases = [
+            (None, false, Severity::Normal),
+            (Some(3), false, Severity::Normal),
+            (Some(8), false, Severity::Warning),
+            (Some(8), true, Severity::Normal),
+        ];
+
+        for (pending_days, acknowledged, expected) in cases {
+            let mut status = Status::new(updates(1), None, None, vec![], vec![], &config);
+            status.oldest_pending =
+                pending_days.map(|days| Utc::now() - chrono::Duration::days(days));
+            status.acknowledged = acknowledged;
+            status.update_severity(&config);
+
+            assert_eq!(
+                status.severity, expected,
+                "{:?} days, acknowledged: {}",
+                pending_days, acknowledged
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_transitions_the_state_does_not_allow() {
+        let mut status = Status::new(updates(3), None, None, vec![], vec![], &Config::default());
+        status.state = AppState::UpdatesAvailable { count: 3 };
+
+        assert!(status.transition(Transition::Pause));
+        assert!(!status.transition(Transition::CheckStarted));
+        assert_eq!(status.state, AppState::Paused);
+
+        assert!(status.transition(Transition::Resume { updates: 3 }));
+        assert_eq!(status.state, AppState::UpdatesAvailable { count: 3 });
+    }
+
+    #[test]
+    fn finds_the_keyring() {
+        let cases = [
+            (vec!["archlinux-keyring 20240101-1 -> 20240201-1"], true),
+            (vec!["linux 6.9.1-1 -> 6.9.2-1"], false),
+            (vec!["archlinux-keyring-extra 1-1 -> 2-1"], false),
+            (vec![], false),
+        ];
+        for (updates, expected) in cases {
+            let updates: Vec<String> = updates.into_iter().map(String::from).collect();
+            assert_eq!(keyring_pending(&updates), expected, "{:?}", updates);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use crate::status::Status;
+
+/// How a summary that doesn't fit `max_width` is shortened.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Abbreviation {
+    /// Shortens the words step by step: `73 upd (3 rem, 5 dev)`.
+    #[default]
+    Abbreviate,
+    /// Keeps the wording but leaves out the breakdown: `73 pending updates`.
+    DropDetails,
+    /// Cuts the full sentence off with an ellipsis.
+    Truncate,
+}
+
+/// One-line text output for bars.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SummaryConfig {
+    /// Maximum number of characters. Unlimited if not set.
+    pub max_width: Option<usize>,
+    pub abbreviation: Abbreviation,
+}
+
+/// Renders the status as a single line that fits `config.max_width`.
+pub fn summary(status: &Status, config: &SummaryConfig) -> String {
+    let total = status.total_count() + source_count(status);
+
+    // (full label, abbreviated label, count) of everything that isn't pacman
+    // on this machine.
+    let mut details: Vec<(String, String, usize)> = Vec::new();
+    let remote_count = status.total_count() - status.updates.len();
+    if remote_count > 0 {
+        details.push(("remote".to_string(), "rem".to_string(), remote_count));
+    }
+    for source in &status.sources {
+        if !source.updates.is_empty() {
+            let name = source.name.to_lowercase();
+            let short = name.chars().take(3).collect();
+            details.push((name, short, source.updates.len()));
+        }
+    }
+
+    let with_details = |main: String, labels: &dyn Fn(&(String, String, usize)) -> String| {
+        if details.is_empty() {
+            main
+        } else {
+            let details: Vec<String> = details.iter().map(labels).collect();
+            format!("{} ({})", main, details.join(", "))
+        }
+    };
+
+    let full = with_details(format!("{} pending updates", total), &|(name, _, count)| {
+        format!("{} {}", count, name)
+    });
+
+    let Some(max_width) = config.max_width else {
+        return full;
+    };
+
+    let candidates = match config.abbreviation {
+        Abbreviation::Abbreviate => vec![
+            full.clone(),
+            with_details(format!("{} updates", total), &|(name, _, count)| {
+                format!("{} {}", count, name)
+            }),
+            with_details(format!("{} upd", total), &|(_, short, count)| {
+                format!("{} {}", count, short)
+            }),
+            with_details(total.to_string(), &|(_, _, count)| count.to_string()),
+            total.to_string(),
+        ],
+        Abbreviation::DropDetails => vec![
+            full.clone(),
+            format!("{} pending updates", total),
+            format!("{} updates", total),
+            total.to_string(),
+        ],
+        Abbreviation::Truncate => vec![full.clone()],
+    };
+
+    if let Some(candidate) = candidates
+        .into_iter()
+        .find(|candidate| candidate.chars().count() <= max_width)
+    {
+        return candidate;
+    }
+
+    truncate(&full, max_width)
+}
+
+fn source_count(status: &Status) -> usize {
+    status
+        .sources
+        .iter()
+        .map(|source| source.updates.len())
+        .sum()
+}
+
+fn truncate(text: &str, max_width: usize) -> String {
+    if max_width == 0 {
+        return String::new();
+    }
+    let mut truncated: String = text.chars().take(max_width - 1).collect();
+    truncated.push('…');
+    truncated
+}
@@ -0,0 +1,345 @@
+use std::{
+    any::Any,
+    backtrace::{Backtrace, BacktraceStatus},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Condvar, Mutex,
+        mpsc::{Receiver, RecvTimeoutError, Sender},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use serde::Serialize;
+use tracing::{error, warn};
+
+use crate::Event;
+
+/// How long to wait before the first restart. Doubles with each failure in
+/// a row, up to `MAX_RESTART_DELAY`.
+const MIN_RESTART_DELAY: Duration = Duration::from_secs(5);
+
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// A worker that ran this long before it stopped counts as healthy, so the
+/// next restart is quick again.
+const HEALTHY_RUN: Duration = Duration::from_secs(10 * 60);
+
+/// How long threads get to stop when the app shuts down.
+const JOIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often blocking receives check whether the app is shutting down.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Set once the app is shutting down. Waits on `SHUTDOWN_CONDVAR` end early
+/// then.
+static SHUTTING_DOWN: Mutex<bool> = Mutex::new(false);
+static SHUTDOWN_CONDVAR: Condvar = Condvar::new();
+
+/// A worker thread that stopped and was restarted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ThreadFailure {
+    pub thread: String,
+    pub reason: String,
+}
+
+/// Logs panics, so that they end up in the log file or the journal rather
+/// than only on the terminal.
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let backtrace = Backtrace::capture();
+        if backtrace.status() == BacktraceStatus::Captured {
+            error!("{}\n{}", info, backtrace);
+        } else {
+            error!("{}", info);
+        }
+    }));
+}
+
+/// Tells the workers to stop. They aren't restarted anymore from now on.
+pub fn shutdown() {
+    *SHUTTING_DOWN.lock().unwrap() = true;
+    SHUTDOWN_CONDVAR.notify_all();
+}
+
+pub fn shutting_down() -> bool {
+    *SHUTTING_DOWN.lock().unwrap()
+}
+
+/// Sleeps for `duration`, or less if the app shuts down in the meantime.
+/// Returns whether it is shutting down.
+pub fn sleep(duration: Duration) -> bool {
+    let shutting_down = SHUTTING_DOWN.lock().unwrap();
+    let (shutting_down, _) = SHUTDOWN_CONDVAR
+        .wait_timeout_while(shutting_down, duration, |shutting_down| !*shutting_down)
+        .unwrap();
+    *shutting_down
+}
+
+/// Receives the next message, or `None` once the channel is closed or the
+/// app shuts down.
+pub fn recv<T>(rx: &Receiver<T>) -> Option<T> {
+    loop {
+        match rx.recv_timeout(SHUTDOWN_POLL_INTERVAL) {
+            Ok(message) => return Some(message),
+            Err(RecvTimeoutError::Timeout) if !shutting_down() => {}
+            Err(_) => return None,
+        }
+    }
+}
+
+/// Like `Receiver::recv_timeout`, but gives up with `Disconnected` once the
+/// app shuts down.
+pub fn recv_timeout<T>(rx: &Receiver<T>, timeout: Duration) -> Result<T, RecvTimeoutError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(remaining.min(SHUTDOWN_POLL_INTERVAL)) {
+            Err(RecvTimeoutError::Timeout) if shutting_down() => {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            Err(RecvTimeoutError::Timeout) if remaining > SHUTDOWN_POLL_INTERVAL => {}
+            result => return result,
+        }
+    }
+}
+
+/// Waits for the threads to stop, but no longer than `JOIN_TIMEOUT`, so that
+/// a stuck thread can't hold up the exit.
+pub fn join(threads: Vec<JoinHandle<()>>) {
+    let deadline = Instant::now() + JOIN_TIMEOUT;
+    while Instant::now() < deadline && !threads.iter().all(JoinHandle::is_finished) {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    for thread in threads {
+        if thread.is_finished() {
+            let _ = thread.join();
+        } else {
+            warn!(
+                "The {} thread didn't stop in time",
+                thread.thread().name().unwrap_or("unnamed")
+            );
+        }
+    }
+}
+
+/// Runs `worker` on a thread named `name`, and runs it again whenever it
+/// panics or returns, reporting each failure as `Event::ThreadFailed`.
+/// Restarts happen on the same thread, since GTK can't move to another one.
+/// Stops once the app shuts down or the main loop is gone.
+pub fn supervise<F>(name: &'static str, tx: Sender<Event>, mut worker: F) -> Option<JoinHandle<()>>
+where
+    F: FnMut() + Send + 'static,
+{
+    supervise_fallible(name, tx, move || {
+        worker();
+        Ok(())
+    })
+}
+
+/// Like `supervise`, for workers that can tell why they stopped. The error is
+/// logged and reported as the reason of the failure.
+pub fn supervise_fallible<F>(
+    name: &'static str,
+    tx: Sender<Event>,
+    worker: F,
+) -> Option<JoinHandle<()>>
+where
+    F: FnMut() -> Result<()> + Send + 'static,
+{
+    spawn_supervised(
+        name,
+        tx,
+        worker,
+        Backoff::new(MIN_RESTART_DELAY, MAX_RESTART_DELAY, HEALTHY_RUN),
+    )
+}
+
+/// How long to wait before restarting a worker, after it failed.
+struct Backoff {
+    min: Duration,
+    max: Duration,
+    healthy_run: Duration,
+    delay: Duration,
+}
+
+impl Backoff {
+    fn new(min: Duration, max: Duration, healthy_run: Duration) -> Self {
+        Self {
+            min,
+            max,
+            healthy_run,
+            delay: min,
+        }
+    }
+
+    /// The delay before the next restart of a worker that ran for `ran_for`.
+    fn next(&mut self, ran_for: Duration) -> Duration {
+        if ran_for >= self.healthy_run {
+            self.delay = self.min;
+        }
+        let delay = self.delay;
+        self.delay = (self.delay * 2).min(self.max);
+        delay
+    }
+}
+
+fn spawn_supervised<F>(
+    name: &'static str,
+    tx: Sender<Event>,
+    mut worker: F,
+    mut backoff: Backoff,
+) -> Option<JoinHandle<()>>
+where
+    F: FnMut() -> Result<()> + Send + 'static,
+{
+    let spawned = thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            loop {
+                let started_at = Instant::now();
+                let reason = match panic::catch_unwind(AssertUnwindSafe(&mut worker)) {
+                    Ok(Ok(())) => "it returned".to_string(),
+                    Ok(Err(e)) => format!("it failed: {:#}", e),
+                    Err(panic) => format!("it panicked: {}", panic_message(&*panic)),
+                };
+                if shutting_down() {
+                    return;
+                }
+
+                let restart_delay = backoff.next(started_at.elapsed());
+                let failure = ThreadFailure {
+                    thread: name.to_string(),
+                    reason,
+                };
+                if tx.send(Event::ThreadFailed(failure.clone())).is_err() {
+                    return;
+                }
+                error!(
+                    "The {} thread stopped because {}, restarting it in {} seconds",
+                    failure.thread,
+                    failure.reason,
+                    restart_delay.as_secs()
+                );
+
+                if sleep(restart_delay) {
+                    return;
+                }
+            }
+        });
+
+    match spawned {
+        Ok(thread) => Some(thread),
+        Err(e) => {
+            error!("Failed to start the {} thread: {}", name, e);
+            None
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, mpsc::channel};
+
+    use anyhow::bail;
+
+    use super::*;
+
+    #[test]
+    fn doubles_the_delay_until_a_healthy_run() {
+        let mut backoff = Backoff::new(
+            Duration::from_secs(5),
+            Duration::from_secs(30),
+            Duration::from_secs(600),
+        );
+        let cases = [
+            (0, 5),
+            (1, 10),
+            (1, 20),
+            (1, 30),
+            (599, 30),
+            // Ran long enough to count as healthy.
+            (600, 5),
+            (1, 10),
+        ];
+
+        for (ran_for, expected) in cases {
+            assert_eq!(
+                backoff.next(Duration::from_secs(ran_for)),
+                Duration::from_secs(expected),
+                "after running {}s",
+                ran_for
+            );
+        }
+    }
+
+    #[test]
+    fn restarts_workers_that_panic_fail_or_return() {
+        let (tx, rx) = channel();
+        let runs = Arc::new(Mutex::new(Vec::new()));
+        let worker_runs = runs.clone();
+        let thread = spawn_supervised(
+            "test",
+            tx,
+            move || {
+                let run = {
+                    let mut runs = worker_runs.lock().unwrap();
+                    runs.push(Instant::now());
+                    runs.len()
+                };
+                match run {
+                    1 => panic!("boom"),
+                    2 => bail!("no network"),
+                    _ => Ok(()),
+                }
+            },
+            Backoff::new(
+                Duration::from_millis(20),
+                Duration::from_secs(1),
+                Duration::from_secs(60),
+            ),
+        )
+        .unwrap();
+
+        let reasons: Vec<String> = (0..3)
+            .map(|_| match rx.recv_timeout(Duration::from_secs(5)) {
+                Ok(Event::ThreadFailed(failure)) => {
+                    assert_eq!(failure.thread, "test");
+                    failure.reason
+                }
+                _ => panic!("expected a thread failure"),
+            })
+            .collect();
+        assert_eq!(
+            reasons,
+            ["it panicked: boom", "it failed: no network", "it returned"]
+        );
+
+        // Stops once nobody listens anymore.
+        drop(rx);
+        thread.join().unwrap();
+
+        let runs = runs.lock().unwrap();
+        assert_eq!(runs.len(), 4);
+        let delays: Vec<Duration> = runs.windows(2).map(|runs| runs[1] - runs[0]).collect();
+        for (delay, min) in delays.iter().zip([20, 40, 80]) {
+            assert!(
+                *delay >= Duration::from_millis(min),
+                "restarted after {:?}",
+                delay
+            );
+        }
+    }
+}
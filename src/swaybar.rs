@@ -0,0 +1,122 @@
+use std::{
+    io::{BufRead, Write, stdin, stdout},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+use serde_json::{Value, json};
+
+use crate::control;
+
+/// The block's `name`, by which its click events are recognized.
+const BLOCK_NAME: &str = "arch-updates-rs";
+
+const WARNING_COLOR: &str = "#ffcc00";
+const CRITICAL_COLOR: &str = "#ff5555";
+const INACTIVE_COLOR: &str = "#888888";
+
+/// How long to wait before following the running instance again after it
+/// exited or couldn't be reached.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Speaks the i3bar/swaybar protocol on stdout, with a block that follows
+/// the running instance. Left clicks check now, right clicks pause or resume.
+/// Runs until the bar closes stdout.
+pub fn run() -> Result<()> {
+    println!("{}", json!({ "version": 1, "click_events": true }));
+    println!("[");
+
+    let paused = Arc::new(AtomicBool::new(false));
+    let clicks_paused = paused.clone();
+    thread::spawn(move || handle_clicks(&clicks_paused));
+
+    loop {
+        let result = control::watch_status(|status| {
+            paused.store(status["state"] == "paused", Ordering::Relaxed);
+            print_block(block(&status));
+            Ok(())
+        });
+
+        // Logging isn't set up for commands, so errors go to the bar's log.
+        if let Err(e) = result {
+            eprintln!("{:#}", e);
+            print_block(json!({
+                "name": BLOCK_NAME,
+                "full_text": "updates: not running",
+                "color": INACTIVE_COLOR,
+            }));
+        }
+
+        thread::sleep(RECONNECT_INTERVAL);
+    }
+}
+
+fn print_block(block: Value) {
+    // The bar closed stdout, so there is no one left to show the block to.
+    if writeln!(stdout(), "[{}],", block).is_err() {
+        std::process::exit(0);
+    }
+}
+
+/// The block for the status JSON of the control socket.
+fn block(status: &Value) -> Value {
+    let total = status["total_count"].as_u64().unwrap_or_default();
+    let full_text = match status["state"].as_str().unwrap_or_default() {
+        "checking" => "updates: checking".to_string(),
+        "upgrading" => "updates: upgrading".to_string(),
+        "paused" => format!("updates: {} (paused)", total),
+        "offline" => format!("updates: {} (offline)", total),
+        "error" => "updates: check failed".to_string(),
+        _ => format!("updates: {}", total),
+    };
+
+    let mut block = json!({
+        "name": BLOCK_NAME,
+        "full_text": full_text,
+        "short_text": total.to_string(),
+    });
+    match status["severity"].as_str() {
+        Some("warning") => block["color"] = json!(WARNING_COLOR),
+        Some("critical") => {
+            block["color"] = json!(CRITICAL_COLOR);
+            block["urgent"] = json!(true);
+        }
+        _ => {}
+    }
+    if matches!(status["state"].as_str(), Some("paused" | "error")) {
+        block["color"] = json!(INACTIVE_COLOR);
+    }
+
+    block
+}
+
+/// Reads the click events the bar writes to stdin, an endless JSON array of
+/// one event per line.
+fn handle_clicks(paused: &AtomicBool) {
+    for line in stdin().lock().lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        let line = line.trim_start_matches(['[', ',']).trim();
+        let Ok(event) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        if event["name"] != BLOCK_NAME {
+            continue;
+        }
+
+        let result = match event["button"].as_u64() {
+            Some(1) => control::request_check(),
+            Some(3) => control::request_pause(!paused.load(Ordering::Relaxed)),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            eprintln!("{:#}", e);
+        }
+    }
+}
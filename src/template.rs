@@ -0,0 +1,186 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::fs::FileTypeExt,
+    path::PathBuf,
+    sync::mpsc::{Sender, channel},
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{error, info_span};
+
+use crate::{config::Config, control};
+
+/// How long to wait before following the running instance again after it
+/// exited or couldn't be reached.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The color markup of the bar the text is for.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Markup {
+    #[default]
+    None,
+    Xmobar,
+    Dzen,
+    Conky,
+    Lemonbar,
+}
+
+impl Markup {
+    /// The tags that start and end text in `color`.
+    fn tags(self, color: &str) -> (String, String) {
+        match self {
+            Markup::None => (String::new(), String::new()),
+            Markup::Xmobar => (format!("<fc={}>", color), "</fc>".to_string()),
+            Markup::Dzen => (format!("^fg({})", color), "^fg()".to_string()),
+            Markup::Conky => (format!("${{color {}}}", color), "${color}".to_string()),
+            Markup::Lemonbar => (format!("%{{F{}}}", color), "%{F-}".to_string()),
+        }
+    }
+}
+
+/// A line of plain text for bars like xmobar, dzen and conky.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TemplateConfig {
+    /// `{icon}`, `{count}`, `{local_count}`, `{state}` and `{severity}` are
+    /// filled in. Text between `{color}` and `{/color}` is colored by the
+    /// severity.
+    pub format: String,
+    pub markup: Markup,
+    pub warning_color: String,
+    pub critical_color: String,
+    /// A FIFO or file the running instance writes every change to.
+    pub path: Option<PathBuf>,
+}
+
+impl Default for TemplateConfig {
+    fn default() -> Self {
+        Self {
+            format: "{color}{icon} {count}{/color}".to_string(),
+            markup: Markup::None,
+            warning_color: "#ffcc00".to_string(),
+            critical_color: "#ff5555".to_string(),
+            path: None,
+        }
+    }
+}
+
+/// Renders the status JSON of the control socket with `config.format`.
+pub fn render(status: &Value, config: &TemplateConfig) -> String {
+    let count = status["total_count"].as_u64().unwrap_or_default();
+    let local_count = status["count"].as_u64().unwrap_or_default();
+    let state = status["state"].as_str().unwrap_or_default();
+    let severity = status["severity"].as_str().unwrap_or_default();
+
+    let icon = match state {
+        "checking" | "upgrading" => "⟳",
+        "paused" => "⏸",
+        "error" | "offline" => "⚠",
+        _ if count > 0 => "↑",
+        _ => "✓",
+    };
+    let color = match severity {
+        "warning" => Some(&config.warning_color),
+        "critical" => Some(&config.critical_color),
+        _ => None,
+    };
+    let (open, close) = color
+        .map(|color| config.markup.tags(color))
+        .unwrap_or_default();
+
+    config
+        .format
+        .replace("{icon}", icon)
+        .replace("{count}", &count.to_string())
+        .replace("{local_count}", &local_count.to_string())
+        .replace("{state}", state)
+        .replace("{severity}", severity)
+        .replace("{color}", &open)
+        .replace("{/color}", &close)
+}
+
+/// Writes the lines sent to the returned sender to `path`. A FIFO is opened
+/// on a thread of its own, since that blocks until a reader opens it too.
+/// Lines sent while no one reads are dropped, except for the latest.
+pub fn spawn_writer(path: PathBuf) -> Sender<String> {
+    let (tx, rx) = channel::<String>();
+
+    thread::spawn(move || {
+        let _span = info_span!("template").entered();
+        let mut latest = None;
+        loop {
+            if latest.is_none() {
+                match rx.recv() {
+                    Ok(line) => latest = Some(line),
+                    Err(_) => return,
+                }
+            }
+            while let Ok(line) = rx.try_recv() {
+                latest = Some(line);
+            }
+
+            let is_fifo = fs::metadata(&path).is_ok_and(|metadata| metadata.file_type().is_fifo());
+            if !is_fifo {
+                if let Some(line) = latest.take()
+                    && let Err(e) = fs::write(&path, line + "\n")
+                {
+                    error!("Failed to write template output to {:?}: {}", path, e);
+                }
+                continue;
+            }
+
+            let mut fifo = match OpenOptions::new().write(true).open(&path) {
+                Ok(fifo) => fifo,
+                Err(e) => {
+                    error!("Failed to open {:?}: {}", path, e);
+                    return;
+                }
+            };
+            // Once the reader goes away, the latest line waits for the next
+            // one.
+            while let Some(line) = &latest
+                && writeln!(fifo, "{}", line).is_ok()
+            {
+                match rx.recv() {
+                    Ok(line) => latest = Some(line),
+                    Err(_) => return,
+                }
+            }
+        }
+    });
+
+    tx
+}
+
+/// Prints a line for every change of the running instance's status, with
+/// `format` or the configured one. Runs until stdout is closed.
+pub fn follow(format: Option<String>) -> Result<()> {
+    let mut config = Config::load()?.template;
+    if let Some(format) = format {
+        config.format = format;
+    }
+
+    loop {
+        let result = control::watch_status(|status| {
+            let line = render(&status, &config);
+            // The bar closed stdout, so there is no one left to read on.
+            if writeln!(std::io::stdout(), "{}", line).is_err() {
+                std::process::exit(0);
+            }
+            Ok(())
+        });
+
+        // Logging isn't set up for commands, so errors go to the bar's log.
+        if let Err(e) = result {
+            eprintln!("{:#}", e);
+        }
+
+        thread::sleep(RECONNECT_INTERVAL);
+    }
+}
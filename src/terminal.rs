@@ -0,0 +1,130 @@
+use std::{
+    process::{Command, Stdio},
+    thread,
+};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+const TERMINALS: &[&str] = &["foot", "alacritty", "kitty", "xterm"];
+
+pub const DEFAULT_UPDATE_COMMAND: &str = "sudo pacman -Syu";
+
+/// Runs before the update command when a new keyring is pending.
+pub const KEYRING_UPDATE_COMMAND: &str = "sudo pacman -Sy --needed archlinux-keyring";
+
+/// Replaced by the command in the `terminal` template.
+const COMMAND_PLACEHOLDER: &str = "{cmd}";
+
+/// Keeps the terminal open so the output can be read.
+const WAIT_FOR_ENTER: &str = "echo; read -r -p 'Press Enter to close' _";
+
+/// Window manager hints for windows spawned by the program, so users can write
+/// compositor rules (floating, centered, workspace) for them.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowHints {
+    /// Wayland app_id, and X11 WM_CLASS where the terminal supports it.
+    pub app_id: String,
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl Default for WindowHints {
+    fn default() -> Self {
+        Self {
+            app_id: "arch-updates-rs".to_string(),
+            columns: 120,
+            rows: 36,
+        }
+    }
+}
+
+/// Opens a terminal emulator running the shell `command`. `template` is the
+/// configured `terminal`, e.g. `kitty -e {cmd}`. Without one, the first
+/// available of `TERMINALS` is used.
+pub fn launch(template: Option<&str>, hints: &WindowHints, command: &str) -> Result<()> {
+    let script = format!("{}; {}", command, WAIT_FOR_ENTER);
+
+    let mut terminal_command = if let Some(template) = template {
+        info!("Launching {:?} to run {:?}", template, command);
+        from_template(template, &script)
+    } else {
+        let Some(terminal) = TERMINALS.iter().find(|terminal| is_installed(terminal)) else {
+            bail!("None of {} is installed", TERMINALS.join(", "));
+        };
+        info!("Launching {} to run {:?}", terminal, command);
+
+        let mut terminal_command = Command::new(terminal);
+        terminal_command.args(hint_args(terminal, hints));
+        terminal_command.args(["-e", "sh", "-c", &script]);
+        terminal_command
+    };
+
+    let mut child = terminal_command.stdin(Stdio::null()).spawn()?;
+    thread::spawn(move || child.wait());
+
+    Ok(())
+}
+
+/// Builds the command line of a `terminal` template. The template is run by
+/// the shell, so it may quote its own arguments. `{cmd}` becomes the quoted
+/// script, which is appended if the template doesn't mention it.
+fn from_template(template: &str, script: &str) -> Command {
+    let script = format!("sh -c {}", shell_quote(script));
+    let command_line = if template.contains(COMMAND_PLACEHOLDER) {
+        template.replace(COMMAND_PLACEHOLDER, &script)
+    } else {
+        format!("{} {}", template, script)
+    };
+
+    let mut command = Command::new("sh");
+    command.args(["-c", &command_line]);
+    command
+}
+
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn hint_args(terminal: &str, hints: &WindowHints) -> Vec<String> {
+    match terminal {
+        "foot" => vec![
+            format!("--app-id={}", hints.app_id),
+            format!("--window-size-chars={}x{}", hints.columns, hints.rows),
+        ],
+        "alacritty" => vec![
+            "--class".to_string(),
+            hints.app_id.clone(),
+            "-o".to_string(),
+            format!("window.dimensions.columns={}", hints.columns),
+            "-o".to_string(),
+            format!("window.dimensions.lines={}", hints.rows),
+        ],
+        "kitty" => vec![
+            "--class".to_string(),
+            hints.app_id.clone(),
+            "-o".to_string(),
+            format!("initial_window_width={}c", hints.columns),
+            "-o".to_string(),
+            format!("initial_window_height={}c", hints.rows),
+        ],
+        "xterm" => vec![
+            "-class".to_string(),
+            hints.app_id.clone(),
+            "-geometry".to_string(),
+            format!("{}x{}", hints.columns, hints.rows),
+        ],
+        _ => vec![],
+    }
+}
+
+fn is_installed(program: &str) -> bool {
+    Command::new("which")
+        .arg(program)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
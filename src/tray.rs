@@ -0,0 +1,1261 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, Sender, channel},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use image::{
+    RgbaImage,
+    imageops::{self, FilterType},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, info_span, warn};
+
+use crate::{
+    Event,
+    appearance::{self, ColorScheme, IconTheme},
+    bus::StateUpdate,
+    changelog,
+    config::{self, Config},
+    confirm::{self, Confirmation},
+    hostname,
+    i18n::t,
+    maintenance::Action,
+    privileges, runtime_dir, sni,
+    state::AppState,
+    status::{self, Severity, Status, elapsed_since},
+    supervisor,
+    version::{EpochDisplay, display_update_line},
+};
+
+const CHECKING_ICON_BYTES: &[u8] = include_bytes!("../assets/checking.png");
+const NO_UPDATES_ICON_BYTES: &[u8] = include_bytes!("../assets/no-updates.png");
+const UPDATES_ICON_BYTES: &[u8] = include_bytes!("../assets/updates.png");
+const UPDATES_WARNING_LEVEL_ICON_BYTES: &[u8] = include_bytes!("../assets/updates-warn.png");
+const UPDATES_CRITICAL_LEVEL_ICON_BYTES: &[u8] = include_bytes!("../assets/updates-critical.png");
+const UPDATING_ICON_BYTES: &[u8] = include_bytes!("../assets/updating.png");
+const DOWNLOADED_ICON_BYTES: &[u8] = include_bytes!("../assets/updates-downloaded.png");
+const NEWS_ICON_BYTES: &[u8] = include_bytes!("../assets/updates-news.png");
+const REBOOT_REQUIRED_ICON_BYTES: &[u8] = include_bytes!("../assets/reboot-required.png");
+
+pub const DEBUG_SNAPSHOT_MENU_ID: &str = "debug-snapshot";
+pub const UPDATE_NOW_MENU_ID: &str = "update-now";
+pub const ACKNOWLEDGE_ALL_MENU_ID: &str = "acknowledge-all";
+pub const DOWNLOAD_UPDATES_MENU_ID: &str = "download-updates";
+pub const INSTALL_UPDATES_MENU_ID: &str = "install-updates";
+pub const MARK_NEWS_READ_MENU_ID: &str = "mark-news-read";
+pub const PAUSE_MENU_ID: &str = "pause";
+/// Opens the updates window, only in builds with GTK.
+pub const SHOW_UPDATES_MENU_ID: &str = "show-updates";
+
+/// How long to wait before trying to show the tray icon again. Doubles with
+/// each failure in a row, up to `MAX_SHOW_RETRY_DELAY`.
+const MIN_SHOW_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_SHOW_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// How the tray icon is shown.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrayBackend {
+    /// GTK with libappindicator, with the confirmation dialogs in GTK too.
+    Gtk,
+    /// A StatusNotifierItem spoken directly over D-Bus, without GTK. Needs a
+    /// panel or bar that hosts StatusNotifierItems, like waybar or KDE's.
+    /// Builds with GTK 4 always show their icon this way.
+    Sni,
+    /// A small widget in a corner of the screen, above other windows, for
+    /// Wayland compositors without a tray. Needs the `layer-shell` feature,
+    /// or `layer-shell-gtk4` with GTK 4.
+    Widget,
+}
+
+/// Where the widget of `tray_backend = "widget"` is shown.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct WidgetConfig {
+    pub corner: Corner,
+    /// Distance from the edges of the screen in pixels.
+    pub margin: i32,
+}
+
+impl Default for WidgetConfig {
+    fn default() -> Self {
+        Self {
+            corner: Corner::TopRight,
+            margin: 8,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Default for TrayBackend {
+    fn default() -> Self {
+        if cfg!(feature = "gtk") {
+            TrayBackend::Gtk
+        } else {
+            TrayBackend::Sni
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IconScaling {
+    /// Resamples to the exact size. Edges may get slightly blurry.
+    #[default]
+    Smooth,
+    /// Shrinks by a whole factor only and pads the rest with transparency, so
+    /// every pixel stays sharp.
+    Integer,
+}
+
+/// Which updates the icon's severity is based on.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IconCount {
+    /// Only pacman updates, on this machine and the remote hosts.
+    #[default]
+    Pacman,
+    /// Pacman updates plus the updates of the additional sources.
+    Combined,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct IconConfig {
+    /// Size in pixels the tray host displays icons at, e.g. 16, 22, 24, 32 or
+    /// 48. Without it the 48px icons are handed over as they are.
+    pub size: Option<u32>,
+    pub scaling: IconScaling,
+    pub theme: IconTheme,
+    pub count: IconCount,
+}
+
+/// Renders one of the bundled icons for the panel's color scheme, at the
+/// configured size.
+pub fn render_icon(
+    icon: IconKind,
+    config: &IconConfig,
+    color_scheme: ColorScheme,
+) -> Result<RgbaImage> {
+    let mut image_buff = match image::load_from_memory(icon.bytes()) {
+        Ok(image_dyn) => image_dyn.into_rgba8(),
+        Err(e) => return Err(e).context("Failed to load icon"),
+    };
+
+    if color_scheme == ColorScheme::Light {
+        darken_gray_pixels(&mut image_buff);
+    }
+    if icon.faded() {
+        fade_pixels(&mut image_buff);
+    }
+
+    Ok(match config.size {
+        Some(size) if size > 0 && size != image_buff.width() => {
+            resize_icon(&image_buff, size, config.scaling)
+        }
+        _ => image_buff,
+    })
+}
+
+/// The gray icons are drawn for dark panels and all but disappear on light
+/// ones. Darkens their gray background while keeping white glyphs and the
+/// colored icons as they are.
+fn darken_gray_pixels(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let [r, g, b, _] = pixel.0;
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let is_gray = max - min < 24;
+        let is_white = min > 240;
+
+        if is_gray && !is_white {
+            for channel in &mut pixel.0[..3] {
+                *channel = (*channel as f32 * 0.45) as u8;
+            }
+        }
+    }
+}
+
+/// Makes the icon half transparent, for the states in which nothing is
+/// being checked.
+fn fade_pixels(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        pixel.0[3] /= 2;
+    }
+}
+
+fn resize_icon(image: &RgbaImage, size: u32, scaling: IconScaling) -> RgbaImage {
+    match scaling {
+        IconScaling::Smooth => imageops::resize(image, size, size, FilterType::CatmullRom),
+        IconScaling::Integer => {
+            // Upscale by repeating pixels, downscale by averaging whole blocks.
+            let scaled = if size > image.width() {
+                let factor = size / image.width();
+                imageops::resize(
+                    image,
+                    image.width() * factor,
+                    image.height() * factor,
+                    FilterType::Nearest,
+                )
+            } else {
+                let factor = image.width().div_ceil(size);
+                imageops::thumbnail(image, image.width() / factor, image.height() / factor)
+            };
+
+            let mut canvas = RgbaImage::new(size, size);
+            let x = (size - scaled.width()) / 2;
+            let y = (size - scaled.height()) / 2;
+            imageops::overlay(&mut canvas, &scaled, x as i64, y as i64);
+            canvas
+        }
+    }
+}
+
+/// Which of the bundled icons the tray shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IconKind {
+    NoUpdates,
+    Updates,
+    Warning,
+    Critical,
+    Checking,
+    Updating,
+    /// Updates below the warning threshold that are already downloaded.
+    Downloaded,
+    /// Updates held back until the Arch news are read.
+    News,
+    /// The running kernel was upgraded without a reboot since.
+    RebootRequired,
+    Paused,
+    Offline,
+    /// The last check failed.
+    CheckFailed,
+}
+
+impl IconKind {
+    fn for_severity(severity: Severity) -> Self {
+        match severity {
+            Severity::None => IconKind::NoUpdates,
+            Severity::Normal => IconKind::Updates,
+            Severity::Warning => IconKind::Warning,
+            Severity::Critical => IconKind::Critical,
+        }
+    }
+
+    /// The state decides unless the app is idle or has updates. Warning and
+    /// critical updates keep their icon when they are downloaded, the
+    /// severity matters more. Unread news come first, since they may say how
+    /// to upgrade safely, then a pending reboot.
+    fn for_status(status: &Status) -> Self {
+        match status.state {
+            AppState::Checking => return IconKind::Checking,
+            AppState::Upgrading => return IconKind::Updating,
+            AppState::Paused => return IconKind::Paused,
+            AppState::Offline => return IconKind::Offline,
+            AppState::Error { .. } => return IconKind::CheckFailed,
+            AppState::Idle | AppState::UpdatesAvailable { .. } => {}
+        }
+        if !status.unread_news.is_empty() && !status.updates.is_empty() {
+            return IconKind::News;
+        }
+        if status.kernel_mismatch.is_some() {
+            return IconKind::RebootRequired;
+        }
+
+        match status.severity {
+            Severity::Normal if status.downloaded => IconKind::Downloaded,
+            severity => IconKind::for_severity(severity),
+        }
+    }
+
+    fn bytes(self) -> &'static [u8] {
+        match self {
+            IconKind::NoUpdates => NO_UPDATES_ICON_BYTES,
+            IconKind::Updates => UPDATES_ICON_BYTES,
+            IconKind::Warning => UPDATES_WARNING_LEVEL_ICON_BYTES,
+            IconKind::Critical => UPDATES_CRITICAL_LEVEL_ICON_BYTES,
+            IconKind::Checking => CHECKING_ICON_BYTES,
+            IconKind::Updating => UPDATING_ICON_BYTES,
+            IconKind::Downloaded => DOWNLOADED_ICON_BYTES,
+            IconKind::News => NEWS_ICON_BYTES,
+            IconKind::RebootRequired => REBOOT_REQUIRED_ICON_BYTES,
+            IconKind::Paused | IconKind::Offline => NO_UPDATES_ICON_BYTES,
+            IconKind::CheckFailed => UPDATES_WARNING_LEVEL_ICON_BYTES,
+        }
+    }
+
+    fn faded(self) -> bool {
+        matches!(self, IconKind::Paused | IconKind::Offline)
+    }
+}
+
+/// The submenus a status is rendered to, in menu order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuSection {
+    /// This machine's pacman updates.
+    Local,
+    Host(usize),
+    Source(usize),
+    /// Updates pacman.conf ignores, with `show_ignored_updates`.
+    Ignored,
+    /// Packages checkrebuild found.
+    Rebuild,
+    Statistics,
+}
+
+/// The menu items whose text changes at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuLabel {
+    UpdateNow,
+    MarkNewsRead,
+    LastChecked,
+    LastUpgraded,
+    /// How long the oldest pending update has been pending.
+    OldestPending,
+    /// Pauses or resumes checks.
+    Pause,
+}
+
+/// What the tray can be asked to show. Implemented by each backend and, in
+/// tests, by a recorder, so rendering can be checked without a session.
+pub trait TraySink {
+    fn set_icon(&mut self, icon: IconKind) -> Result<()>;
+    fn set_tooltip(&mut self, tooltip: &str) -> Result<()>;
+    fn set_submenu(
+        &mut self,
+        section: MenuSection,
+        title: &str,
+        lines: &[String],
+        enabled: bool,
+    ) -> Result<()>;
+    fn set_label(&mut self, label: MenuLabel, text: &str) -> Result<()>;
+    /// Shows one entry per advisory below the pending updates.
+    fn set_advisories(&mut self, messages: &[String]) -> Result<()>;
+}
+
+/// Formats how long ago something happened, e.g. "12 min ago".
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    if seconds < 60 {
+        t!("elapsed-just-now")
+    } else if seconds < 60 * 60 {
+        t!("elapsed-minutes", minutes = seconds / 60)
+    } else if seconds < 60 * 60 * 24 {
+        t!("elapsed-hours", hours = seconds / (60 * 60))
+    } else {
+        t!("elapsed-days", days = seconds / (60 * 60 * 24))
+    }
+}
+
+fn statistics_lines(status: &Status) -> Vec<String> {
+    let Some(statistics) = &status.statistics else {
+        return vec![t!("statistics-none")];
+    };
+
+    let mut lines = vec![t!(
+        "statistics-average",
+        average = format!("{:.1}", statistics.average_updates_per_week)
+    )];
+
+    if let Some(biggest_recent_batch) = statistics.biggest_recent_batch {
+        lines.push(t!("statistics-biggest-batch", count = biggest_recent_batch));
+    }
+
+    if !statistics.most_updated_packages.is_empty() {
+        lines.push(t!("statistics-most-updated"));
+        for (package, times) in &statistics.most_updated_packages {
+            lines.push(format!("    {} ({}×)", package, times));
+        }
+    }
+
+    lines
+}
+
+fn display_update_lines(updates: &[String], epoch_display: EpochDisplay) -> Vec<String> {
+    updates
+        .iter()
+        .map(|update| display_update_line(update, epoch_display))
+        .collect()
+}
+
+/// Renders the submenu of a remote host or an additional source, showing the
+/// error instead of the updates if the check failed.
+fn render_section(
+    sink: &mut impl TraySink,
+    section: MenuSection,
+    name: &str,
+    updates: &[String],
+    error: &Option<String>,
+    epoch_display: EpochDisplay,
+) -> Result<()> {
+    match error {
+        Some(error) => sink.set_submenu(
+            section,
+            &t!("section-check-failed", name = name),
+            std::slice::from_ref(error),
+            false,
+        ),
+        None => sink.set_submenu(
+            section,
+            &t!(
+                "section-pending-updates",
+                name = name,
+                count = updates.len()
+            ),
+            &display_update_lines(updates, epoch_display),
+            true,
+        ),
+    }
+}
+
+/// Renders the icon and the menu for the result of a check. `local_prefix`
+/// labels this machine's updates when there are remote hosts.
+fn render_status(
+    sink: &mut impl TraySink,
+    status: &Status,
+    epoch_display: EpochDisplay,
+    local_prefix: &str,
+) -> Result<()> {
+    render_state(sink, status)?;
+
+    let update_now = if status::keyring_pending(&status.updates) {
+        t!(
+            "update-now-keyring-first",
+            package = status::KEYRING_PACKAGE
+        )
+    } else {
+        t!("update-now")
+    };
+    sink.set_label(MenuLabel::UpdateNow, &update_now)?;
+
+    let mark_news_read = if status.unread_news.is_empty() {
+        t!("mark-news-read")
+    } else {
+        t!("mark-news-read-unread", count = status.unread_news.len())
+    };
+    sink.set_label(MenuLabel::MarkNewsRead, &mark_news_read)?;
+
+    let title = if status.downloaded {
+        t!(
+            "local-pending-updates-downloaded",
+            prefix = local_prefix,
+            count = status.updates.len()
+        )
+    } else {
+        t!(
+            "local-pending-updates",
+            prefix = local_prefix,
+            count = status.updates.len()
+        )
+    };
+    sink.set_submenu(
+        MenuSection::Local,
+        &title,
+        &display_update_lines(&status.updates, epoch_display),
+        true,
+    )?;
+
+    for (index, host) in status.hosts.iter().enumerate() {
+        render_section(
+            sink,
+            MenuSection::Host(index),
+            &host.name,
+            &host.updates,
+            &host.error,
+            epoch_display,
+        )?;
+    }
+
+    for (index, source) in status.sources.iter().enumerate() {
+        render_section(
+            sink,
+            MenuSection::Source(index),
+            source.name,
+            &source.updates,
+            &source.error,
+            epoch_display,
+        )?;
+    }
+
+    // Greyed out, since pacman won't install them.
+    sink.set_submenu(
+        MenuSection::Ignored,
+        &t!("ignored-updates", count = status.ignored_updates.len()),
+        &display_update_lines(&status.ignored_updates, epoch_display),
+        false,
+    )?;
+
+    sink.set_submenu(
+        MenuSection::Rebuild,
+        &t!("needs-rebuild", count = status.needs_rebuild.len()),
+        &status.needs_rebuild,
+        !status.needs_rebuild.is_empty(),
+    )?;
+
+    let advisories: Vec<String> = status
+        .advisories
+        .iter()
+        .map(|advisory| advisory.message.clone())
+        .collect();
+    sink.set_advisories(&advisories)?;
+
+    sink.set_submenu(
+        MenuSection::Statistics,
+        &t!("statistics"),
+        &statistics_lines(status),
+        false,
+    )
+}
+
+/// Renders the parts that follow the state: the icon and the pause entry.
+fn render_state(sink: &mut impl TraySink, status: &Status) -> Result<()> {
+    sink.set_icon(IconKind::for_status(status))?;
+    let pause = if status.state == AppState::Paused {
+        t!("resume-checks")
+    } else {
+        t!("pause-checks")
+    };
+    sink.set_label(MenuLabel::Pause, &pause)
+}
+
+/// Shows what the running upgrade is doing in the tooltip and in place of
+/// the pending update count, until the next check renders the status again.
+fn render_progress(
+    sink: &mut impl TraySink,
+    status: &Status,
+    progress: &str,
+    epoch_display: EpochDisplay,
+) -> Result<()> {
+    sink.set_icon(IconKind::Updating)?;
+    sink.set_tooltip(&format!("{}\n{}", t!("updating"), progress))?;
+    sink.set_submenu(
+        MenuSection::Local,
+        progress,
+        &display_update_lines(&status.updates, epoch_display),
+        true,
+    )
+}
+
+/// Renders the parts that show how long ago things happened: the "Last
+/// checked", "Last upgraded" and "Oldest pending" labels and the tooltip.
+fn render_elapsed(sink: &mut impl TraySink, status: &Status) -> Result<()> {
+    let last_checked_text = match status.last_checked {
+        Some(last_checked) => t!(
+            "last-checked",
+            elapsed = format_elapsed(elapsed_since(last_checked))
+        ),
+        None => t!("last-checked-never"),
+    };
+    sink.set_label(MenuLabel::LastChecked, &last_checked_text)?;
+
+    let last_upgraded_text = match status.last_upgrade {
+        Some(last_upgrade) => t!(
+            "last-upgraded",
+            elapsed = format_elapsed(elapsed_since(last_upgrade))
+        ),
+        None => t!("last-upgraded-unknown"),
+    };
+    sink.set_label(MenuLabel::LastUpgraded, &last_upgraded_text)?;
+
+    let oldest_pending_text = match status.oldest_pending {
+        Some(oldest_pending) if !status.updates.is_empty() => {
+            match elapsed_since(oldest_pending).as_secs() / (60 * 60 * 24) {
+                0 => t!("oldest-pending-today"),
+                days => t!("oldest-pending", days = days),
+            }
+        }
+        _ => t!("oldest-pending-none"),
+    };
+    sink.set_label(MenuLabel::OldestPending, &oldest_pending_text)?;
+
+    let count_text = if status.hosts.is_empty() {
+        t!("pending-updates", count = status.updates.len())
+    } else {
+        t!(
+            "pending-updates-total",
+            total = status.total_count(),
+            count = status.updates.len()
+        )
+    };
+    let mut tooltip = match &status.config_error {
+        Some(_) => format!("{}\n{}", t!("config-error"), count_text),
+        None => count_text,
+    };
+    let state_text = match &status.state {
+        AppState::Paused => Some(t!("state-paused")),
+        AppState::Offline => Some(t!("state-offline")),
+        AppState::Error { error } => Some(t!("state-error", error = error)),
+        _ => None,
+    };
+    if let Some(state_text) = state_text {
+        tooltip = format!("{}\n{}", state_text, tooltip);
+    }
+    if !status.unread_news.is_empty() && !status.updates.is_empty() {
+        tooltip.push_str(&format!("\n{}", t!("read-news-first")));
+        for item in &status.unread_news {
+            tooltip.push_str(&format!("\n    {}", item.title));
+        }
+    }
+    for line in breakdown_lines(status) {
+        tooltip.push_str(&format!("\n    {}", line));
+    }
+    if !status.partial_upgrade.is_empty() && !status.updates.is_empty() {
+        tooltip.push_str(&format!(
+            "\n{}",
+            t!(
+                "partial-upgrade",
+                packages = status.partial_upgrade.join(", ")
+            )
+        ));
+    }
+    if let Some(mismatch) = &status.kernel_mismatch {
+        tooltip.push_str(&format!(
+            "\n{}",
+            t!(
+                "reboot-required",
+                running = mismatch.running,
+                installed = mismatch.installed.join(", ")
+            )
+        ));
+    }
+    for advisory in &status.advisories {
+        tooltip.push_str(&format!("\n{}", advisory.message));
+    }
+    for failure in &status.thread_failures {
+        tooltip.push_str(&format!(
+            "\n{}",
+            t!(
+                "thread-restarted",
+                thread = failure.thread,
+                reason = failure.reason
+            )
+        ));
+    }
+    if status.downloaded {
+        tooltip.push_str(&format!("\n{}", t!("downloaded-ready")));
+    }
+    tooltip.push_str(&format!("\n{}\n{}", last_checked_text, last_upgraded_text));
+    sink.set_tooltip(&tooltip)
+}
+
+/// Per-host and per-source counts for the tooltip, when there is more than
+/// this machine's pacman count to show.
+fn breakdown_lines(status: &Status) -> Vec<String> {
+    let hosts = status.hosts.iter().map(|host| match host.error {
+        Some(_) => t!("section-check-failed", name = host.name),
+        None => format!("{}: {}", host.name, host.updates.len()),
+    });
+    let sources = status.sources.iter().map(|source| match source.error {
+        Some(_) => t!("section-check-failed", name = source.name),
+        None => format!("{}: {}", source.name, source.updates.len()),
+    });
+    hosts.chain(sources).collect()
+}
+
+/// The start of a menu snapshot, before the backend lists its entries.
+pub fn snapshot_header(status: &Status, config: &Config) -> String {
+    format!(
+        "arch-updates-rs {} menu snapshot\nstate: {}\nstatus: {:?}\nconfig: {:?}\n\nmenu:\n",
+        env!("CARGO_PKG_VERSION"),
+        status.state.as_str(),
+        status,
+        config
+    )
+}
+
+pub fn write_diagnostics_snapshot(contents: &str) -> Result<PathBuf> {
+    let diagnostics_dir = runtime_dir()?.join("arch-updates-rs-diagnostics");
+    std::fs::create_dir_all(&diagnostics_dir).context("Failed to create diagnostics directory")?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let snapshot_path = diagnostics_dir.join(format!("menu-{}.txt", timestamp));
+    std::fs::write(&snapshot_path, contents).context("Failed to write menu snapshot")?;
+
+    Ok(snapshot_path)
+}
+
+/// Runs a maintenance action in a terminal, after asking for confirmation
+/// unless the user turned it off for this action.
+fn run_maintenance_action(action: Action, config: &mut Config) {
+    // Copying the command instead changes nothing, so there is nothing to
+    // confirm.
+    if privileges::can_escalate() && config.needs_confirmation(action.id()) {
+        let details = match action.preview() {
+            Ok(details) => details,
+            Err(e) => t!("preview-failed", error = e),
+        };
+
+        match confirm::ask(&action.label(), action.command(), &details) {
+            Confirmation::Cancelled => return,
+            Confirmation::Confirmed { dont_ask_again } => {
+                if dont_ask_again {
+                    config.confirm.insert(action.id().to_string(), false);
+                    if let Err(e) = confirm::dont_ask_again(action.id()) {
+                        error!("Failed to save confirmation setting: {:#}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Err(e) = privileges::run(config, action.command()) {
+        error!("Failed to launch terminal for {}: {}", action.label(), e);
+    }
+}
+
+/// Runs what the menu entry `id` stands for, for the entries that work the
+/// same with every backend.
+pub fn run_menu_entry(id: &str, config: &mut Config, status: &Status, app_tx: &Sender<Event>) {
+    if id == UPDATE_NOW_MENU_ID {
+        if let Err(e) = privileges::run(config, &config.update_command_for(&status.updates)) {
+            error!("Failed to launch update terminal: {}", e);
+        }
+    } else if id == INSTALL_UPDATES_MENU_ID {
+        app_tx.send(Event::InstallUpdates).unwrap();
+    } else if id == MARK_NEWS_READ_MENU_ID {
+        app_tx.send(Event::MarkNewsRead).unwrap();
+    } else if id == ACKNOWLEDGE_ALL_MENU_ID {
+        app_tx.send(Event::AcknowledgeAll).unwrap();
+    } else if id == PAUSE_MENU_ID {
+        let event = if status.state == AppState::Paused {
+            Event::Resume
+        } else {
+            Event::Pause
+        };
+        app_tx.send(event).unwrap();
+    } else if id == SHOW_UPDATES_MENU_ID {
+        #[cfg(any(feature = "gtk", feature = "gtk4"))]
+        crate::updates_window::show(&status.updates, config);
+    } else if let Some(package) = id.strip_prefix(changelog::MENU_ID_PREFIX) {
+        if let Err(e) = changelog::show(config, package) {
+            error!("Failed to show the changelog of {}: {:#}", package, e);
+        }
+    } else if let Some(action) = Action::from_id(id) {
+        run_maintenance_action(action, config);
+    }
+}
+
+/// Saves the **Download updates in the background** toggle. The main loop
+/// picks the change up by reloading the config file. Returns whether it was
+/// saved.
+pub fn save_download_updates(config: &mut Config, enabled: bool) -> bool {
+    match config::write_option(None, "download_updates", enabled) {
+        Ok(()) => {
+            config.download_updates = enabled;
+            true
+        }
+        Err(e) => {
+            error!("Failed to save download_updates: {:#}", e);
+            false
+        }
+    }
+}
+
+/// Labels this machine's updates when there are remote hosts.
+pub fn local_prefix(config: &Config) -> String {
+    if config.remote_hosts.is_empty() {
+        String::new()
+    } else {
+        format!("{}: ", hostname())
+    }
+}
+
+/// What a tray backend keeps between state updates.
+pub struct TrayState {
+    pub status: Status,
+    last_checked_refreshed_at: Option<Instant>,
+    local_prefix: String,
+}
+
+impl TrayState {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            status: Status::default(),
+            last_checked_refreshed_at: None,
+            local_prefix: local_prefix(config),
+        }
+    }
+
+    /// Renders `update`, or takes over a reloaded `config`. Returns
+    /// `Ok(false)` once the app shuts down.
+    pub fn apply(
+        &mut self,
+        sink: &mut impl TraySink,
+        update: StateUpdate,
+        config: &mut Config,
+    ) -> Result<bool> {
+        match update {
+            StateUpdate::State(state) => {
+                self.status.state = state;
+                // The tooltip mentions some states.
+                self.last_checked_refreshed_at = None;
+                render_state(sink, &self.status)?;
+            }
+            StateUpdate::Status(status) => {
+                self.status = *status;
+                self.last_checked_refreshed_at = None;
+                render_status(sink, &self.status, config.epoch_display, &self.local_prefix)?;
+                info!("Updated tray icon");
+            }
+            StateUpdate::Progress(progress) => {
+                // Keep the periodic refresh from replacing the progress.
+                self.last_checked_refreshed_at = Some(Instant::now());
+                render_progress(sink, &self.status, &progress, config.epoch_display)?;
+            }
+            // Shown with the status that follows.
+            StateUpdate::UpgradeDetected(_) => {}
+            StateUpdate::Config(new_config) => {
+                self.local_prefix = local_prefix(&new_config);
+                *config = *new_config;
+            }
+            StateUpdate::Shutdown => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Renders the parts that show how long ago things happened again, every
+    /// `tooltip_secs`.
+    pub fn refresh_elapsed(&mut self, sink: &mut impl TraySink, config: &Config) {
+        if self.status.last_checked.is_some()
+            && self.last_checked_refreshed_at.is_none_or(|refreshed_at| {
+                refreshed_at.elapsed() >= Duration::from_secs(config.throttle.tooltip_secs)
+            })
+        {
+            if let Err(e) = render_elapsed(sink, &self.status) {
+                error!("{:#}", e);
+            }
+
+            self.last_checked_refreshed_at = Some(Instant::now());
+        }
+    }
+}
+
+/// Runs `show` until the tray icon could be shown, waiting longer after each
+/// failure. Started early in the session, the display or the panel may not be
+/// up yet. Checks go on meanwhile, and the tray catches up on the queued
+/// state updates once it is shown. Returns `None` once the app shuts down.
+pub fn retry_until_shown<T>(mut show: impl FnMut() -> Result<T>) -> Option<T> {
+    let mut delay = MIN_SHOW_RETRY_DELAY;
+    loop {
+        match show() {
+            Ok(shown) => return Some(shown),
+            Err(e) => warn!("{:#}, trying again in {} seconds", e, delay.as_secs()),
+        }
+        if supervisor::sleep(delay) {
+            return None;
+        }
+        delay = (delay * 2).min(MAX_SHOW_RETRY_DELAY);
+    }
+}
+
+/// Starts the tray thread, which shows the `updates` and sends the user's
+/// commands to `app_tx`.
+pub fn setup_tray_icon(
+    config: Config,
+    app_tx: Sender<Event>,
+    updates: Receiver<StateUpdate>,
+) -> Option<JoinHandle<()>> {
+    let (color_scheme_tx, color_scheme_rx) = channel::<ColorScheme>();
+
+    if config.icon.theme == IconTheme::Auto {
+        appearance::watch_color_scheme(color_scheme_tx);
+    }
+
+    // Kept across restarts, so that no update gets lost.
+    let updates = Arc::new(Mutex::new(updates));
+    let color_schemes = Arc::new(Mutex::new(color_scheme_rx));
+    supervisor::supervise("tray", app_tx.clone(), move || {
+        let _span = info_span!("tray").entered();
+
+        let config = config.clone();
+        let app_tx = app_tx.clone();
+        let updates = updates.clone();
+        let color_schemes = color_schemes.clone();
+
+        match config.tray_backend {
+            #[cfg(feature = "gtk")]
+            TrayBackend::Gtk => crate::gtk_tray::run(config, app_tx, updates, color_schemes),
+            #[cfg(not(feature = "gtk"))]
+            TrayBackend::Gtk => {
+                warn!("Built without GTK 3, showing the tray icon over D-Bus instead");
+                sni::run(config, app_tx, updates, color_schemes);
+            }
+            TrayBackend::Sni => sni::run(config, app_tx, updates, color_schemes),
+            #[cfg(any(feature = "layer-shell", feature = "layer-shell-gtk4"))]
+            TrayBackend::Widget => crate::widget::run(config, app_tx, updates, color_schemes),
+            #[cfg(all(feature = "gtk", not(feature = "layer-shell")))]
+            TrayBackend::Widget => {
+                warn!("Built without layer-shell, showing a tray icon instead of the widget");
+                crate::gtk_tray::run(config, app_tx, updates, color_schemes);
+            }
+            #[cfg(all(not(feature = "gtk"), not(feature = "layer-shell-gtk4")))]
+            TrayBackend::Widget => {
+                warn!("Built without layer-shell, showing a tray icon instead of the widget");
+                sni::run(config, app_tx, updates, color_schemes);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        news::NewsItem, remote::HostStatus, sources::SourceStatus, state::Transition,
+        supervisor::ThreadFailure,
+    };
+
+    /// Everything the tray was asked to show, in order.
+    #[derive(Debug, PartialEq)]
+    enum Rendered {
+        Icon(IconKind),
+        Tooltip(String),
+        Submenu {
+            section: MenuSection,
+            title: String,
+            lines: Vec<String>,
+            enabled: bool,
+        },
+        Label(MenuLabel, String),
+        Advisories(Vec<String>),
+    }
+
+    /// A tray that records what it was asked to render instead of drawing it.
+    #[derive(Default)]
+    struct VirtualTray {
+        rendered: Vec<Rendered>,
+    }
+
+    impl VirtualTray {
+        fn icon(&self) -> Option<IconKind> {
+            self.rendered
+                .iter()
+                .rev()
+                .find_map(|rendered| match rendered {
+                    Rendered::Icon(icon) => Some(*icon),
+                    _ => None,
+                })
+        }
+
+        fn submenu(&self, section: MenuSection) -> Option<(&str, &[String], bool)> {
+            self.rendered
+                .iter()
+                .rev()
+                .find_map(|rendered| match rendered {
+                    Rendered::Submenu {
+                        section: rendered_section,
+                        title,
+                        lines,
+                        enabled,
+                    } if *rendered_section == section => {
+                        Some((title.as_str(), lines.as_slice(), *enabled))
+                    }
+                    _ => None,
+                })
+        }
+
+        fn tooltip(&self) -> Option<&str> {
+            self.rendered
+                .iter()
+                .rev()
+                .find_map(|rendered| match rendered {
+                    Rendered::Tooltip(tooltip) => Some(tooltip.as_str()),
+                    _ => None,
+                })
+        }
+    }
+
+    impl TraySink for VirtualTray {
+        fn set_icon(&mut self, icon: IconKind) -> Result<()> {
+            self.rendered.push(Rendered::Icon(icon));
+            Ok(())
+        }
+
+        fn set_tooltip(&mut self, tooltip: &str) -> Result<()> {
+            self.rendered.push(Rendered::Tooltip(tooltip.to_string()));
+            Ok(())
+        }
+
+        fn set_submenu(
+            &mut self,
+            section: MenuSection,
+            title: &str,
+            lines: &[String],
+            enabled: bool,
+        ) -> Result<()> {
+            self.rendered.push(Rendered::Submenu {
+                section,
+                title: title.to_string(),
+                lines: lines.to_vec(),
+                enabled,
+            });
+            Ok(())
+        }
+
+        fn set_label(&mut self, label: MenuLabel, text: &str) -> Result<()> {
+            self.rendered.push(Rendered::Label(label, text.to_string()));
+            Ok(())
+        }
+
+        fn set_advisories(&mut self, messages: &[String]) -> Result<()> {
+            self.rendered.push(Rendered::Advisories(messages.to_vec()));
+            Ok(())
+        }
+    }
+
+    fn updates(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|index| format!("package-{} 1.0-1 -> 1.1-1", index))
+            .collect()
+    }
+
+    #[test]
+    fn shows_no_updates_icon_when_up_to_date() {
+        let mut tray = VirtualTray::default();
+        let status = Status::new(vec![], None, None, vec![], vec![], &Config::default());
+
+        render_status(&mut tray, &status, EpochDisplay::Show, "").unwrap();
+
+        assert_eq!(tray.icon(), Some(IconKind::NoUpdates));
+        assert_eq!(
+            tray.submenu(MenuSection::Local),
+            Some(("0 pending updates", &[][..], true))
+        );
+    }
+
+    #[test]
+    fn shows_the_icon_of_the_severity() {
+        let cases = [
+            (Severity::Normal, IconKind::Updates),
+            (Severity::Warning, IconKind::Warning),
+            (Severity::Critical, IconKind::Critical),
+        ];
+
+        for (severity, icon) in cases {
+            let mut tray = VirtualTray::default();
+            let mut status =
+                Status::new(updates(3), None, None, vec![], vec![], &Config::default());
+            status.severity = severity;
+
+            render_status(&mut tray, &status, EpochDisplay::Show, "").unwrap();
+
+            assert_eq!(tray.icon(), Some(icon), "{:?}", severity);
+        }
+    }
+
+    #[test]
+    fn lists_updates_in_the_local_submenu() {
+        let mut tray = VirtualTray::default();
+        let status = Status::new(
+            vec!["linux 1:6.9.1-1 -> 1:6.9.2-1".to_string()],
+            None,
+            None,
+            vec![],
+            vec![],
+            &Config::default(),
+        );
+
+        render_status(&mut tray, &status, EpochDisplay::Hide, "laptop: ").unwrap();
+
+        assert_eq!(
+            tray.submenu(MenuSection::Local),
+            Some((
+                "laptop: 1 pending update",
+                &["linux 6.9.1-1 -> 6.9.2-1".to_string()][..],
+                true
+            ))
+        );
+    }
+
+    #[test]
+    fn greys_out_ignored_updates_without_counting_them() {
+        let mut tray = VirtualTray::default();
+        let mut status = Status::new(updates(1), None, None, vec![], vec![], &Config::default());
+        status.ignored_updates = vec!["linux 6.9.1-1 -> 6.9.2-1".to_string()];
+
+        render_status(&mut tray, &status, EpochDisplay::Show, "").unwrap();
+
+        assert_eq!(
+            tray.submenu(MenuSection::Local).map(|(title, _, _)| title),
+            Some("1 pending update")
+        );
+        assert_eq!(
+            tray.submenu(MenuSection::Ignored),
+            Some((
+                "Ignored by pacman.conf: 1",
+                &["linux 6.9.1-1 -> 6.9.2-1".to_string()][..],
+                false
+            ))
+        );
+    }
+
+    #[test]
+    fn shows_failed_hosts_and_sources_with_their_error() {
+        let mut tray = VirtualTray::default();
+        let hosts = vec![HostStatus {
+            name: "nas".to_string(),
+            updates: vec![],
+            severity: Severity::None,
+            error: Some("Connection refused".to_string()),
+        }];
+        let sources = vec![SourceStatus {
+            id: "snap",
+            name: "Snaps",
+            updates: updates(2),
+            error: None,
+        }];
+        let status = Status::new(vec![], None, None, hosts, sources, &Config::default());
+
+        render_status(&mut tray, &status, EpochDisplay::Show, "laptop: ").unwrap();
+        render_elapsed(&mut tray, &status).unwrap();
+
+        assert_eq!(
+            tray.submenu(MenuSection::Host(0)),
+            Some((
+                "nas: check failed",
+                &["Connection refused".to_string()][..],
+                false
+            ))
+        );
+        assert_eq!(
+            tray.submenu(MenuSection::Source(0))
+                .map(|(title, _, _)| title),
+            Some("Snaps: 2 pending updates")
+        );
+        assert_eq!(
+            tray.tooltip(),
+            Some(
+                "0 pending updates (0 on this machine)\n    nas: check failed\n    Snaps: 2\nLast checked: just now\nLast upgraded: unknown"
+            )
+        );
+    }
+
+    #[test]
+    fn shows_upgrade_progress_instead_of_the_count() {
+        let mut tray = VirtualTray::default();
+        let status = Status::new(updates(2), None, None, vec![], vec![], &Config::default());
+
+        render_progress(
+            &mut tray,
+            &status,
+            "Installing 1/2: package-0",
+            EpochDisplay::Show,
+        )
+        .unwrap();
+
+        assert_eq!(tray.icon(), Some(IconKind::Updating));
+        assert_eq!(tray.tooltip(), Some("Updating\nInstalling 1/2: package-0"));
+        assert_eq!(
+            tray.submenu(MenuSection::Local).map(|(title, _, _)| title),
+            Some("Installing 1/2: package-0")
+        );
+    }
+
+    #[test]
+    fn holds_updates_back_until_the_news_are_read() {
+        let mut tray = VirtualTray::default();
+        let mut status = Status::new(updates(2), None, None, vec![], vec![], &Config::default());
+        status.downloaded = true;
+        status.unread_news = vec![NewsItem {
+            title: "Manual intervention required".to_string(),
+            link: String::new(),
+            published: chrono::Utc::now(),
+        }];
+
+        render_status(&mut tray, &status, EpochDisplay::Show, "").unwrap();
+        assert_eq!(tray.icon(), Some(IconKind::News));
+
+        status.unread_news.clear();
+        render_status(&mut tray, &status, EpochDisplay::Show, "").unwrap();
+        assert_eq!(tray.icon(), Some(IconKind::Downloaded));
+    }
+
+    #[test]
+    fn warns_about_partial_upgrades() {
+        let mut tray = VirtualTray::default();
+        let mut status = Status::new(updates(2), None, None, vec![], vec![], &Config::default());
+        status.partial_upgrade = vec!["firefox".to_string()];
+
+        render_elapsed(&mut tray, &status).unwrap();
+
+        assert!(tray.tooltip().is_some_and(|tooltip| {
+            tooltip.contains("Partial upgrade: firefox installed without a full upgrade")
+        }));
+    }
+
+    #[test]
+    fn flags_config_errors_in_the_tooltip() {
+        let mut tray = VirtualTray::default();
+        let mut status = Status::new(vec![], None, None, vec![], vec![], &Config::default());
+        status.config_error = Some("invalid type".to_string());
+
+        render_elapsed(&mut tray, &status).unwrap();
+
+        assert!(
+            tray.tooltip()
+                .is_some_and(|tooltip| tooltip.starts_with("Config error"))
+        );
+    }
+
+    #[test]
+    fn reports_restarted_threads_in_the_tooltip() {
+        let mut tray = VirtualTray::default();
+        let mut status = Status::new(vec![], None, None, vec![], vec![], &Config::default());
+        status.thread_failures.push(ThreadFailure {
+            thread: "watcher".to_string(),
+            reason: "it returned".to_string(),
+        });
+
+        render_elapsed(&mut tray, &status).unwrap();
+
+        assert!(tray.tooltip().is_some_and(|tooltip| {
+            tooltip.contains("The watcher thread was restarted because it returned")
+        }));
+    }
+
+    #[test]
+    fn derives_the_icon_and_pause_entry_from_the_state() {
+        let mut tray = VirtualTray::default();
+        let mut status = Status::new(updates(3), None, None, vec![], vec![], &Config::default());
+        status.state = AppState::UpdatesAvailable { count: 3 };
+
+        assert!(status.transition(Transition::Pause));
+        render_state(&mut tray, &status).unwrap();
+        render_elapsed(&mut tray, &status).unwrap();
+
+        assert_eq!(tray.icon(), Some(IconKind::Paused));
+        assert!(tray.rendered.contains(&Rendered::Label(
+            MenuLabel::Pause,
+            "Resume checks".to_string()
+        )));
+        assert!(
+            tray.tooltip()
+                .is_some_and(|tooltip| tooltip.starts_with("Checks are paused"))
+        );
+
+        status.state = AppState::Error {
+            error: "mirror unreachable".to_string(),
+        };
+        render_state(&mut tray, &status).unwrap();
+
+        assert_eq!(tray.icon(), Some(IconKind::CheckFailed));
+    }
+}
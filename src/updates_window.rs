@@ -0,0 +1,204 @@
+use tracing::error;
+
+use crate::{config::Config, filters, i18n::t};
+
+/// Whether the update line `update` ("name old -> new") from the repository
+/// `repo` matches what was typed into the search entry. Every word of
+/// `query` has to be part of the name, one of the versions or the
+/// repository, ignoring case.
+pub fn matches(update: &str, repo: Option<&str>, query: &str) -> bool {
+    let fields: Vec<String> = update
+        .split_whitespace()
+        .filter(|field| *field != "->")
+        .chain(repo)
+        .map(str::to_lowercase)
+        .collect();
+
+    query
+        .to_lowercase()
+        .split_whitespace()
+        .all(|term| fields.iter().any(|field| field.contains(term)))
+}
+
+/// One line of the window: the update and the repository it comes from.
+struct Row {
+    update: String,
+    repo: Option<String>,
+}
+
+impl Row {
+    fn text(&self) -> String {
+        match &self.repo {
+            Some(repo) => format!("{}/{}", repo, self.update),
+            None => self.update.clone(),
+        }
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        matches(&self.update, self.repo.as_deref(), query)
+    }
+}
+
+fn rows(updates: &[String], config: &Config) -> Vec<Row> {
+    let repos = match filters::package_repos(&config.pacman_db_path()) {
+        Ok(repos) => Some(repos),
+        Err(e) => {
+            // Still searchable by name and version.
+            error!("Failed to look up the repositories of updates: {:#}", e);
+            None
+        }
+    };
+
+    updates
+        .iter()
+        .map(|update| Row {
+            update: update.clone(),
+            repo: update
+                .split_whitespace()
+                .next()
+                .and_then(|name| repos.as_ref()?.get(name))
+                .cloned(),
+        })
+        .collect()
+}
+
+/// Opens a window listing `updates`, with a search entry that hides the ones
+/// not matching while typing. Must be called on the tray thread.
+#[cfg(feature = "gtk")]
+pub fn show(updates: &[String], config: &Config) {
+    use gtk::prelude::*;
+
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title(&t!("updates-window-title", count = updates.len()));
+    window.set_default_size(480, 420);
+
+    let search_entry = gtk::SearchEntry::new();
+    search_entry.set_placeholder_text(Some(&t!("updates-window-search")));
+
+    let list = gtk::ListBox::new();
+    let mut list_rows = Vec::new();
+    for row in rows(updates, config) {
+        let label = gtk::Label::new(Some(&row.text()));
+        label.set_xalign(0.0);
+        label.set_selectable(true);
+        let list_row = gtk::ListBoxRow::new();
+        list_row.add(&label);
+        list.add(&list_row);
+        list_rows.push((list_row, row));
+    }
+
+    search_entry.connect_search_changed(move |entry| {
+        let query = entry.text();
+        for (list_row, row) in &list_rows {
+            list_row.set_visible(row.matches(query.as_str()));
+        }
+    });
+
+    let scrolled_window =
+        gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    scrolled_window.set_vexpand(true);
+    scrolled_window.add(&list);
+
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    content.pack_start(&search_entry, false, false, 0);
+    content.pack_start(&scrolled_window, true, true, 0);
+    window.add(&content);
+
+    window.show_all();
+    window.present();
+}
+
+/// Opens a window listing `updates`, with a search entry that hides the ones
+/// not matching while typing. The window is shown on the GTK thread.
+#[cfg(feature = "gtk4")]
+pub fn show(updates: &[String], config: &Config) {
+    let title = t!("updates-window-title", count = updates.len());
+    let placeholder = t!("updates-window-search");
+    let rows = rows(updates, config);
+
+    let result = crate::gtk4_thread::invoke(move || {
+        use gtk4::prelude::*;
+
+        let window = gtk4::Window::builder()
+            .title(title)
+            .default_width(480)
+            .default_height(420)
+            .build();
+
+        let search_entry = gtk4::SearchEntry::builder()
+            .placeholder_text(placeholder)
+            .build();
+
+        let list = gtk4::ListBox::new();
+        let mut list_rows = Vec::new();
+        for row in rows {
+            let label = gtk4::Label::new(Some(&row.text()));
+            label.set_xalign(0.0);
+            label.set_selectable(true);
+            let list_row = gtk4::ListBoxRow::new();
+            list_row.set_child(Some(&label));
+            list.append(&list_row);
+            list_rows.push((list_row, row));
+        }
+
+        search_entry.connect_search_changed(move |entry| {
+            let query = entry.text();
+            for (list_row, row) in &list_rows {
+                list_row.set_visible(row.matches(query.as_str()));
+            }
+        });
+
+        let scrolled_window = gtk4::ScrolledWindow::new();
+        scrolled_window.set_vexpand(true);
+        scrolled_window.set_child(Some(&list));
+
+        let content = gtk4::Box::new(gtk4::Orientation::Vertical, 6);
+        content.append(&search_entry);
+        content.append(&scrolled_window);
+        window.set_child(Some(&content));
+
+        window.present();
+    });
+    if let Err(e) = result {
+        error!("Failed to show the updates: {:#}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_name_repo_or_version() {
+        let update = "linux-firmware 20250508-1 -> 20250613-1";
+        let cases = [
+            ("", true),
+            ("linux", true),
+            ("FIRMWARE", true),
+            ("core", true),
+            ("20250613", true),
+            ("0508", true),
+            ("firmware core", true),
+            ("firmware extra", false),
+            ("-1", true),
+            ("mesa", false),
+        ];
+
+        for (query, expected) in cases {
+            assert_eq!(
+                matches(update, Some("core"), query),
+                expected,
+                "query {:?}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn matches_without_a_known_repo() {
+        let update = "yay 12.4.2-1 -> 12.5.0-1";
+
+        assert!(matches(update, None, "yay 12.5"));
+        assert!(!matches(update, None, "aur"));
+    }
+}
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+/// How the epoch of a version (the `2:` in `2:1.4-1`) is shown in the menu.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EpochDisplay {
+    #[default]
+    Show,
+    Hide,
+    /// Moves the epoch behind the version: `1.4-1 (epoch 2)`.
+    Subtle,
+}
+
+/// Rewrites the versions of a checkupdates line (`name old -> new`) for display.
+pub fn display_update_line(line: &str, epoch_display: EpochDisplay) -> String {
+    if epoch_display == EpochDisplay::Show {
+        return line.to_string();
+    }
+
+    line.split(' ')
+        .map(|word| display_version(word, epoch_display))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn display_version(word: &str, epoch_display: EpochDisplay) -> String {
+    let Some((epoch, version)) = word.split_once(':') else {
+        return word.to_string();
+    };
+
+    if epoch.is_empty() || !epoch.chars().all(|c| c.is_ascii_digit()) {
+        return word.to_string();
+    }
+
+    match epoch_display {
+        EpochDisplay::Show => word.to_string(),
+        EpochDisplay::Hide => version.to_string(),
+        EpochDisplay::Subtle => format!("{} (epoch {})", version, epoch),
+    }
+}
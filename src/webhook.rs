@@ -0,0 +1,76 @@
+use std::{thread, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    hostname,
+    status::{Severity, Status},
+};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: Option<String>,
+    /// Only post when the severity level changes, not on every count change.
+    pub only_on_threshold: bool,
+}
+
+#[derive(Serialize)]
+struct Payload {
+    host: String,
+    count: usize,
+    previous_count: usize,
+    severity: Severity,
+    previous_severity: Severity,
+    updates: Vec<String>,
+    last_checked: Option<DateTime<Utc>>,
+}
+
+/// Posts the new state to the configured webhook if the update count changed
+/// or the severity crossed a threshold.
+pub fn notify_change(config: &WebhookConfig, previous: &Status, current: &Status) {
+    let Some(url) = config.url.clone() else {
+        return;
+    };
+
+    let count_changed = previous.updates.len() != current.updates.len();
+    let severity_changed = previous.severity != current.severity;
+
+    if !severity_changed && (config.only_on_threshold || !count_changed) {
+        return;
+    }
+
+    let payload = Payload {
+        host: hostname(),
+        count: current.updates.len(),
+        previous_count: previous.updates.len(),
+        severity: current.severity,
+        previous_severity: previous.severity,
+        updates: current.updates.clone(),
+        last_checked: current.last_checked,
+    };
+
+    let body = match serde_json::to_string(&payload) {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Failed to serialize webhook payload: {}", e);
+            return;
+        }
+    };
+
+    thread::spawn(move || {
+        let agent = ureq::AgentBuilder::new().timeout(WEBHOOK_TIMEOUT).build();
+        match agent
+            .post(&url)
+            .set("Content-Type", "application/json")
+            .send_string(&body)
+        {
+            Ok(_) => info!("Posted update state to webhook"),
+            Err(e) => error!("Failed to post to webhook: {}", e),
+        }
+    });
+}
@@ -0,0 +1,481 @@
+use std::sync::{Arc, Mutex, mpsc::Receiver, mpsc::Sender, mpsc::channel};
+
+use anyhow::Result;
+#[cfg(feature = "layer-shell")]
+use gtk_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
+#[cfg(feature = "layer-shell-gtk4")]
+use gtk4_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
+use tracing::{error, warn};
+
+use crate::{
+    Event,
+    appearance::ColorScheme,
+    bus::StateUpdate,
+    config::Config,
+    status::Status,
+    tray::{
+        Corner, IconKind, MenuLabel, MenuSection, TraySink, TrayState, UPDATE_NOW_MENU_ID,
+        run_menu_entry,
+    },
+    updates_window,
+};
+
+/// Sets the widget apart from the labels of other windows.
+const WIDGET_CLASS: &str = "arch-updates-rs";
+
+/// The colors of the widget, one style class per state.
+const CSS: &str = "
+.arch-updates-rs { padding: 2px 8px; border-radius: 8px; font-weight: bold; color: #ffffff; }
+.arch-updates-rs.no-updates, .arch-updates-rs.downloaded { background-color: #26a269; }
+.arch-updates-rs.updates { background-color: #1c71d8; }
+.arch-updates-rs.warning, .arch-updates-rs.check-failed { background-color: #e5a50a; }
+.arch-updates-rs.critical { background-color: #c01c28; }
+.arch-updates-rs.news, .arch-updates-rs.reboot-required { background-color: #c64600; }
+.arch-updates-rs.checking, .arch-updates-rs.updating { background-color: #613583; }
+.arch-updates-rs.paused, .arch-updates-rs.offline { background-color: #5e5c64; }
+";
+
+/// The namespace compositor rules can match the widget's layer surface by.
+const NAMESPACE: &str = "arch-updates-rs";
+
+const PRIMARY_BUTTON: u32 = 1;
+const MIDDLE_BUTTON: u32 = 2;
+const SECONDARY_BUTTON: u32 = 3;
+
+/// The style class that colors the widget for `icon`.
+fn style_class(icon: IconKind) -> &'static str {
+    match icon {
+        IconKind::NoUpdates => "no-updates",
+        IconKind::Updates => "updates",
+        IconKind::Warning => "warning",
+        IconKind::Critical => "critical",
+        IconKind::Checking => "checking",
+        IconKind::Updating => "updating",
+        IconKind::Downloaded => "downloaded",
+        IconKind::News => "news",
+        IconKind::RebootRequired => "reboot-required",
+        IconKind::Paused => "paused",
+        IconKind::Offline => "offline",
+        IconKind::CheckFailed => "check-failed",
+    }
+}
+
+/// The edges of the screen the widget is anchored to.
+fn edges(corner: Corner) -> [Edge; 2] {
+    match corner {
+        Corner::TopLeft => [Edge::Top, Edge::Left],
+        Corner::TopRight => [Edge::Top, Edge::Right],
+        Corner::BottomLeft => [Edge::Bottom, Edge::Left],
+        Corner::BottomRight => [Edge::Bottom, Edge::Right],
+    }
+}
+
+/// Clicking the widget shows the pending updates, a middle click checks and
+/// a right click runs **Update now**.
+fn handle_click(button: u32, config: &mut Config, status: &Status, app_tx: &Sender<Event>) {
+    match button {
+        PRIMARY_BUTTON => updates_window::show(&status.updates, config),
+        MIDDLE_BUTTON => app_tx.send(Event::Checking).unwrap(),
+        SECONDARY_BUTTON => run_menu_entry(UPDATE_NOW_MENU_ID, config, status, app_tx),
+        _ => {}
+    }
+}
+
+/// This machine's update count, colored by the state. Hosts, sources and
+/// the rest of the status are only in the tooltip, there is no menu.
+#[cfg(feature = "layer-shell")]
+struct Widget {
+    window: gtk::Window,
+    label: gtk::Label,
+    current_icon: IconKind,
+}
+
+#[cfg(feature = "layer-shell")]
+impl Drop for Widget {
+    /// Takes the widget off the screen when the tray thread is restarted.
+    fn drop(&mut self) {
+        use gtk::prelude::*;
+
+        self.window.close();
+    }
+}
+
+#[cfg(feature = "layer-shell")]
+impl TraySink for Widget {
+    fn set_icon(&mut self, icon: IconKind) -> Result<()> {
+        use gtk::prelude::*;
+
+        let style_context = self.label.style_context();
+        style_context.remove_class(style_class(self.current_icon));
+        style_context.add_class(style_class(icon));
+        self.current_icon = icon;
+        Ok(())
+    }
+
+    fn set_tooltip(&mut self, tooltip: &str) -> Result<()> {
+        use gtk::prelude::*;
+
+        self.window.set_tooltip_text(Some(tooltip));
+        Ok(())
+    }
+
+    fn set_submenu(
+        &mut self,
+        section: MenuSection,
+        _title: &str,
+        lines: &[String],
+        _enabled: bool,
+    ) -> Result<()> {
+        use gtk::prelude::*;
+
+        if section == MenuSection::Local {
+            self.label.set_text(&lines.len().to_string());
+        }
+        Ok(())
+    }
+
+    fn set_label(&mut self, _label: MenuLabel, _text: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_advisories(&mut self, _messages: &[String]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Shows the widget with gtk-layer-shell until the app shuts down, or until
+/// it fails and has to be restarted. Falls back to the GTK tray icon if the
+/// compositor doesn't support the layer shell.
+#[cfg(feature = "layer-shell")]
+pub fn run(
+    mut config: Config,
+    app_tx: Sender<Event>,
+    updates: Arc<Mutex<Receiver<StateUpdate>>>,
+    color_schemes: Arc<Mutex<Receiver<ColorScheme>>>,
+) {
+    use std::time::Duration;
+
+    use anyhow::Context;
+    use gtk::prelude::*;
+
+    use crate::tray::retry_until_shown;
+
+    if retry_until_shown(|| gtk::init().context("Failed to initialize GTK")).is_none() {
+        return;
+    }
+
+    if !gtk_layer_shell::is_supported() {
+        warn!("The compositor doesn't support the layer shell, showing a tray icon instead");
+        crate::gtk_tray::run(config, app_tx, updates, color_schemes);
+        return;
+    }
+
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.init_layer_shell();
+    window.set_layer(Layer::Overlay);
+    window.set_keyboard_mode(KeyboardMode::None);
+    window.set_namespace(NAMESPACE);
+    for edge in edges(config.widget.corner) {
+        window.set_anchor(edge, true);
+        window.set_layer_shell_margin(edge, config.widget.margin);
+    }
+
+    let css_provider = gtk::CssProvider::new();
+    if let Err(e) = css_provider.load_from_data(CSS.as_bytes()) {
+        error!("Failed to load the widget style: {}", e);
+        return;
+    }
+
+    let label = gtk::Label::new(Some("0"));
+    let style_context = label.style_context();
+    style_context.add_provider(&css_provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+    style_context.add_class(WIDGET_CLASS);
+    style_context.add_class(style_class(IconKind::NoUpdates));
+
+    let (clicks_tx, clicks) = channel();
+    let event_box = gtk::EventBox::new();
+    event_box.connect_button_press_event(move |_, event| {
+        let _ = clicks_tx.send(event.button());
+        gtk::glib::Propagation::Stop
+    });
+    event_box.add(&label);
+    window.add(&event_box);
+    window.show_all();
+
+    let mut widget = Widget {
+        window,
+        label,
+        current_icon: IconKind::NoUpdates,
+    };
+
+    let mut state = TrayState::new(&config);
+
+    glib::timeout_add_local(Duration::from_millis(100), move || {
+        while let Ok(button) = clicks.try_recv() {
+            handle_click(button, &mut config, &state.status, &app_tx);
+        }
+
+        // The colors don't depend on a panel's color scheme.
+        while color_schemes.lock().unwrap().try_recv().is_ok() {}
+
+        while let Ok(update) = updates.lock().unwrap().try_recv() {
+            match state.apply(&mut widget, update, &mut config) {
+                Ok(true) => {}
+                Ok(false) => {
+                    gtk::main_quit();
+                    return glib::ControlFlow::Break;
+                }
+                Err(e) => {
+                    // Ends this run of the tray thread, so that it is
+                    // restarted.
+                    error!("Failed to update widget: {:#}", e);
+                    gtk::main_quit();
+                    return glib::ControlFlow::Break;
+                }
+            }
+        }
+
+        state.refresh_elapsed(&mut widget, &config);
+
+        glib::ControlFlow::Continue
+    });
+
+    gtk::main();
+}
+
+#[cfg(feature = "layer-shell-gtk4")]
+thread_local! {
+    /// The window of the widget and its label, on the GTK thread.
+    static WIDGET: std::cell::RefCell<Option<(gtk4::Window, gtk4::Label)>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Stands in for the widget on the tray thread and hands every change to the
+/// GTK thread, which owns the window.
+#[cfg(feature = "layer-shell-gtk4")]
+struct Widget {
+    current_icon: IconKind,
+}
+
+#[cfg(feature = "layer-shell-gtk4")]
+impl Widget {
+    fn change(&self, f: impl FnOnce(&gtk4::Window, &gtk4::Label) + Send + 'static) -> Result<()> {
+        crate::gtk4_thread::invoke(move || {
+            WIDGET.with_borrow(|widget| {
+                if let Some((window, label)) = widget {
+                    f(window, label);
+                }
+            })
+        })
+    }
+}
+
+#[cfg(feature = "layer-shell-gtk4")]
+impl Drop for Widget {
+    /// Takes the widget off the screen when the tray thread is restarted.
+    fn drop(&mut self) {
+        use gtk4::prelude::*;
+
+        let _ = crate::gtk4_thread::invoke(|| {
+            if let Some((window, _)) = WIDGET.take() {
+                window.close();
+            }
+        });
+    }
+}
+
+#[cfg(feature = "layer-shell-gtk4")]
+impl TraySink for Widget {
+    fn set_icon(&mut self, icon: IconKind) -> Result<()> {
+        use gtk4::prelude::*;
+
+        let previous = style_class(self.current_icon);
+        self.current_icon = icon;
+        self.change(move |_, label| {
+            label.remove_css_class(previous);
+            label.add_css_class(style_class(icon));
+        })
+    }
+
+    fn set_tooltip(&mut self, tooltip: &str) -> Result<()> {
+        use gtk4::prelude::*;
+
+        let tooltip = tooltip.to_string();
+        self.change(move |window, _| window.set_tooltip_text(Some(&tooltip)))
+    }
+
+    fn set_submenu(
+        &mut self,
+        section: MenuSection,
+        _title: &str,
+        lines: &[String],
+        _enabled: bool,
+    ) -> Result<()> {
+        if section != MenuSection::Local {
+            return Ok(());
+        }
+        let count = lines.len().to_string();
+        self.change(move |_, label| label.set_text(&count))
+    }
+
+    fn set_label(&mut self, _label: MenuLabel, _text: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_advisories(&mut self, _messages: &[String]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Creates the widget on the GTK thread. Returns whether the compositor
+/// supports the layer shell.
+#[cfg(feature = "layer-shell-gtk4")]
+fn show(corner: Corner, margin: i32, clicks_tx: Sender<u32>) -> bool {
+    use gtk4::prelude::*;
+
+    if !gtk4_layer_shell::is_supported() {
+        return false;
+    }
+
+    let window = gtk4::Window::new();
+    window.init_layer_shell();
+    window.set_layer(Layer::Overlay);
+    window.set_keyboard_mode(KeyboardMode::None);
+    window.set_namespace(NAMESPACE);
+    for edge in edges(corner) {
+        window.set_anchor(edge, true);
+        window.set_margin(edge, margin);
+    }
+
+    let css_provider = gtk4::CssProvider::new();
+    css_provider.load_from_data(CSS);
+    if let Some(display) = gtk4::gdk::Display::default() {
+        gtk4::style_context_add_provider_for_display(
+            &display,
+            &css_provider,
+            gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
+    }
+
+    let label = gtk4::Label::new(Some("0"));
+    label.add_css_class(WIDGET_CLASS);
+    label.add_css_class(style_class(IconKind::NoUpdates));
+
+    let click = gtk4::GestureClick::new();
+    // Reports every button, not only the primary one.
+    click.set_button(0);
+    click.connect_pressed(move |click, _, _, _| {
+        let _ = clicks_tx.send(click.current_button());
+    });
+    label.add_controller(click);
+
+    window.set_child(Some(&label));
+    window.present();
+    WIDGET.set(Some((window, label)));
+    true
+}
+
+/// Shows the widget with gtk4-layer-shell on the GTK thread until the app
+/// shuts down, or until it fails and has to be restarted. Falls back to the
+/// D-Bus tray icon if the compositor doesn't support the layer shell.
+#[cfg(feature = "layer-shell-gtk4")]
+pub fn run(
+    mut config: Config,
+    app_tx: Sender<Event>,
+    updates: Arc<Mutex<Receiver<StateUpdate>>>,
+    color_schemes: Arc<Mutex<Receiver<ColorScheme>>>,
+) {
+    use std::{sync::mpsc::RecvTimeoutError, time::Duration};
+
+    use crate::{sni, supervisor};
+
+    /// How often clicks are looked at while waiting for state updates.
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    let (clicks_tx, clicks) = channel();
+    let (shown_tx, shown) = channel();
+    let corner = config.widget.corner;
+    let margin = config.widget.margin;
+    let shown = crate::gtk4_thread::invoke(move || {
+        let _ = shown_tx.send(show(corner, margin, clicks_tx));
+    })
+    .map(|()| shown.recv().unwrap_or(false));
+    match shown {
+        Ok(true) => {}
+        Ok(false) => {
+            warn!("The compositor doesn't support the layer shell, showing a tray icon instead");
+            sni::run(config, app_tx, updates, color_schemes);
+            return;
+        }
+        Err(e) => {
+            error!("{:#}, showing a tray icon instead", e);
+            sni::run(config, app_tx, updates, color_schemes);
+            return;
+        }
+    }
+
+    let mut widget = Widget {
+        current_icon: IconKind::NoUpdates,
+    };
+
+    let mut state = TrayState::new(&config);
+    loop {
+        while let Ok(button) = clicks.try_recv() {
+            handle_click(button, &mut config, &state.status, &app_tx);
+        }
+
+        // The colors don't depend on a panel's color scheme.
+        while color_schemes.lock().unwrap().try_recv().is_ok() {}
+
+        let update = supervisor::recv_timeout(&updates.lock().unwrap(), POLL_INTERVAL);
+        match update {
+            Ok(update) => match state.apply(&mut widget, update, &mut config) {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(e) => {
+                    // Ends this run of the tray thread, so that it is
+                    // restarted.
+                    error!("Failed to update widget: {:#}", e);
+                    return;
+                }
+            },
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        state.refresh_elapsed(&mut widget, &config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_state_has_a_color() {
+        let icons = [
+            IconKind::NoUpdates,
+            IconKind::Updates,
+            IconKind::Warning,
+            IconKind::Critical,
+            IconKind::Checking,
+            IconKind::Updating,
+            IconKind::Downloaded,
+            IconKind::News,
+            IconKind::RebootRequired,
+            IconKind::Paused,
+            IconKind::Offline,
+            IconKind::CheckFailed,
+        ];
+
+        for icon in icons {
+            let selector = format!(".{}.{}", WIDGET_CLASS, style_class(icon));
+            assert!(
+                CSS.contains(&format!("{},", selector))
+                    || CSS.contains(&format!("{} {{", selector)),
+                "no color for {:?}",
+                icon
+            );
+        }
+    }
+}